@@ -1,18 +1,25 @@
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 
-use nix::unistd::{chown, Group, User};
+use nix::unistd::{chown, Gid, Uid};
 
 use tokio::fs::{create_dir, remove_dir_all};
 use tracing::{span, Span};
 
-use crate::action::{Action, ActionDescription, ActionErrorKind, ActionState};
-use crate::action::{ActionError, StatefulAction};
+use crate::action::{
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionErrorKind, ActionState,
+};
+use crate::action::{ActionError, CancellationToken, Journal, Progress, StatefulAction};
+
+use super::{find_gid, find_uid, path_dependencies, path_drift, restore_path};
 
 /** Create a directory at the given location, optionally with an owning user, group, and mode.
 
 If `force_prune_on_revert` is set, the folder will always be deleted on
 [`revert`](CreateDirectory::revert).
+
+If a `root` is given, the owning user and group are looked up in the `etc/passwd` and `etc/group` files inside of it
+instead of on the host.
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct CreateDirectory {
@@ -21,6 +28,8 @@ pub struct CreateDirectory {
     group: Option<String>,
     mode: Option<u32>,
     force_prune_on_revert: bool,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl CreateDirectory {
@@ -31,6 +40,7 @@ impl CreateDirectory {
         group: impl Into<Option<String>>,
         mode: impl Into<Option<u32>>,
         force_prune_on_revert: bool,
+        root: Option<PathBuf>,
     ) -> Result<StatefulAction<Self>, ActionError> {
         let path = path.as_ref().to_path_buf();
         let user = user.into();
@@ -51,35 +61,33 @@ impl CreateDirectory {
             // Does it have the right user/group?
             if let Some(user) = &user {
                 // If the file exists, the user must also exist to be correct.
-                let expected_uid = User::from_name(user.as_str())
-                    .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+                let expected_uid = find_uid(user, root.as_deref())
+                    .await
                     .map_err(Self::error)?
                     .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
-                    .map_err(Self::error)?
-                    .uid;
+                    .map_err(Self::error)?;
                 let found_uid = metadata.uid();
-                if found_uid != expected_uid.as_raw() {
+                if found_uid != expected_uid {
                     return Err(Self::error(ActionErrorKind::PathUserMismatch(
                         path.clone(),
                         found_uid,
-                        expected_uid.as_raw(),
+                        expected_uid,
                     )));
                 }
             }
             if let Some(group) = &group {
                 // If the file exists, the group must also exist to be correct.
-                let expected_gid = Group::from_name(group.as_str())
-                    .map_err(|e| ActionErrorKind::GettingGroupId(group.clone(), e))
+                let expected_gid = find_gid(group, root.as_deref())
+                    .await
                     .map_err(Self::error)?
                     .ok_or_else(|| ActionErrorKind::NoUser(group.clone()))
-                    .map_err(Self::error)?
-                    .gid;
+                    .map_err(Self::error)?;
                 let found_gid = metadata.gid();
-                if found_gid != expected_gid.as_raw() {
+                if found_gid != expected_gid {
                     return Err(Self::error(ActionErrorKind::PathGroupMismatch(
                         path.clone(),
                         found_gid,
-                        expected_gid.as_raw(),
+                        expected_gid,
                     )));
                 }
             }
@@ -97,8 +105,11 @@ impl CreateDirectory {
                 group,
                 mode,
                 force_prune_on_revert,
+                root,
             },
             state: action_state,
+            retry: None,
+            attempts: 0,
         })
     }
 }
@@ -126,41 +137,53 @@ impl Action for CreateDirectory {
         )
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(path_dependencies(
+            &self.path,
+            self.user.as_deref(),
+            self.group.as_deref(),
+        ))
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user,
             group,
             mode,
             force_prune_on_revert: _,
+            root,
         } = self;
 
         let gid = if let Some(group) = group {
-            Some(
-                Group::from_name(group.as_str())
-                    .map_err(|e| ActionErrorKind::GettingGroupId(group.clone(), e))
+            Some(Gid::from_raw(
+                find_gid(group, root.as_deref())
+                    .await
                     .map_err(Self::error)?
                     .ok_or(ActionErrorKind::NoGroup(group.clone()))
-                    .map_err(Self::error)?
-                    .gid,
-            )
+                    .map_err(Self::error)?,
+            ))
         } else {
             None
         };
         let uid = if let Some(user) = user {
-            Some(
-                User::from_name(user.as_str())
-                    .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+            Some(Uid::from_raw(
+                find_uid(user, root.as_deref())
+                    .await
                     .map_err(Self::error)?
                     .ok_or(ActionErrorKind::NoUser(user.clone()))
-                    .map_err(Self::error)?
-                    .uid,
-            )
+                    .map_err(Self::error)?,
+            ))
         } else {
             None
         };
@@ -183,6 +206,63 @@ impl Action for CreateDirectory {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            path,
+            user,
+            group,
+            mode,
+            force_prune_on_revert: _,
+            root,
+        } = self;
+
+        let drift = path_drift(
+            path,
+            true,
+            user.as_deref(),
+            group.as_deref(),
+            *mode,
+            root.as_deref(),
+        )
+        .await
+        .map_err(Self::error)?;
+        Ok(drift
+            .into_iter()
+            .map(|description| ActionDrift::new(self.tracing_synopsis(), description))
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        if !self.path.is_dir() {
+            return self.execute(cancel, journal, progress).await;
+        }
+        let Self {
+            path,
+            user,
+            group,
+            mode,
+            force_prune_on_revert: _,
+            root,
+        } = self;
+
+        restore_path(
+            path,
+            user.as_deref(),
+            group.as_deref(),
+            *mode,
+            root.as_deref(),
+        )
+        .await
+        .map_err(Self::error)
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -190,6 +270,7 @@ impl Action for CreateDirectory {
             group: _,
             mode: _,
             force_prune_on_revert,
+            root: _,
         } = &self;
         vec![ActionDescription::new(
             format!(
@@ -206,13 +287,19 @@ impl Action for CreateDirectory {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user: _,
             group: _,
             mode: _,
             force_prune_on_revert,
+            root: _,
         } = self;
 
         let is_empty = path
@@ -244,31 +331,123 @@ mod test {
     async fn creates_and_deletes_empty_directory() -> eyre::Result<()> {
         let temp_dir = tempfile::tempdir()?;
         let test_dir = temp_dir.path().join("creates_and_deletes_empty_directory");
-        let mut action = CreateDirectory::plan(test_dir.clone(), None, None, None, false).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, None, false, None).await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_dir.exists(), "Folder should have been deleted");
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn finds_owner_inside_root() -> eyre::Result<()> {
+        let root = tempfile::tempdir()?;
+        tokio::fs::create_dir(root.path().join("etc")).await?;
+        let uid = nix::unistd::getuid().as_raw();
+        let passwd = root.path().join("etc/passwd");
+        tokio::fs::write(
+            &passwd,
+            format!("nix-owner:x:{uid}:{uid}::/:/sbin/nologin\n"),
+        )
+        .await?;
+
+        let test_dir = root.path().join("finds_owner_inside_root");
+        let mut action = CreateDirectory::plan(
+            test_dir.clone(),
+            String::from("nix-owner"),
+            None,
+            None,
+            false,
+            Some(root.path().to_path_buf()),
+        )
+        .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert!(action.try_verify().await?.is_empty());
+
+        let other = uid + 1;
+        tokio::fs::write(
+            &passwd,
+            format!("nix-owner:x:{other}:{other}::/:/sbin/nologin\n"),
+        )
+        .await?;
+        let drift = action.try_verify().await?;
+        assert_eq!(drift.len(), 1);
+        assert!(drift[0].description.contains(&format!("(UID {other})")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn does_not_start_once_cancelled() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_dir = temp_dir.path().join("does_not_start_once_cancelled");
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, None, false, None).await?;
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = action
+            .try_execute(&cancel, &Journal::default(), &Progress::default())
+            .await
+            .unwrap_err();
+
+        assert!(err.is_cancelled());
+        assert_eq!(action.state, ActionState::Uncompleted);
+        assert!(!test_dir.exists(), "Folder should not have been created");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn creates_and_deletes_populated_directory_if_prune_true() -> eyre::Result<()> {
         let temp_dir = tempfile::tempdir()?;
         let test_dir = temp_dir
             .path()
             .join("creates_and_deletes_populated_directory_if_prune_true");
-        let mut action = CreateDirectory::plan(test_dir.clone(), None, None, None, true).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, None, true, None).await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let stub_file = test_dir.as_path().join("stub");
         tokio::fs::write(stub_file, "More content").await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_dir.exists(), "Folder should have been deleted");
 
@@ -281,18 +460,111 @@ mod test {
         let test_dir = temp_dir
             .path()
             .join("creates_and_leaves_populated_directory_if_prune_false");
-        let mut action = CreateDirectory::plan(test_dir.clone(), None, None, None, false).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, None, false, None).await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let stub_file = test_dir.as_path().join("stub");
         tokio::fs::write(&stub_file, "More content").await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(test_dir.exists(), "Folder should not have been deleted");
         assert!(stub_file.exists(), "Folder should not have been deleted");
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn verifies_and_detects_drift() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_dir = temp_dir.path().join("verifies_and_detects_drift");
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, 0o755, false, None).await?;
+
+        assert!(
+            action.try_verify().await?.is_empty(),
+            "Nothing was done yet"
+        );
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        assert!(action.try_verify().await?.is_empty());
+
+        tokio::fs::set_permissions(&test_dir, PermissionsExt::from_mode(0o700)).await?;
+        assert_eq!(action.try_verify().await?.len(), 1);
+
+        tokio::fs::remove_dir(&test_dir).await?;
+        let drift = action.try_verify().await?;
+        assert_eq!(drift.len(), 1);
+        assert!(drift[0].description.contains("is missing"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn repairs_mode_and_missing_directory() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_dir = temp_dir.path().join("repairs_mode_and_missing_directory");
+        let mut action =
+            CreateDirectory::plan(test_dir.clone(), None, None, 0o755, false, None).await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        tokio::fs::set_permissions(&test_dir, PermissionsExt::from_mode(0o700)).await?;
+        assert_eq!(
+            action
+                .try_repair(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default()
+                )
+                .await?
+                .len(),
+            1
+        );
+        assert!(action.try_verify().await?.is_empty());
+
+        tokio::fs::remove_dir(&test_dir).await?;
+        assert_eq!(
+            action
+                .try_repair(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default()
+                )
+                .await?
+                .len(),
+            1
+        );
+        assert!(test_dir.is_dir());
+        assert!(action.try_verify().await?.is_empty());
+
+        Ok(())
+    }
 }
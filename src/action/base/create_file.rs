@@ -11,9 +11,12 @@ use tokio::{
 };
 
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
 
+use super::{path_dependencies, path_drift};
+
 /** Create a file at the given location with the provided `buf`,
 optionally with an owning user, group, and mode.
 
@@ -171,12 +174,25 @@ impl Action for CreateFile {
         span
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(path_dependencies(
+            &self.path,
+            self.user.as_deref(),
+            self.group.as_deref(),
+        ))
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user,
@@ -240,6 +256,55 @@ impl Action for CreateFile {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            path,
+            user,
+            group,
+            mode,
+            buf,
+            force: _,
+        } = self;
+
+        let mut drift = path_drift(path, false, user.as_deref(), group.as_deref(), *mode, None)
+            .await
+            .map_err(Self::error)?;
+        if path.is_file() {
+            let discovered_buf = tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| ActionErrorKind::Read(path.clone(), e))
+                .map_err(Self::error)?;
+            if discovered_buf != *buf {
+                drift.push(format!(
+                    "`{}` has different content than was written",
+                    path.display()
+                ));
+            }
+        }
+        Ok(drift
+            .into_iter()
+            .map(|description| ActionDrift::new(self.tracing_synopsis(), description))
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        // The file was created by us, so any edits to it are discarded
+        if self.path.is_file() {
+            remove_file(&self.path)
+                .await
+                .map_err(|e| ActionErrorKind::Remove(self.path.clone(), e))
+                .map_err(Self::error)?;
+        }
+        self.execute(cancel, journal, progress).await
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -257,7 +322,12 @@ impl Action for CreateFile {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user: _,
@@ -289,9 +359,21 @@ mod test {
         let mut action =
             CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -307,11 +389,23 @@ mod test {
         let mut action =
             CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         write(test_file.as_path(), "More content").await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -338,9 +432,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -451,9 +557,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -491,4 +609,28 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn verifies_and_detects_edits() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("verifies_and_detects_edits");
+        let mut action =
+            CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        assert!(action.try_verify().await?.is_empty());
+
+        write(test_file.as_path(), "More content").await?;
+
+        assert_eq!(action.try_verify().await?.len(), 1);
+
+        Ok(())
+    }
 }
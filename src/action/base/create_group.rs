@@ -1,30 +1,66 @@
+use std::path::PathBuf;
+
 use nix::unistd::Group;
 use target_lexicon::OperatingSystem;
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag};
+use crate::action::{
+    ActionDrift, ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress,
+};
 use crate::execute_command;
+use crate::os::etc_files;
+
+use super::find_gid;
 
-use crate::action::{Action, ActionDescription, StatefulAction};
+use crate::action::{Action, ActionDependency, ActionDescription, StatefulAction};
 
 /**
 Create an operating system level user group
+
+If a `root` is given, the group is added to the `etc/group` file inside of it instead of using the host's tools.
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct CreateGroup {
     name: String,
     gid: u32,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl CreateGroup {
     #[tracing::instrument(level = "debug", skip_all)]
-    pub fn plan(name: String, gid: u32) -> Result<StatefulAction<Self>, ActionError> {
+    pub async fn plan(
+        name: String,
+        gid: u32,
+        root: Option<PathBuf>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
         let this = Self {
             name: name.clone(),
             gid,
+            root: root.clone(),
         };
 
+        if let Some(root) = &root {
+            // Ensure group does not exists
+            if let Some(group) = etc_files::find_group(root, &name)
+                .await
+                .map_err(Self::error)?
+            {
+                if group.gid != gid {
+                    return Err(Self::error(ActionErrorKind::GroupGidMismatch(
+                        name.clone(),
+                        group.gid,
+                        gid,
+                    )));
+                }
+
+                tracing::debug!("Creating group `{}` already complete", this.name);
+                return Ok(StatefulAction::completed(this));
+            }
+            return Ok(StatefulAction::uncompleted(this));
+        }
+
         match OperatingSystem::host() {
             OperatingSystem::MacOSX { .. } | OperatingSystem::Darwin => (),
             _ => {
@@ -66,8 +102,16 @@ impl Action for CreateGroup {
     fn tracing_synopsis(&self) -> String {
         format!("Create group `{}` (GID {})", self.name, self.gid)
     }
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(vec![ActionDependency::Group(self.name.clone())])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
-        let Self { name: _, gid: _ } = &self;
+        let Self {
+            name: _,
+            gid: _,
+            root: _,
+        } = &self;
         vec![ActionDescription::new(
             self.tracing_synopsis(),
             vec![format!(
@@ -86,8 +130,20 @@ impl Action for CreateGroup {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
-        let Self { name, gid } = self;
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let Self { name, gid, root } = self;
+
+        if let Some(root) = root {
+            etc_files::add_group(root, name, *gid)
+                .await
+                .map_err(Self::error)?;
+            return Ok(());
+        }
 
         use OperatingSystem;
         match OperatingSystem::host() {
@@ -110,6 +166,7 @@ impl Action for CreateGroup {
                             name,
                         ])
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -121,6 +178,7 @@ impl Action for CreateGroup {
                             .process_group(0)
                             .args(["-g", &gid.to_string(), "--system", name])
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -130,6 +188,7 @@ impl Action for CreateGroup {
                             .process_group(0)
                             .args(["-g", &gid.to_string(), "--system", name])
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -142,8 +201,90 @@ impl Action for CreateGroup {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self { name, gid, root } = self;
+
+        let discovered_gid = find_gid(name, root.as_deref()).await.map_err(Self::error)?;
+        let drift = match discovered_gid {
+            Some(discovered_gid) if discovered_gid == *gid => return Ok(vec![]),
+            Some(discovered_gid) => {
+                format!("Group `{name}` has GID {discovered_gid}, {gid} was expected")
+            },
+            None => format!("Group `{name}` no longer exists"),
+        };
+        Ok(vec![ActionDrift::new(self.tracing_synopsis(), drift)])
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let Self { name, gid, root } = self;
+
+        match find_gid(name, root.as_deref()).await.map_err(Self::error)? {
+            None => return self.execute(cancel, journal, progress).await,
+            Some(discovered_gid) if discovered_gid == *gid => return Ok(()),
+            Some(_) => (),
+        }
+
+        if let Some(root) = root {
+            etc_files::set_group_gid(root, name, *gid)
+                .await
+                .map_err(Self::error)?;
+            return Ok(());
+        }
+
+        match OperatingSystem::host() {
+            OperatingSystem::MacOSX {
+                major: _,
+                minor: _,
+                patch: _,
+            }
+            | OperatingSystem::Darwin => {
+                execute_command(
+                    Command::new("/usr/bin/dscl")
+                        .process_group(0)
+                        .args([
+                            ".",
+                            "-create",
+                            &format!("/Groups/{name}"),
+                            "PrimaryGroupID",
+                            &gid.to_string(),
+                        ])
+                        .stdin(std::process::Stdio::null()),
+                    cancel,
+                )
+                .await
+                .map_err(Self::error)?;
+            },
+            _ => {
+                if which::which("groupmod").is_ok() {
+                    execute_command(
+                        Command::new("groupmod")
+                            .process_group(0)
+                            .args(["-g", &gid.to_string(), name])
+                            .stdin(std::process::Stdio::null()),
+                        cancel,
+                    )
+                    .await
+                    .map_err(Self::error)?;
+                } else {
+                    return Err(Self::error(
+                        ActionErrorKind::MissingGroupModificationCommand,
+                    ));
+                }
+            },
+        };
+
+        Ok(())
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
-        let Self { name, gid } = &self;
+        let Self { name, gid, root: _ } = &self;
         vec![ActionDescription::new(
             format!("Delete group `{name}` (GID {gid})"),
             vec![format!(
@@ -153,8 +294,20 @@ impl Action for CreateGroup {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
-        let Self { name, gid: _ } = self;
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let Self { name, gid: _, root } = self;
+
+        if let Some(root) = root {
+            etc_files::remove_group(root, name)
+                .await
+                .map_err(Self::error)?;
+            return Ok(());
+        }
 
         use OperatingSystem;
         match OperatingSystem::host() {
@@ -168,6 +321,7 @@ impl Action for CreateGroup {
                     Command::new("/usr/bin/dscl")
                         .args([".", "-delete", &format!("/Groups/{name}")])
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -179,6 +333,7 @@ impl Action for CreateGroup {
                             .process_group(0)
                             .arg(name)
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -188,6 +343,7 @@ impl Action for CreateGroup {
                             .process_group(0)
                             .arg(name)
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -200,3 +356,49 @@ impl Action for CreateGroup {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn repairs_gid_inside_root() -> eyre::Result<()> {
+        let root = tempfile::tempdir()?;
+        tokio::fs::create_dir(root.path().join("etc")).await?;
+        tokio::fs::write(root.path().join("etc/group"), "root:x:0:\n").await?;
+
+        let mut action =
+            CreateGroup::plan("nixbld".into(), 30_000, Some(root.path().to_path_buf())).await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert!(action.try_verify().await?.is_empty());
+
+        tokio::fs::write(
+            root.path().join("etc/group"),
+            "root:x:0:\nnixbld:x:30001:nixbld1\n",
+        )
+        .await?;
+        assert_eq!(action.try_verify().await?.len(), 1);
+
+        let drift = action
+            .try_repair(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert_eq!(drift.len(), 1);
+        assert!(action.try_verify().await?.is_empty());
+        assert_eq!(
+            tokio::fs::read_to_string(root.path().join("etc/group")).await?,
+            "root:x:0:\nnixbld:x:30000:nixbld1\n"
+        );
+
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
 use nix::unistd::{chown, Group, User};
 
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
 use rand::Rng;
 use std::{
@@ -15,6 +16,8 @@ use tokio::{
 };
 use tracing::{span, Span};
 
+use super::{path_dependencies, path_drift, restore_path};
+
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
 pub enum Position {
     Beginning,
@@ -176,12 +179,25 @@ impl Action for CreateOrInsertIntoFile {
         span
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(path_dependencies(
+            &self.path,
+            self.user.as_deref(),
+            self.group.as_deref(),
+        ))
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user,
@@ -311,6 +327,68 @@ impl Action for CreateOrInsertIntoFile {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            path,
+            user,
+            group,
+            mode,
+            buf,
+            position: _,
+        } = self;
+
+        let mut drift = path_drift(path, false, user.as_deref(), group.as_deref(), *mode, None)
+            .await
+            .map_err(Self::error)?;
+        if path.is_file() {
+            let discovered_buf = tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| ActionErrorKind::Read(path.clone(), e))
+                .map_err(Self::error)?;
+            if !discovered_buf.contains(buf.as_str()) {
+                drift.push(format!(
+                    "`{}` no longer contains the Nix related fragment",
+                    path.display()
+                ));
+            }
+        }
+        Ok(drift
+            .into_iter()
+            .map(|description| ActionDrift::new(self.tracing_synopsis(), description))
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        if self.path.is_file() {
+            let discovered_buf = tokio::fs::read_to_string(&self.path)
+                .await
+                .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
+                .map_err(Self::error)?;
+            if discovered_buf.contains(self.buf.as_str()) {
+                // Inserting again would duplicate the fragment, only the owner or mode drifted
+                let Self {
+                    path,
+                    user,
+                    group,
+                    mode,
+                    buf: _,
+                    position: _,
+                } = self;
+                return restore_path(path, user.as_deref(), group.as_deref(), *mode, None)
+                    .await
+                    .map_err(Self::error);
+            }
+        }
+        self.execute(cancel, journal, progress).await
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -330,7 +408,12 @@ impl Action for CreateOrInsertIntoFile {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             user: _,
@@ -406,9 +489,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -435,9 +530,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(test_file.exists(), "File should have not been deleted");
 
@@ -476,9 +583,21 @@ mod test {
             )
             .await?;
 
-            action.try_execute().await?;
-
-            action.try_revert().await?;
+            action
+                .try_execute(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default(),
+                )
+                .await?;
+
+            action
+                .try_revert(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default(),
+                )
+                .await?;
 
             assert!(test_file.exists(), "File should have not been deleted");
             let after_revert_content = read_to_string(&test_file).await?;
@@ -511,9 +630,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(test_file.exists(), "File should have not been deleted");
 
@@ -540,9 +671,21 @@ mod test {
         )
         .await?;
 
-        action.try_execute().await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -580,4 +723,74 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn repairs_removed_fragment_without_duplicating() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir
+            .path()
+            .join("repairs_removed_fragment_without_duplicating");
+        write(test_file.as_path(), "Original\n").await?;
+        let mut action = CreateOrInsertIntoFile::plan(
+            test_file.clone(),
+            None,
+            None,
+            0o644,
+            "Test\n".into(),
+            Position::End,
+        )
+        .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        // Nothing drifted, nothing is repaired
+        assert!(action
+            .try_repair(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default()
+            )
+            .await?
+            .is_empty());
+        assert_eq!(read_to_string(&test_file).await?, "Original\nTest\n");
+
+        // An OS upgrade replaced the file
+        write(test_file.as_path(), "Upgraded\n").await?;
+        assert_eq!(
+            action
+                .try_repair(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default()
+                )
+                .await?
+                .len(),
+            1
+        );
+        assert_eq!(read_to_string(&test_file).await?, "Upgraded\nTest\n");
+
+        // Only the mode drifted
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(0o600)).await?;
+        assert_eq!(
+            action
+                .try_repair(
+                    &CancellationToken::new(),
+                    &Journal::default(),
+                    &Progress::default()
+                )
+                .await?
+                .len(),
+            1
+        );
+        assert_eq!(read_to_string(&test_file).await?, "Upgraded\nTest\n");
+        assert!(action.try_verify().await?.is_empty());
+
+        Ok(())
+    }
 }
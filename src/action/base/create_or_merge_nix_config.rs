@@ -12,12 +12,28 @@ use tokio::{
 use tracing::{span, Span};
 
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
 
-/// The `nix.conf` configuration names that are safe to merge.
-// FIXME(@cole-h): make configurable by downstream users?
-const MERGEABLE_CONF_NAMES: &[&str] = &["experimental-features"];
+use super::path_drift;
+
+/// The `nix.conf` settings which are lists (of space separated values), merged with any existing values
+///
+/// Every `extra-*` setting is a list as well, see [`is_list_conf_name`].
+const LIST_CONF_NAMES: &[&str] = &[
+    "allowed-users",
+    "experimental-features",
+    "hashed-mirrors",
+    "nix-path",
+    "plugin-files",
+    "secret-key-files",
+    "substituters",
+    "system-features",
+    "trusted-public-keys",
+    "trusted-substituters",
+    "trusted-users",
+];
 const NIX_CONF_MODE: u32 = 0o664;
 const NIX_CONF_COMMENT_CHAR: char = '#';
 
@@ -26,7 +42,7 @@ const NIX_CONF_COMMENT_CHAR: char = '#';
 pub enum CreateOrMergeNixConfigError {
     #[error(transparent)]
     ParseNixConfig(#[from] nix_config_parser::ParseError),
-    #[error("Could not merge Nix configuration for key(s) {}; consider removing them from `{1}` in your editor, or removing your existing configuration with `rm {1}`",
+    #[error("Could not merge Nix configuration for key(s) {}; consider removing them from `{1}` in your editor, removing your existing configuration with `rm {1}`, or choosing another `--nix-conf-conflict-policy`",
         .0
         .iter()
         .map(|v| format!("`{v}`"))
@@ -35,6 +51,27 @@ pub enum CreateOrMergeNixConfigError {
     UnmergeableConfig(Vec<String>, std::path::PathBuf),
 }
 
+/// If the `nix.conf` setting `name` is a list, which is merged with any existing values
+pub fn is_list_conf_name(name: &str) -> bool {
+    LIST_CONF_NAMES.contains(&name) || name.starts_with("extra-")
+}
+
+/// What to do when a `nix.conf` setting which is not a list (see [`is_list_conf_name`]) already has a different value
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum NixConfigConflictPolicy {
+    /// Keep the existing value
+    KeepExisting,
+    /// Replace the existing value, which is restored when reverting
+    PreferInstaller,
+    /// Fail, naming the conflicting settings
+    #[default]
+    Fail,
+}
+
 impl Into<ActionErrorKind> for CreateOrMergeNixConfigError {
     fn into(self) -> ActionErrorKind {
         ActionErrorKind::Custom(Box::new(self))
@@ -42,10 +79,24 @@ impl Into<ActionErrorKind> for CreateOrMergeNixConfigError {
 }
 
 /// Create or merge an existing `nix.conf` at the specified path.
+///
+/// Reverting removes only the settings (or for merged lists, the values of them) which were added, the file is only
+/// deleted if nothing else remains in it.
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct CreateOrMergeNixConfig {
     pub(crate) path: PathBuf,
     pending_nix_config: NixConfig,
+    /// The settings, and the values of them, which were added to the file, once they have been
+    ///
+    /// Receipts recorded by older versions lack this, all of the `pending_nix_config` is assumed to have been added.
+    #[serde(default)]
+    added_nix_config: Option<NixConfig>,
+    /// The existing values of settings which were replaced, restored when reverting
+    #[serde(default)]
+    replaced_nix_config: NixConfig,
+    /// What to do when a setting which is not a list already has a different value
+    #[serde(default)]
+    conflict_policy: NixConfigConflictPolicy,
 }
 
 impl CreateOrMergeNixConfig {
@@ -53,17 +104,25 @@ impl CreateOrMergeNixConfig {
     pub async fn plan(
         path: impl AsRef<Path>,
         pending_nix_config: NixConfig,
+        conflict_policy: NixConfigConflictPolicy,
     ) -> Result<StatefulAction<Self>, ActionError> {
         let path = path.as_ref().to_path_buf();
 
         let this = Self {
             path,
             pending_nix_config,
+            // If everything is already set nothing is added
+            added_nix_config: Some(NixConfig::new()),
+            replaced_nix_config: NixConfig::new(),
+            conflict_policy,
         };
 
         if this.path.exists() {
-            let (merged_nix_config, _) =
-                Self::validate_existing_nix_config(&this.pending_nix_config, &this.path)?;
+            let (merged_nix_config, _) = Self::validate_existing_nix_config(
+                &this.pending_nix_config,
+                &this.path,
+                this.conflict_policy,
+            )?;
 
             if !merged_nix_config.settings().is_empty() {
                 return Ok(StatefulAction::uncompleted(this));
@@ -83,6 +142,7 @@ impl CreateOrMergeNixConfig {
         pending_nix_config: &NixConfig,
         existing_nix_config: &NixConfig,
         path: &Path,
+        conflict_policy: NixConfigConflictPolicy,
     ) -> Result<(NixConfig, NixConfig), CreateOrMergeNixConfigError> {
         let mut merged_nix_config = NixConfig::new();
         let mut unmergeable_config_names = Vec::new();
@@ -90,8 +150,9 @@ impl CreateOrMergeNixConfig {
         for (pending_conf_name, pending_conf_value) in pending_nix_config.settings() {
             if let Some(existing_conf_value) = existing_nix_config.settings().get(pending_conf_name)
             {
-                let pending_conf_value = pending_conf_value.split(' ').collect::<Vec<_>>();
-                let existing_conf_value = existing_conf_value.split(' ').collect::<Vec<_>>();
+                let pending_conf_value = pending_conf_value.split_whitespace().collect::<Vec<_>>();
+                let existing_conf_value =
+                    existing_conf_value.split_whitespace().collect::<Vec<_>>();
 
                 if pending_conf_value
                     .iter()
@@ -101,20 +162,33 @@ impl CreateOrMergeNixConfig {
                     // merged_nix_config will be empty and this will be marked as completed. We
                     // don't return early here because there may be more config options to
                     // check.
-                } else if MERGEABLE_CONF_NAMES.contains(&pending_conf_name.as_str()) {
-                    let mut merged_conf_value =
+                } else if is_list_conf_name(pending_conf_name) {
+                    let mut merged_conf_value: Vec<&str> =
                         Vec::with_capacity(pending_conf_value.len() + existing_conf_value.len());
-                    merged_conf_value.extend(pending_conf_value);
-                    merged_conf_value.extend(existing_conf_value);
-                    merged_conf_value.dedup();
-                    let merged_conf_value = merged_conf_value.join(" ");
-                    let merged_conf_value = merged_conf_value.trim();
+                    for value in pending_conf_value.into_iter().chain(existing_conf_value) {
+                        if !merged_conf_value.contains(&value) {
+                            merged_conf_value.push(value);
+                        }
+                    }
 
                     merged_nix_config
                         .settings_mut()
-                        .insert(pending_conf_name.to_owned(), merged_conf_value.to_owned());
+                        .insert(pending_conf_name.to_owned(), merged_conf_value.join(" "));
                 } else {
-                    unmergeable_config_names.push(pending_conf_name.to_owned());
+                    match conflict_policy {
+                        NixConfigConflictPolicy::KeepExisting => tracing::debug!(
+                            "Keeping the existing `{pending_conf_name}` in `{}`",
+                            path.display()
+                        ),
+                        NixConfigConflictPolicy::PreferInstaller => {
+                            merged_nix_config
+                                .settings_mut()
+                                .insert(pending_conf_name.to_owned(), pending_conf_value.join(" "));
+                        },
+                        NixConfigConflictPolicy::Fail => {
+                            unmergeable_config_names.push(pending_conf_name.to_owned())
+                        },
+                    }
                 }
             } else {
                 merged_nix_config
@@ -136,6 +210,7 @@ impl CreateOrMergeNixConfig {
     fn validate_existing_nix_config(
         pending_nix_config: &NixConfig,
         path: &Path,
+        conflict_policy: NixConfigConflictPolicy,
     ) -> Result<(NixConfig, NixConfig), ActionError> {
         let path = path.to_path_buf();
         let metadata = path
@@ -167,11 +242,157 @@ impl CreateOrMergeNixConfig {
             &pending_nix_config,
             &existing_nix_config,
             &path,
+            conflict_policy,
         )
         .map_err(Self::error)?;
 
         Ok((merged_nix_config, existing_nix_config))
     }
+
+    /// The values of each of the `merged_nix_config` which are not in the `existing_nix_config`
+    fn added_values(
+        pending_nix_config: &NixConfig,
+        merged_nix_config: &NixConfig,
+        existing_nix_config: Option<&NixConfig>,
+    ) -> NixConfig {
+        let mut added_nix_config = NixConfig::new();
+        for name in merged_nix_config.settings().keys() {
+            let Some(pending_conf_value) = pending_nix_config.settings().get(name) else {
+                continue;
+            };
+            let existing_conf_value = existing_nix_config
+                .and_then(|existing_nix_config| existing_nix_config.settings().get(name))
+                .map(|value| value.split_whitespace().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let added_conf_value = pending_conf_value
+                .split_whitespace()
+                .filter(|value| !existing_conf_value.contains(value))
+                .collect::<Vec<_>>();
+            if !added_conf_value.is_empty() {
+                added_nix_config
+                    .settings_mut()
+                    .insert(name.clone(), added_conf_value.join(" "));
+            }
+        }
+        added_nix_config
+    }
+
+    /// Replace the contents of `path` with `contents`, atomically so an interrupted write never leaves a partial file
+    async fn write_nix_config(path: &Path, contents: &str) -> Result<(), ActionError> {
+        // Create a temporary file in the same directory as the one
+        // that the final file goes in, so that we can rename it
+        // atomically
+        let parent_dir = path.parent().expect("File must be in a directory");
+        let mut temp_file_path = parent_dir.to_owned();
+        {
+            let mut rng = rand::thread_rng();
+            temp_file_path.push(format!("nix-installer-tmp.{}", rng.gen::<u32>()));
+        }
+        let mut temp_file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            // If the file is created, ensure that it has harmless
+            // permissions regardless of whether the mode will be
+            // changed later (if we ever create setuid executables,
+            // they should only become setuid once they are owned by
+            // the appropriate user)
+            .mode(0o600)
+            .open(&temp_file_path)
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Open(temp_file_path.clone(), e)))?;
+
+        temp_file
+            .write_all(contents.as_bytes())
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Write(temp_file_path.clone(), e)))?;
+        tokio::fs::set_permissions(&temp_file_path, PermissionsExt::from_mode(NIX_CONF_MODE))
+            .await
+            .map_err(|e| {
+                Self::error(ActionErrorKind::SetPermissions(
+                    NIX_CONF_MODE,
+                    path.to_owned(),
+                    e,
+                ))
+            })?;
+        temp_file
+            .sync_all()
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Sync(temp_file_path.clone(), e)))?;
+        tokio::fs::rename(&temp_file_path, path)
+            .await
+            .map_err(|e| {
+                Self::error(ActionErrorKind::Rename(
+                    temp_file_path.to_owned(),
+                    path.to_owned(),
+                    e,
+                ))
+            })?;
+        Ok(())
+    }
+}
+
+/// Remove the `added_nix_config` (and the comment `nix-installer` marks what it wrote with) from the `nix.conf`
+/// contents `buf`, restoring the `replaced_nix_config`, returning what remains, or `None` if nothing does
+///
+/// Settings which had values merged into them keep the rest of their values, and any comments.
+fn remove_added_settings(
+    buf: &str,
+    added_nix_config: &NixConfig,
+    replaced_nix_config: &NixConfig,
+) -> Option<String> {
+    let mut remaining = Vec::new();
+    for line in buf.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("# Generated by") {
+            continue;
+        }
+        let setting = match trimmed.starts_with(NIX_CONF_COMMENT_CHAR) {
+            true => None,
+            false => trimmed.split_once('='),
+        };
+        let Some((name, value)) = setting else {
+            remaining.push(line.to_string());
+            continue;
+        };
+        let name = name.trim();
+        let Some(added_conf_value) = added_nix_config.settings().get(name) else {
+            remaining.push(line.to_string());
+            continue;
+        };
+
+        let (value, inline_comment) = match value.find(NIX_CONF_COMMENT_CHAR) {
+            Some(idx) => value.split_at(idx),
+            None => (value, ""),
+        };
+        let added_conf_value = added_conf_value.split_whitespace().collect::<Vec<_>>();
+        let mut remaining_conf_value = value
+            .split_whitespace()
+            .filter(|value| !added_conf_value.contains(value))
+            .collect::<Vec<_>>();
+        if remaining_conf_value.is_empty() {
+            if let Some(replaced_conf_value) = replaced_nix_config.settings().get(name) {
+                remaining_conf_value = replaced_conf_value.split_whitespace().collect();
+            }
+        }
+        match (remaining_conf_value.is_empty(), inline_comment.is_empty()) {
+            (true, true) => (),
+            // Only an inline comment remains, which is kept as a comment of its own
+            (true, false) => remaining.push(inline_comment.to_string()),
+            (false, true) => remaining.push(format!("{name} = {}", remaining_conf_value.join(" "))),
+            (false, false) => remaining.push(format!(
+                "{name} = {} {inline_comment}",
+                remaining_conf_value.join(" ")
+            )),
+        }
+    }
+
+    while remaining.last().is_some_and(|line| line.trim().is_empty()) {
+        remaining.pop();
+    }
+    if remaining.is_empty() {
+        return None;
+    }
+    Some(remaining.join("\n") + "\n")
 }
 
 #[async_trait::async_trait]
@@ -211,6 +432,10 @@ impl Action for CreateOrMergeNixConfig {
         span
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(vec![ActionDependency::path(&self.path)])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             self.tracing_synopsis(),
@@ -228,10 +453,18 @@ impl Action for CreateOrMergeNixConfig {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             pending_nix_config,
+            added_nix_config,
+            replaced_nix_config,
+            conflict_policy,
         } = self;
 
         if tracing::enabled!(tracing::Level::TRACE) {
@@ -247,37 +480,48 @@ impl Action for CreateOrMergeNixConfig {
             );
         }
 
-        // Create a temporary file in the same directory as the one
-        // that the final file goes in, so that we can rename it
-        // atomically
-        let parent_dir = path.parent().expect("File must be in a directory");
-        let mut temp_file_path = parent_dir.to_owned();
-        {
-            let mut rng = rand::thread_rng();
-            temp_file_path.push(format!("nix-installer-tmp.{}", rng.gen::<u32>()));
-        }
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            // If the file is created, ensure that it has harmless
-            // permissions regardless of whether the mode will be
-            // changed later (if we ever create setuid executables,
-            // they should only become setuid once they are owned by
-            // the appropriate user)
-            .mode(0o600)
-            .open(&temp_file_path)
-            .await
-            .map_err(|e| {
-                Self::error(ActionErrorKind::Open(temp_file_path.clone(), e))
-            })?;
-
         let (mut merged_nix_config, mut existing_nix_config) = if path.exists() {
             let (merged_nix_config, existing_nix_config) =
-                Self::validate_existing_nix_config(&pending_nix_config, &path)?;
+                Self::validate_existing_nix_config(&pending_nix_config, &path, *conflict_policy)?;
             (merged_nix_config, Some(existing_nix_config))
         } else {
             (pending_nix_config.clone(), None)
         };
+        let mut added = Self::added_values(
+            pending_nix_config,
+            &merged_nix_config,
+            existing_nix_config.as_ref(),
+        );
+        // When repairing, what was added before (and is still there) was added too
+        if let Some(added_nix_config) = added_nix_config {
+            for (name, value) in added_nix_config.settings() {
+                let added_conf_value = added.settings_mut().entry(name.clone()).or_default();
+                for value in value.split_whitespace() {
+                    if !added_conf_value
+                        .split_whitespace()
+                        .any(|added| added == value)
+                    {
+                        if !added_conf_value.is_empty() {
+                            added_conf_value.push(' ');
+                        }
+                        added_conf_value.push_str(value);
+                    }
+                }
+            }
+        }
+        let mut replaced = replaced_nix_config.clone();
+        if let Some(existing_nix_config) = &existing_nix_config {
+            for name in merged_nix_config.settings().keys() {
+                if is_list_conf_name(name) || replaced.settings().contains_key(name) {
+                    continue;
+                }
+                if let Some(existing_conf_value) = existing_nix_config.settings().get(name) {
+                    replaced
+                        .settings_mut()
+                        .insert(name.clone(), existing_conf_value.clone());
+                }
+            }
+        }
 
         let mut new_config = String::new();
 
@@ -364,25 +608,29 @@ impl Action for CreateOrMergeNixConfig {
 
                     let inline_comment = &setting_line[inline_comment_idx..];
 
-                    new_config.push_str(&comments);
-                    new_config.push('\n');
+                    if !comments.is_empty() {
+                        new_config.push_str(&comments);
+                        new_config.push('\n');
+                    }
                     new_config.push_str(name);
                     new_config.push_str(" = ");
 
                     if let Some(merged_value) = merged_nix_config.settings_mut().remove(name) {
                         new_config.push_str(&merged_value);
-                        new_config.push(' ');
                     } else {
                         new_config.push_str(value);
                     }
+                    new_config.push(' ');
 
                     new_config.push_str(inline_comment);
                     new_config.push('\n');
 
                     Some(name.clone())
                 } else {
-                    new_config.push_str(&comments);
-                    new_config.push('\n');
+                    if !comments.is_empty() {
+                        new_config.push_str(&comments);
+                        new_config.push('\n');
+                    }
                     new_config.push_str(setting_line);
                     new_config.push('\n');
 
@@ -421,58 +669,156 @@ impl Action for CreateOrMergeNixConfig {
             new_config.push('\n');
         }
 
-        temp_file
-            .write_all(new_config.as_bytes())
-            .await
-            .map_err(|e| Self::error(ActionErrorKind::Write(temp_file_path.clone(), e)))?;
-        tokio::fs::set_permissions(&temp_file_path, PermissionsExt::from_mode(NIX_CONF_MODE))
-            .await
-            .map_err(|e| {
-                Self::error(ActionErrorKind::SetPermissions(
-                    NIX_CONF_MODE,
-                    path.to_owned(),
-                    e,
-                ))
-            })?;
-        temp_file
-            .sync_all()
-            .await
-            .map_err(|e| Self::error(ActionErrorKind::Sync(temp_file_path.clone(), e)))?;
-        tokio::fs::rename(&temp_file_path, &path)
-            .await
-            .map_err(|e| {
-                Self::error(ActionErrorKind::Rename(
-                    temp_file_path.to_owned(),
-                    path.to_owned(),
-                    e,
-                ))
-            })?;
+        Self::write_nix_config(path, &new_config).await?;
+        *added_nix_config = Some(added);
+        *replaced_nix_config = replaced;
 
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            path,
+            pending_nix_config,
+            added_nix_config: _,
+            replaced_nix_config: _,
+            conflict_policy,
+        } = self;
+
+        let mut drift = path_drift(path, false, None, None, Some(NIX_CONF_MODE), None)
+            .await
+            .map_err(Self::error)?;
+        if path.is_file() {
+            let existing_nix_config = NixConfig::parse_file(path)
+                .map_err(CreateOrMergeNixConfigError::ParseNixConfig)
+                .map_err(Self::error)?;
+            for (pending_conf_name, pending_conf_value) in pending_nix_config.settings() {
+                let Some(existing_conf_value) =
+                    existing_nix_config.settings().get(pending_conf_name)
+                else {
+                    drift.push(format!(
+                        "`{pending_conf_name}` is no longer set in `{}`",
+                        path.display()
+                    ));
+                    continue;
+                };
+                // A different existing value was kept on purpose
+                if *conflict_policy == NixConfigConflictPolicy::KeepExisting
+                    && !is_list_conf_name(pending_conf_name)
+                {
+                    continue;
+                }
+                let existing_conf_value =
+                    existing_conf_value.split_whitespace().collect::<Vec<_>>();
+                if !pending_conf_value
+                    .split_whitespace()
+                    .all(|e| existing_conf_value.contains(&e))
+                {
+                    drift.push(format!(
+                        "`{pending_conf_name}` in `{}` no longer includes `{pending_conf_value}`",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        Ok(drift
+            .into_iter()
+            .map(|description| ActionDrift::new(self.tracing_synopsis(), description))
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        // Merging refuses a `nix.conf` with an unexpected mode
+        if self.path.is_file() {
+            tokio::fs::set_permissions(&self.path, PermissionsExt::from_mode(NIX_CONF_MODE))
+                .await
+                .map_err(|e| ActionErrorKind::SetPermissions(NIX_CONF_MODE, self.path.clone(), e))
+                .map_err(Self::error)?;
+        }
+        self.execute(cancel, journal, progress).await
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
-            pending_nix_config: _,
+            pending_nix_config,
+            added_nix_config,
+            replaced_nix_config,
+            conflict_policy: _,
         } = &self;
 
+        let added_nix_config = added_nix_config.as_ref().unwrap_or(pending_nix_config);
+        let mut explanation = vec![format!(
+            "Removed settings: {settings}",
+            settings = added_nix_config
+                .settings()
+                .iter()
+                .map(|(k, v)| format!("{k}=\"{v}\""))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )];
+        if !replaced_nix_config.settings().is_empty() {
+            explanation.push(format!(
+                "Restored settings: {settings}",
+                settings = replaced_nix_config
+                    .settings()
+                    .iter()
+                    .map(|(k, v)| format!("{k}=\"{v}\""))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+        explanation.push(format!(
+            "Delete file `{}` if nothing else remains in it",
+            path.display()
+        ));
         vec![ActionDescription::new(
-            format!("Delete file `{}`", path.display()),
-            vec![format!("Delete file `{}`", path.display())],
+            format!("Remove the added settings from `{}`", path.display()),
+            explanation,
         )]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
-            pending_nix_config: _,
+            pending_nix_config,
+            added_nix_config,
+            replaced_nix_config,
+            conflict_policy: _,
         } = self;
 
-        remove_file(&path)
+        let buf = tokio::fs::read_to_string(&path)
             .await
-            .map_err(|e| Self::error(ActionErrorKind::Remove(path.to_owned(), e)))?;
+            .map_err(|e| Self::error(ActionErrorKind::Read(path.to_owned(), e)))?;
+        match remove_added_settings(
+            &buf,
+            added_nix_config.as_ref().unwrap_or(pending_nix_config),
+            replaced_nix_config,
+        ) {
+            Some(remaining) => {
+                tracing::debug!(
+                    "Keeping `{}`, settings not added by `nix-installer` remain",
+                    path.display()
+                );
+                Self::write_nix_config(path, &remaining).await?;
+            },
+            None => remove_file(&path)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Remove(path.to_owned(), e)))?,
+        }
 
         Ok(())
     }
@@ -492,16 +838,30 @@ mod test {
         nix_config
             .settings_mut()
             .insert("experimental-features".into(), "ca-references".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let s = std::fs::read_to_string(&test_file)?;
         assert!(s.contains("# Generated by"));
         assert!(s.contains("ca-references"));
         assert!(NixConfig::parse_file(&test_file).is_ok());
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         assert!(!test_file.exists(), "File should have been deleted");
 
@@ -509,24 +869,36 @@ mod test {
     }
 
     #[tokio::test]
-    async fn creates_and_deletes_file_even_if_edited() -> eyre::Result<()> {
+    async fn creates_and_keeps_file_if_edited() -> eyre::Result<()> {
         let temp_dir = tempfile::TempDir::new()?;
-        let test_file = temp_dir
-            .path()
-            .join("creates_and_deletes_file_even_if_edited");
+        let test_file = temp_dir.path().join("creates_and_keeps_file_if_edited");
         let mut nix_config = NixConfig::new();
         nix_config
             .settings_mut()
             .insert("experimental-features".into(), "ca-references".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         write(test_file.as_path(), "More content").await?;
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        assert!(!test_file.exists(), "File should have been deleted");
+        assert_eq!(std::fs::read_to_string(&test_file)?, "More content\n");
 
         Ok(())
     }
@@ -546,13 +918,31 @@ mod test {
         nix_config
             .settings_mut()
             .insert("experimental-features".into(), "flakes".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
-
-        action.try_revert().await?;
-
-        assert!(!test_file.exists(), "File should have been deleted");
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        // Nothing was added, so it is left as it was
+        assert_eq!(
+            std::fs::read_to_string(&test_file)?,
+            format!("{test_content}\n")
+        );
 
         Ok(())
     }
@@ -578,9 +968,17 @@ mod test {
         nix_config
             .settings_mut()
             .insert("allow-dirty".into(), "false".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let s = std::fs::read_to_string(&test_file)?;
         assert!(s.contains("# Generated by"));
@@ -595,9 +993,25 @@ mod test {
         assert!(s.contains("warn-dirty = true"));
         assert!(NixConfig::parse_file(&test_file).is_ok());
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        assert!(!test_file.exists(), "File should have been deleted");
+        let reverted = NixConfig::parse_file(&test_file)?;
+        assert_eq!(
+            reverted.settings().get("experimental-features"),
+            Some(&"flakes".to_string())
+        );
+        assert_eq!(
+            reverted.settings().get("warn-dirty"),
+            Some(&"true".to_string())
+        );
+        assert_eq!(reverted.settings().len(), 2);
+        assert!(!std::fs::read_to_string(&test_file)?.contains("# Generated by"));
 
         Ok(())
     }
@@ -623,7 +1037,9 @@ mod test {
         nix_config
             .settings_mut()
             .insert("warn-dirty".into(), "false".into());
-        match CreateOrMergeNixConfig::plan(&test_file, nix_config).await {
+        match CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+            .await
+        {
             Err(err) => match err.kind() {
                 ActionErrorKind::Custom(e) => {
                     match e.downcast_ref::<CreateOrMergeNixConfigError>() {
@@ -666,9 +1082,17 @@ mod test {
         nix_config
             .settings_mut()
             .insert("experimental-features".into(), "ca-references".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let s = std::fs::read_to_string(&test_file)?;
         assert!(s.contains("# the following line should be warn-dirty = true\nwarn-dirty = true"));
@@ -680,9 +1104,21 @@ mod test {
         assert!(s.contains("ca-references"));
         assert!(NixConfig::parse_file(&test_file).is_ok());
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        assert!(!test_file.exists(), "File should have been deleted");
+        let s = std::fs::read_to_string(&test_file)?;
+        assert!(s.contains("# test 2\n# test\nexperimental-features = flakes # some inline comment about experimental-features\n"));
+        assert!(s.contains("# the following line should be warn-dirty = true\nwarn-dirty = true"));
+        assert!(s.contains("# this is an inline comment"));
+        assert!(s.contains("# this is an ungrouped comment\n# this too"));
+        assert!(!s.contains("ca-references"));
+        assert!(!s.contains("# Generated by"));
 
         Ok(())
     }
@@ -698,9 +1134,17 @@ mod test {
         nix_config
             .settings_mut()
             .insert("experimental-features".into(), "ca-references".into());
-        let mut action = CreateOrMergeNixConfig::plan(&test_file, nix_config).await?;
-
-        action.try_execute().await?;
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
         let s = std::fs::read_to_string(&test_file)?;
         assert!(s.contains("# Generated by"));
@@ -708,9 +1152,342 @@ mod test {
         assert_eq!(s.matches("a = b").count(), 1);
         assert!(NixConfig::parse_file(&test_file).is_ok());
 
-        action.try_revert().await?;
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
 
-        assert!(!test_file.exists(), "File should have been deleted");
+        let reverted = NixConfig::parse_file(&test_file)?;
+        assert_eq!(reverted.settings().get("a"), Some(&"b".to_string()));
+        assert_eq!(reverted.settings().get("c"), Some(&"d".to_string()));
+        assert_eq!(reverted.settings().get("experimental-features"), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verifies_merged_settings() -> eyre::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let test_file = temp_dir.path().join("verifies_merged_settings");
+
+        write(test_file.as_path(), "experimental-features = flakes\n").await?;
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(NIX_CONF_MODE)).await?;
+        let mut nix_config = NixConfig::new();
+        nix_config
+            .settings_mut()
+            .insert("experimental-features".into(), "nix-command".into());
+        nix_config
+            .settings_mut()
+            .insert("auto-optimise-store".into(), "true".into());
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        assert!(action.try_verify().await?.is_empty());
+
+        // Values separated by more than a single space are still included
+        write(
+            test_file.as_path(),
+            "experimental-features = flakes\tnix-command\nauto-optimise-store = true\n",
+        )
+        .await?;
+        assert!(action.try_verify().await?.is_empty());
+
+        // Someone removed `nix-command` and the other setting, but kept their own `flakes`
+        write(test_file.as_path(), "experimental-features = flakes\n").await?;
+        let drift = action.try_verify().await?;
+        assert_eq!(drift.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reverts_only_added_settings() -> eyre::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let test_file = temp_dir.path().join("reverts_only_added_settings");
+
+        write(
+            test_file.as_path(),
+            "# Managed by the admin\nexperimental-features = flakes # we like flakes\nmax-jobs = 4\n",
+        )
+        .await?;
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(NIX_CONF_MODE)).await?;
+        let mut nix_config = NixConfig::new();
+        nix_config
+            .settings_mut()
+            .insert("experimental-features".into(), "nix-command flakes".into());
+        nix_config
+            .settings_mut()
+            .insert("auto-optimise-store".into(), "true".into());
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        let added = action.action.added_nix_config.clone().unwrap();
+        assert_eq!(
+            added.settings().get("experimental-features"),
+            Some(&"nix-command".to_string())
+        );
+        assert_eq!(
+            added.settings().get("auto-optimise-store"),
+            Some(&"true".to_string())
+        );
+        assert_eq!(added.settings().len(), 2);
+
+        // The admin keeps editing after the install
+        let mut s = std::fs::read_to_string(&test_file)?;
+        s.push_str("# Added later\nkeep-outputs = true\n");
+        write(test_file.as_path(), s).await?;
+
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        let s = std::fs::read_to_string(&test_file)?;
+        assert!(s.starts_with(
+            "# Managed by the admin\nexperimental-features = flakes # we like flakes\n"
+        ));
+        assert!(s.contains("# Added later\nkeep-outputs = true\n"));
+        assert!(!s.contains("nix-command"));
+        assert!(!s.contains("# Generated by"));
+        let reverted = NixConfig::parse_file(&test_file)?;
+        assert_eq!(reverted.settings().get("max-jobs"), Some(&"4".to_string()));
+        assert_eq!(reverted.settings().get("auto-optimise-store"), None);
+        assert_eq!(reverted.settings().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_added_settings() {
+        let mut added = NixConfig::new();
+        added
+            .settings_mut()
+            .insert("experimental-features".into(), "nix-command flakes".into());
+        added
+            .settings_mut()
+            .insert("build-users-group".into(), "nixbld".into());
+
+        // Only what `nix-installer` wrote
+        assert_eq!(
+            remove_added_settings(
+                "\n# Generated by https://github.com/DeterminateSystems/nix-installer, version 0.8.1.\nexperimental-features = nix-command flakes\nbuild-users-group = nixbld\n\n",
+                &added,
+                &NixConfig::new()
+            ),
+            None
+        );
+        // Values merged into a list are split back apart, an inline comment on a setting with no values left is kept
+        assert_eq!(
+            remove_added_settings(
+                "experimental-features = nix-command flakes repl-flake\n  build-users-group = nixbld # the default\n",
+                &added,
+                &NixConfig::new()
+            ),
+            Some("experimental-features = repl-flake\n# the default\n".into())
+        );
+        // Anything else is left alone
+        assert_eq!(
+            remove_added_settings(
+                "!include nix.local.conf\n# build-users-group = nixbld\n",
+                &added,
+                &NixConfig::new()
+            ),
+            Some("!include nix.local.conf\n# build-users-group = nixbld\n".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn merges_list_settings() -> eyre::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let test_file = temp_dir.path().join("merges_list_settings");
+
+        write(
+            test_file.as_path(),
+            "substituters = https://cache.example.com https://cache.nixos.org\ntrusted-public-keys = cache.example.com-1:AAAA\ntrusted-users = root alice\nextra-platforms = aarch64-linux\n",
+        )
+        .await?;
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(NIX_CONF_MODE)).await?;
+        let mut nix_config = NixConfig::new();
+        for (name, value) in [
+            ("substituters", "https://cache.nixos.org"),
+            (
+                "trusted-public-keys",
+                "cache.nixos.org-1:6NCHdD59X431o0gWypbMrAURkbJ16ZPMQFGspcDShjY=",
+            ),
+            ("trusted-users", "root @wheel"),
+            ("extra-platforms", "i686-linux aarch64-linux"),
+        ] {
+            nix_config.settings_mut().insert(name.into(), value.into());
+        }
+        let mut action =
+            CreateOrMergeNixConfig::plan(&test_file, nix_config, NixConfigConflictPolicy::Fail)
+                .await?;
+
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        let merged = NixConfig::parse_file(&test_file)?;
+        let values = |name: &str| {
+            let mut values = merged.settings()[name]
+                .split_whitespace()
+                .collect::<Vec<_>>();
+            values.sort();
+            values.join(" ")
+        };
+        assert_eq!(
+            values("substituters"),
+            "https://cache.example.com https://cache.nixos.org"
+        );
+        assert_eq!(
+            values("trusted-public-keys"),
+            "cache.example.com-1:AAAA cache.nixos.org-1:6NCHdD59X431o0gWypbMrAURkbJ16ZPMQFGspcDShjY="
+        );
+        assert_eq!(values("trusted-users"), "@wheel alice root");
+        assert_eq!(values("extra-platforms"), "aarch64-linux i686-linux");
+        assert!(action.try_verify().await?.is_empty());
+
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+
+        let reverted = NixConfig::parse_file(&test_file)?;
+        assert_eq!(
+            reverted.settings().get("trusted-public-keys"),
+            Some(&"cache.example.com-1:AAAA".to_string())
+        );
+        assert_eq!(
+            reverted.settings().get("trusted-users"),
+            Some(&"root alice".to_string())
+        );
+        assert_eq!(
+            reverted.settings().get("extra-platforms"),
+            Some(&"aarch64-linux".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resolves_scalar_conflicts_by_policy() -> eyre::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut nix_config = NixConfig::new();
+        nix_config
+            .settings_mut()
+            .insert("max-jobs".into(), "8".into());
+        nix_config
+            .settings_mut()
+            .insert("auto-optimise-store".into(), "true".into());
+
+        let conflicting = |name: &str| -> eyre::Result<PathBuf> {
+            let test_file = temp_dir.path().join(name);
+            std::fs::write(&test_file, "max-jobs = 4 # tuned for this host\n")?;
+            std::fs::set_permissions(&test_file, PermissionsExt::from_mode(NIX_CONF_MODE))?;
+            Ok(test_file)
+        };
+
+        let test_file = conflicting("fail")?;
+        assert!(CreateOrMergeNixConfig::plan(
+            &test_file,
+            nix_config.clone(),
+            NixConfigConflictPolicy::Fail
+        )
+        .await
+        .is_err());
+
+        let test_file = conflicting("keep_existing")?;
+        let mut action = CreateOrMergeNixConfig::plan(
+            &test_file,
+            nix_config.clone(),
+            NixConfigConflictPolicy::KeepExisting,
+        )
+        .await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        let merged = NixConfig::parse_file(&test_file)?;
+        assert_eq!(merged.settings().get("max-jobs"), Some(&"4".to_string()));
+        assert_eq!(
+            merged.settings().get("auto-optimise-store"),
+            Some(&"true".to_string())
+        );
+        assert!(action.try_verify().await?.is_empty());
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert_eq!(
+            std::fs::read_to_string(&test_file)?,
+            "max-jobs = 4 # tuned for this host\n"
+        );
+
+        let test_file = conflicting("prefer_installer")?;
+        let mut action = CreateOrMergeNixConfig::plan(
+            &test_file,
+            nix_config.clone(),
+            NixConfigConflictPolicy::PreferInstaller,
+        )
+        .await?;
+        action
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        let merged = NixConfig::parse_file(&test_file)?;
+        assert_eq!(merged.settings().get("max-jobs"), Some(&"8".to_string()));
+        assert!(action.try_verify().await?.is_empty());
+        // The replaced value is restored
+        action
+            .try_revert(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert_eq!(
+            std::fs::read_to_string(&test_file)?,
+            "max-jobs = 4 # tuned for this host\n"
+        );
 
         Ok(())
     }
@@ -1,25 +1,51 @@
+use std::path::PathBuf;
+
 use nix::unistd::User;
 use target_lexicon::OperatingSystem;
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag};
+use crate::action::{
+    ActionDrift, ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress,
+};
 use crate::execute_command;
+use crate::os::etc_files;
 
-use crate::action::{Action, ActionDescription, StatefulAction};
+use crate::action::{Action, ActionDependency, ActionDescription, StatefulAction};
 
 /**
 Delete an operating system level user
+
+If a `root` is given, the user is removed from the `etc/passwd` file inside of it instead of using the host's tools.
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct DeleteUser {
     name: String,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl DeleteUser {
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn plan(name: String) -> Result<StatefulAction<Self>, ActionError> {
-        let this = Self { name: name.clone() };
+    pub async fn plan(
+        name: String,
+        root: Option<PathBuf>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let this = Self {
+            name: name.clone(),
+            root: root.clone(),
+        };
+
+        if let Some(root) = &root {
+            // Ensure user exists
+            if !etc_files::user_exists(root, &name)
+                .await
+                .map_err(Self::error)?
+            {
+                return Err(Self::error(ActionErrorKind::NoUser(name)));
+            }
+            return Ok(StatefulAction::uncompleted(this));
+        }
 
         match OperatingSystem::host() {
             OperatingSystem::MacOSX { .. } | OperatingSystem::Darwin => (),
@@ -61,6 +87,10 @@ impl Action for DeleteUser {
         span!(tracing::Level::DEBUG, "delete_user", user = self.name,)
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(vec![ActionDependency::User(self.name.clone())])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             self.tracing_synopsis(),
@@ -71,7 +101,19 @@ impl Action for DeleteUser {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        if let Some(root) = &self.root {
+            etc_files::remove_user(root, &self.name)
+                .await
+                .map_err(Self::error)?;
+            return Ok(());
+        }
+
         use OperatingSystem;
         match OperatingSystem::host() {
             OperatingSystem::MacOSX {
@@ -117,6 +159,7 @@ impl Action for DeleteUser {
                             .process_group(0)
                             .arg(&self.name)
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -126,6 +169,7 @@ impl Action for DeleteUser {
                             .process_group(0)
                             .arg(&self.name)
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -138,12 +182,39 @@ impl Action for DeleteUser {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self { name, root } = self;
+
+        let exists = match root {
+            Some(root) => etc_files::user_exists(root, name)
+                .await
+                .map_err(Self::error)?,
+            None => User::from_name(name.as_str())
+                .map_err(|e| ActionErrorKind::GettingUserId(name.clone(), e))
+                .map_err(Self::error)?
+                .is_some(),
+        };
+        if exists {
+            return Ok(vec![ActionDrift::new(
+                self.tracing_synopsis(),
+                format!("User `{name}` exists again"),
+            )]);
+        }
+        Ok(vec![])
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         Ok(())
     }
 }
@@ -15,9 +15,168 @@ pub use create_directory::CreateDirectory;
 pub use create_file::CreateFile;
 pub use create_group::CreateGroup;
 pub use create_or_insert_into_file::CreateOrInsertIntoFile;
-pub use create_or_merge_nix_config::CreateOrMergeNixConfig;
+pub use create_or_merge_nix_config::{
+    is_list_conf_name, CreateOrMergeNixConfig, NixConfigConflictPolicy,
+};
 pub use delete_user::DeleteUser;
-pub use fetch_and_unpack_nix::{FetchAndUnpackNix, FetchUrlError};
+pub use fetch_and_unpack_nix::{FetchAndUnpackNix, FetchNixOptions, FetchUrlError};
 pub use move_unpacked_nix::{MoveUnpackedNix, MoveUnpackedNixError};
 pub use remove_directory::RemoveDirectory;
 pub use setup_default_profile::{SetupDefaultProfile, SetupDefaultProfileError};
+
+use std::{
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+use nix::unistd::{chown, Gid, Group, Uid, User};
+
+use super::{ActionDependency, ActionErrorKind};
+use crate::os::etc_files;
+
+/// The dependencies of an action creating the file or directory at `path`, owned by `user` and `group`, see
+/// [`Action::dependencies`](crate::action::Action::dependencies)
+pub(crate) fn path_dependencies(
+    path: &Path,
+    user: Option<&str>,
+    group: Option<&str>,
+) -> Vec<ActionDependency> {
+    let mut dependencies = vec![ActionDependency::path(path)];
+    if let Some(user) = user {
+        dependencies.push(ActionDependency::User(user.to_string()));
+    }
+    if let Some(group) = group {
+        dependencies.push(ActionDependency::Group(group.to_string()));
+    }
+    dependencies
+}
+
+/// Find the UID of `user`, in the `etc/passwd` of `root` if one is given, otherwise on the host
+pub(crate) async fn find_uid(
+    user: &str,
+    root: Option<&Path>,
+) -> Result<Option<u32>, ActionErrorKind> {
+    match root {
+        Some(root) => etc_files::find_user_uid(root, user).await,
+        None => Ok(User::from_name(user)
+            .map_err(|e| ActionErrorKind::GettingUserId(user.to_string(), e))?
+            .map(|user| user.uid.as_raw())),
+    }
+}
+
+/// Find the GID of `group`, in the `etc/group` of `root` if one is given, otherwise on the host
+pub(crate) async fn find_gid(
+    group: &str,
+    root: Option<&Path>,
+) -> Result<Option<u32>, ActionErrorKind> {
+    match root {
+        Some(root) => Ok(etc_files::find_group(root, group)
+            .await?
+            .map(|group| group.gid)),
+        None => Ok(Group::from_name(group)
+            .map_err(|e| ActionErrorKind::GettingGroupId(group.to_string(), e))?
+            .map(|group| group.gid.as_raw())),
+    }
+}
+
+/// Describe how the file or directory at `path` has drifted from the given type, owner, and mode, see
+/// [`Action::verify`](crate::action::Action::verify)
+pub(crate) async fn path_drift(
+    path: &Path,
+    is_dir: bool,
+    user: Option<&str>,
+    group: Option<&str>,
+    mode: Option<u32>,
+    root: Option<&Path>,
+) -> Result<Vec<String>, ActionErrorKind> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(vec![format!("`{}` is missing", path.display())])
+        },
+        Err(e) => return Err(ActionErrorKind::GettingMetadata(path.to_path_buf(), e)),
+    };
+
+    if metadata.is_dir() != is_dir {
+        return Ok(vec![format!(
+            "`{}` is no longer a {}",
+            path.display(),
+            if is_dir { "directory" } else { "file" }
+        )]);
+    }
+
+    let mut drift = vec![];
+    if let Some(mode) = mode {
+        // We only care about user-group-other permissions
+        let discovered_mode = metadata.permissions().mode() & 0o777;
+        if discovered_mode != mode {
+            drift.push(format!(
+                "`{}` has mode `{discovered_mode:#o}`, `{mode:#o}` was expected",
+                path.display()
+            ));
+        }
+    }
+    if let Some(user) = user {
+        match find_uid(user, root).await? {
+            Some(expected) if expected == metadata.uid() => (),
+            Some(expected) => drift.push(format!(
+                "`{}` is owned by UID {}, user `{user}` (UID {expected}) was expected",
+                path.display(),
+                metadata.uid(),
+            )),
+            None => drift.push(format!(
+                "`{}` should be owned by user `{user}`, which no longer exists",
+                path.display()
+            )),
+        }
+    }
+    if let Some(group) = group {
+        match find_gid(group, root).await? {
+            Some(expected) if expected == metadata.gid() => (),
+            Some(expected) => drift.push(format!(
+                "`{}` is owned by GID {}, group `{group}` (GID {expected}) was expected",
+                path.display(),
+                metadata.gid(),
+            )),
+            None => drift.push(format!(
+                "`{}` should be owned by group `{group}`, which no longer exists",
+                path.display()
+            )),
+        }
+    }
+    Ok(drift)
+}
+
+/// Restore the owner and mode of the file or directory at `path`, after it [drifted](path_drift)
+pub(crate) async fn restore_path(
+    path: &Path,
+    user: Option<&str>,
+    group: Option<&str>,
+    mode: Option<u32>,
+    root: Option<&Path>,
+) -> Result<(), ActionErrorKind> {
+    let uid = match user {
+        Some(user) => Some(Uid::from_raw(
+            find_uid(user, root)
+                .await?
+                .ok_or_else(|| ActionErrorKind::NoUser(user.to_string()))?,
+        )),
+        None => None,
+    };
+    let gid = match group {
+        Some(group) => {
+            Some(Gid::from_raw(find_gid(group, root).await?.ok_or_else(
+                || ActionErrorKind::NoGroup(group.to_string()),
+            )?))
+        },
+        None => None,
+    };
+    chown(path, uid, gid).map_err(|e| ActionErrorKind::Chown(path.to_path_buf(), e))?;
+
+    if let Some(mode) = mode {
+        tokio::fs::set_permissions(path, PermissionsExt::from_mode(mode))
+            .await
+            .map_err(|e| ActionErrorKind::SetPermissions(mode, path.to_path_buf(), e))?;
+    }
+    Ok(())
+}
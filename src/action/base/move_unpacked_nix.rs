@@ -1,36 +1,49 @@
-use std::{
-    fs::Permissions,
-    os::unix::prelude::PermissionsExt,
-    path::{Path, PathBuf},
-};
+use std::{fs::Permissions, os::unix::prelude::PermissionsExt, path::PathBuf};
 
 use tracing::{span, Span};
 use walkdir::WalkDir;
 
-use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+use crate::{
+    action::{
+        Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+        ActionTag, CancellationToken, Journal, Progress, StatefulAction,
+    },
+    settings::rooted,
 };
 
 pub(crate) const DEST: &str = "/nix/";
 
 /**
-Move an unpacked Nix at `src` to `/nix`
+Move an unpacked Nix at `src` to `/nix` (inside of `root`, if set)
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct MoveUnpackedNix {
     unpacked_path: PathBuf,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl MoveUnpackedNix {
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn plan(unpacked_path: PathBuf) -> Result<StatefulAction<Self>, ActionError> {
+    pub async fn plan(
+        unpacked_path: PathBuf,
+        root: Option<PathBuf>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
         // Note: Do NOT try to check for the src/dest since the installer creates those
-        Ok(Self { unpacked_path }.into())
+        Ok(Self {
+            unpacked_path,
+            root,
+        }
+        .into())
+    }
+
+    fn dest(&self) -> PathBuf {
+        rooted(self.root.as_deref(), DEST)
     }
 }
 
 #[async_trait::async_trait]
-#[typetag::serde(name = "mount_unpacked_nix")]
+#[typetag::serde(name = "move_unpacked_nix")]
 impl Action for MoveUnpackedNix {
     fn action_tag() -> ActionTag {
         ActionTag("move_unpacked_nix")
@@ -42,12 +55,19 @@ impl Action for MoveUnpackedNix {
     fn tracing_span(&self) -> Span {
         span!(
             tracing::Level::DEBUG,
-            "mount_unpacked_nix",
+            "move_unpacked_nix",
             src = tracing::field::display(self.unpacked_path.display()),
-            dest = DEST,
+            dest = tracing::field::display(self.dest().display()),
         )
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(vec![
+            ActionDependency::path(&self.unpacked_path),
+            ActionDependency::path(self.dest()),
+        ])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             format!("Move the downloaded Nix into `/nix`"),
@@ -59,8 +79,17 @@ impl Action for MoveUnpackedNix {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
-        let Self { unpacked_path } = self;
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let dest = self.dest();
+        let Self {
+            unpacked_path,
+            root: _,
+        } = self;
 
         // This is the `nix-$VERSION` folder which unpacks from the tarball, not a nix derivation
         let found_nix_paths = glob::glob(&format!("{}/nix-*", unpacked_path.display()))
@@ -76,7 +105,7 @@ impl Action for MoveUnpackedNix {
             .await
             .map_err(|e| ActionErrorKind::ReadDir(src_store.clone(), e))
             .map_err(Self::error)?;
-        let dest_store = Path::new(DEST).join("store");
+        let dest_store = dest.join("store");
         if dest_store.exists() {
             if !dest_store.is_dir() {
                 return Err(Self::error(ActionErrorKind::PathWasNotDirectory(
@@ -143,12 +172,45 @@ impl Action for MoveUnpackedNix {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let store = self.dest().join("store");
+        if !store.is_dir() {
+            return Ok(vec![ActionDrift::new(
+                self.tracing_synopsis(),
+                format!("`{}` is missing", store.display()),
+            )]);
+        }
+        Ok(vec![])
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        // The unpacked Nix is normally cleaned up at the end of an install
+        if !self.unpacked_path.exists() {
+            return Err(Self::error(MoveUnpackedNixError::MissingUnpackedNix(
+                self.unpacked_path.clone(),
+            )));
+        }
+        self.execute(cancel, journal, progress).await
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![/* Deliberately empty -- this is a noop */]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // Noop
         Ok(())
     }
@@ -169,6 +231,8 @@ pub enum MoveUnpackedNixError {
         #[source]
         glob::GlobError,
     ),
+    #[error("The unpacked Nix at `{0}` no longer exists so the Nix store cannot be moved into place again, uninstall and reinstall Nix instead")]
+    MissingUnpackedNix(PathBuf),
 }
 
 impl Into<ActionErrorKind> for MoveUnpackedNixError {
@@ -3,8 +3,8 @@ use std::path::{Path, PathBuf};
 use tokio::fs::remove_dir_all;
 use tracing::{span, Span};
 
-use crate::action::{Action, ActionDescription, ActionErrorKind, ActionState};
-use crate::action::{ActionError, StatefulAction};
+use crate::action::{Action, ActionDependency, ActionDescription, ActionErrorKind, ActionState};
+use crate::action::{ActionError, CancellationToken, Journal, Progress, StatefulAction};
 
 /** Remove a directory, does nothing on revert.
 */
@@ -23,6 +23,8 @@ impl RemoveDirectory {
                 path: path.to_path_buf(),
             },
             state: ActionState::Uncompleted,
+            retry: None,
+            attempts: 0,
         })
     }
 }
@@ -45,12 +47,21 @@ impl Action for RemoveDirectory {
         )
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        Some(vec![ActionDependency::path(&self.path)])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         if self.path.exists() {
             if !self.path.is_dir() {
                 return Err(Self::error(ActionErrorKind::PathWasNotDirectory(
@@ -72,7 +83,12 @@ impl Action for RemoveDirectory {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         Ok(())
     }
 }
@@ -1,8 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
-    action::{ActionError, ActionErrorKind, ActionTag, StatefulAction},
+    action::{
+        ActionDrift, ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress,
+        StatefulAction,
+    },
     execute_command, set_env,
+    settings::rooted,
 };
 
 use glob::glob;
@@ -10,20 +14,65 @@ use glob::glob;
 use tokio::{io::AsyncWriteExt, process::Command};
 use tracing::{span, Span};
 
-use crate::action::{Action, ActionDescription};
+use crate::action::{Action, ActionDependency, ActionDescription};
+
+const DEFAULT_PROFILE: &str = "/nix/var/nix/profiles/default";
 
 /**
 Setup the default Nix profile with `nss-cacert` and `nix` itself.
+
+If a `root` is given, Nix is run inside of it with `chroot`, as the Nix binaries expect to find their
+dependencies in `/nix/store`.
  */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct SetupDefaultProfile {
     unpacked_path: PathBuf,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl SetupDefaultProfile {
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn plan(unpacked_path: PathBuf) -> Result<StatefulAction<Self>, ActionError> {
-        Ok(Self { unpacked_path }.into())
+    pub async fn plan(
+        unpacked_path: PathBuf,
+        root: Option<PathBuf>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        Ok(Self {
+            unpacked_path,
+            root,
+        }
+        .into())
+    }
+
+    /// The path of a store path found on the host, as seen by a command from [`SetupDefaultProfile::command`]
+    fn store_path(&self, path: PathBuf) -> PathBuf {
+        match &self.root {
+            Some(root) => match path.strip_prefix(root) {
+                Ok(stripped) => Path::new("/").join(stripped),
+                Err(_) => path,
+            },
+            None => path,
+        }
+    }
+
+    fn command(&self, program: PathBuf) -> Command {
+        match &self.root {
+            Some(root) => {
+                let mut command = Command::new("chroot");
+                command.arg(root);
+                command.arg(program);
+                command
+            },
+            None => Command::new(program),
+        }
+    }
+
+    /// Arguments which select the default profile, when running inside a root the `HOME` of the host is meaningless
+    fn profile_args(&self) -> Vec<&'static str> {
+        match &self.root {
+            Some(_) => vec!["--profile", DEFAULT_PROFILE],
+            None => vec![],
+        }
     }
 }
 
@@ -45,12 +94,25 @@ impl Action for SetupDefaultProfile {
         )
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        // Nix is installed into the store, and the default profile created, inside of `/nix`
+        Some(vec![
+            ActionDependency::path(&self.unpacked_path),
+            ActionDependency::path(rooted(self.root.as_deref(), "/nix")),
+        ])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // Find an `nix` package
         let nix_pkg_glob = format!("{}/nix-*/store/*-nix-*.*.*", self.unpacked_path.display());
         let mut found_nix_pkg = None;
@@ -71,10 +133,12 @@ impl Action for SetupDefaultProfile {
             };
         }
         let nix_pkg = if let Some(nix_pkg) = found_nix_pkg {
-            tokio::fs::read_link(&nix_pkg)
-                .await
-                .map_err(|e| ActionErrorKind::ReadSymlink(nix_pkg, e))
-                .map_err(Self::error)?
+            self.store_path(
+                tokio::fs::read_link(&nix_pkg)
+                    .await
+                    .map_err(|e| ActionErrorKind::ReadSymlink(nix_pkg, e))
+                    .map_err(Self::error)?,
+            )
         } else {
             return Err(Self::error(SetupDefaultProfileError::NoNix));
         };
@@ -104,10 +168,12 @@ impl Action for SetupDefaultProfile {
             };
         }
         let nss_ca_cert_pkg = if let Some(nss_ca_cert_pkg) = found_nss_ca_cert_pkg {
-            tokio::fs::read_link(&nss_ca_cert_pkg)
-                .await
-                .map_err(|e| ActionErrorKind::ReadSymlink(nss_ca_cert_pkg, e))
-                .map_err(Self::error)?
+            self.store_path(
+                tokio::fs::read_link(&nss_ca_cert_pkg)
+                    .await
+                    .map_err(|e| ActionErrorKind::ReadSymlink(nss_ca_cert_pkg, e))
+                    .map_err(Self::error)?,
+            )
         } else {
             return Err(Self::error(SetupDefaultProfileError::NoNssCacert));
         };
@@ -125,7 +191,7 @@ impl Action for SetupDefaultProfile {
             .await
             .map_err(|e| ActionErrorKind::Read(reginfo_path.to_path_buf(), e))
             .map_err(Self::error)?;
-        let mut load_db_command = Command::new(nix_pkg.join("bin/nix-store"));
+        let mut load_db_command = self.command(nix_pkg.join("bin/nix-store"));
         load_db_command.process_group(0);
         load_db_command.arg("--load-db");
         load_db_command.stdin(std::process::Stdio::piped());
@@ -176,8 +242,9 @@ impl Action for SetupDefaultProfile {
 
         // Install `nix` itself into the store
         execute_command(
-            Command::new(nix_pkg.join("bin/nix-env"))
+            self.command(nix_pkg.join("bin/nix-env"))
                 .process_group(0)
+                .args(self.profile_args())
                 .arg("-i")
                 .arg(&nix_pkg)
                 .stdin(std::process::Stdio::null())
@@ -190,14 +257,16 @@ impl Action for SetupDefaultProfile {
                     "NIX_SSL_CERT_FILE",
                     nss_ca_cert_pkg.join("etc/ssl/certs/ca-bundle.crt"),
                 ), /* This is apparently load bearing... */
+            cancel,
         )
         .await
         .map_err(Self::error)?;
 
         // Install `nix` itself into the store
         execute_command(
-            Command::new(nix_pkg.join("bin/nix-env"))
+            self.command(nix_pkg.join("bin/nix-env"))
                 .process_group(0)
+                .args(self.profile_args())
                 .arg("-i")
                 .arg(&nss_ca_cert_pkg)
                 .stdin(std::process::Stdio::null())
@@ -210,18 +279,35 @@ impl Action for SetupDefaultProfile {
                     "NIX_SSL_CERT_FILE",
                     nss_ca_cert_pkg.join("etc/ssl/certs/ca-bundle.crt"),
                 ), /* This is apparently load bearing... */
+            cancel,
         )
         .await
         .map_err(Self::error)?;
 
-        set_env(
-            "NIX_SSL_CERT_FILE",
-            "/nix/var/nix/profiles/default/etc/ssl/certs/ca-bundle.crt",
-        );
+        // The environment of this process only matters for the host
+        if self.root.is_none() {
+            set_env(
+                "NIX_SSL_CERT_FILE",
+                format!("{DEFAULT_PROFILE}/etc/ssl/certs/ca-bundle.crt"),
+            );
+        }
 
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let profile = rooted(self.root.as_deref(), DEFAULT_PROFILE);
+        // The profile is a symlink to a generation in the store, which must also still exist
+        if !profile.exists() {
+            return Ok(vec![ActionDrift::new(
+                self.tracing_synopsis(),
+                format!("`{}` is missing", profile.display()),
+            )]);
+        }
+        Ok(vec![])
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             "Unset the default Nix profile".to_string(),
@@ -230,7 +316,12 @@ impl Action for SetupDefaultProfile {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         std::env::remove_var("NIX_SSL_CERT_FILE");
 
         Ok(())
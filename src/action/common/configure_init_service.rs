@@ -4,11 +4,14 @@ use std::path::PathBuf;
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag, StatefulAction};
+use crate::action::{
+    ActionDrift, ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress,
+    StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
-use crate::settings::InitSystem;
+use crate::settings::{rooted, InitSystem};
 
 #[cfg(target_os = "linux")]
 const SERVICE_SRC: &str = "/nix/var/nix/profiles/default/lib/systemd/system/nix-daemon.service";
@@ -29,51 +32,65 @@ const DARWIN_NIX_DAEMON_SOURCE: &str =
     "/nix/var/nix/profiles/default/Library/LaunchDaemons/org.nixos.nix-daemon.plist";
 /**
 Configure the init to run the Nix daemon
+
+If a `root` is given, the init service is only enabled (offline) inside the root, it is not started.
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct ConfigureInitService {
     init: InitSystem,
     start_daemon: bool,
     ssl_cert_file: Option<PathBuf>,
+    #[serde(default)]
+    root: Option<PathBuf>,
 }
 
 impl ConfigureInitService {
     #[cfg(target_os = "linux")]
-    async fn check_if_systemd_unit_exists(src: &str, dest: &str) -> Result<(), ActionErrorKind> {
+    async fn check_if_systemd_unit_exists(src: &str, dest: &Path) -> Result<(), ActionErrorKind> {
         // TODO: once we have a way to communicate interaction between the library and the cli,
         // interactively ask for permission to remove the file
 
-        let unit_src = PathBuf::from(src);
         // NOTE: Check if the unit file already exists...
-        let unit_dest = PathBuf::from(dest);
-        if unit_dest.exists() {
-            if unit_dest.is_symlink() {
-                let link_dest = tokio::fs::read_link(&unit_dest)
-                    .await
-                    .map_err(|e| ActionErrorKind::ReadSymlink(unit_dest.clone(), e))?;
-                if link_dest != unit_src {
-                    return Err(ActionErrorKind::SymlinkExists(unit_dest));
-                }
-            } else {
-                return Err(ActionErrorKind::FileExists(unit_dest));
-            }
-        }
+        Self::check_if_symlink_exists(src, dest).await?;
         // NOTE: ...and if there are any overrides in the most well-known places for systemd
-        if Path::new(&format!("{dest}.d")).exists() {
-            return Err(ActionErrorKind::DirExists(PathBuf::from(format!(
-                "{dest}.d"
-            ))));
+        let overrides = PathBuf::from(format!("{}.d", dest.display()));
+        if overrides.symlink_metadata().is_ok() {
+            return Err(ActionErrorKind::DirExists(overrides));
         }
 
         Ok(())
     }
 
+    /// Check that `dest` is either missing or a symlink to `src`, returning if it is the latter
+    ///
+    /// Links are never followed, as inside an alternate root one to `src` (in `/nix`) would resolve to the `/nix` of
+    /// the host rather than the one in the root.
+    #[cfg(target_os = "linux")]
+    async fn check_if_symlink_exists(src: &str, dest: &Path) -> Result<bool, ActionErrorKind> {
+        if dest.is_symlink() {
+            let link_dest = tokio::fs::read_link(dest)
+                .await
+                .map_err(|e| ActionErrorKind::ReadSymlink(dest.to_path_buf(), e))?;
+            if link_dest != Path::new(src) {
+                return Err(ActionErrorKind::SymlinkExists(dest.to_path_buf()));
+            }
+            return Ok(true);
+        }
+        if dest.symlink_metadata().is_ok() {
+            return Err(ActionErrorKind::FileExists(dest.to_path_buf()));
+        }
+        Ok(false)
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn plan(
         init: InitSystem,
         start_daemon: bool,
         ssl_cert_file: Option<PathBuf>,
+        root: Option<PathBuf>,
     ) -> Result<StatefulAction<Self>, ActionError> {
+        // Nothing can be started inside an alternate root, it will be started when the root is booted
+        let start_daemon = start_daemon && root.is_none();
         let ssl_cert_file_path = if let Some(ssl_cert_file) = ssl_cert_file {
             Some(
                 ssl_cert_file
@@ -93,17 +110,26 @@ impl ConfigureInitService {
             InitSystem::Systemd => {
                 // If /run/systemd/system exists, we can be reasonably sure the machine is booted
                 // with systemd: https://www.freedesktop.org/software/systemd/man/sd_booted.html
-                if !(Path::new("/run/systemd/system").exists() || which::which("systemctl").is_ok())
-                {
+                //
+                // Inside an alternate root, only `systemctl --root` is used so it must be present.
+                let booted_with_systemd =
+                    root.is_none() && Path::new("/run/systemd/system").exists();
+                if !(booted_with_systemd || which::which("systemctl").is_ok()) {
                     return Err(Self::error(ActionErrorKind::SystemdMissing));
                 }
 
-                Self::check_if_systemd_unit_exists(SERVICE_SRC, SERVICE_DEST)
-                    .await
-                    .map_err(Self::error)?;
-                Self::check_if_systemd_unit_exists(SOCKET_SRC, SOCKET_DEST)
-                    .await
-                    .map_err(Self::error)?;
+                Self::check_if_systemd_unit_exists(
+                    SERVICE_SRC,
+                    &rooted(root.as_deref(), SERVICE_DEST),
+                )
+                .await
+                .map_err(Self::error)?;
+                Self::check_if_systemd_unit_exists(
+                    SOCKET_SRC,
+                    &rooted(root.as_deref(), SOCKET_DEST),
+                )
+                .await
+                .map_err(Self::error)?;
             },
             #[cfg(target_os = "linux")]
             InitSystem::None => {
@@ -115,9 +141,38 @@ impl ConfigureInitService {
             init,
             start_daemon,
             ssl_cert_file: ssl_cert_file_path,
+            root,
         }
         .into())
     }
+
+    /// Resolve an absolute `path` inside of the `root`, if set
+    #[cfg(target_os = "linux")]
+    fn rooted(&self, path: &str) -> PathBuf {
+        rooted(self.root.as_deref(), path)
+    }
+
+    /// Describe how the symlink at `dest` no longer points to `src`, if it does not
+    #[cfg(target_os = "linux")]
+    async fn symlink_drift(src: &str, dest: &Path) -> Result<Option<String>, ActionErrorKind> {
+        if !dest.is_symlink() {
+            return Ok(Some(format!(
+                "`{}` is no longer a symlink to `{src}`",
+                dest.display()
+            )));
+        }
+        let link_dest = tokio::fs::read_link(dest)
+            .await
+            .map_err(|e| ActionErrorKind::ReadSymlink(dest.to_path_buf(), e))?;
+        if link_dest != Path::new(src) {
+            return Ok(Some(format!(
+                "`{}` links to `{}`, `{src}` was expected",
+                dest.display(),
+                link_dest.display()
+            )));
+        }
+        Ok(None)
+    }
 }
 
 #[async_trait::async_trait]
@@ -148,6 +203,28 @@ impl Action for ConfigureInitService {
         match self.init {
             #[cfg(target_os = "linux")]
             InitSystem::Systemd => {
+                if let Some(root) = &self.root {
+                    let explanation = vec![
+                        format!(
+                            "Run `systemd-tempfiles --root={} --create --prefix=/nix/var/nix`",
+                            root.display()
+                        ),
+                        format!(
+                            "Symlink `{SERVICE_SRC}` to `{}`",
+                            self.rooted(SERVICE_DEST).display()
+                        ),
+                        format!(
+                            "Symlink `{SOCKET_SRC}` to `{}`",
+                            self.rooted(SOCKET_DEST).display()
+                        ),
+                        format!(
+                            "Run `systemctl --root={} enable nix-daemon.socket`",
+                            root.display()
+                        ),
+                    ];
+                    vec.push(ActionDescription::new(self.tracing_synopsis(), explanation));
+                    return vec;
+                }
                 let mut explanation = vec![
                     "Run `systemd-tempfiles --create --prefix=/nix/var/nix`".to_string(),
                     format!("Symlink `{SERVICE_SRC}` to `{SERVICE_DEST}`"),
@@ -176,11 +253,24 @@ impl Action for ConfigureInitService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        #[cfg(target_os = "linux")]
+        let (service_dest, socket_dest, tmpfiles_dest) = (
+            self.rooted(SERVICE_DEST),
+            self.rooted(SOCKET_DEST),
+            self.rooted(TMPFILES_DEST),
+        );
+        #[cfg_attr(target_os = "macos", allow(unused_variables))]
         let Self {
             init,
             start_daemon,
             ssl_cert_file,
+            root,
         } = self;
 
         match init {
@@ -203,6 +293,7 @@ impl Action for ConfigureInitService {
                         .args(&["load", "-w"])
                         .arg(DARWIN_NIX_DAEMON_DEST)
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -215,6 +306,7 @@ impl Action for ConfigureInitService {
                             .arg("NIX_SSL_CERT_FILE")
                             .arg(format!("{ssl_cert_file:?}"))
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -228,6 +320,7 @@ impl Action for ConfigureInitService {
                             .arg("-k")
                             .arg("system/org.nixos.nix-daemon")
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
@@ -241,43 +334,58 @@ impl Action for ConfigureInitService {
                             .process_group(0)
                             .arg("daemon-reload")
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
                 }
+                let root = root.as_deref();
                 // The goal state is the `socket` enabled and active, the service not enabled and stopped (it activates via socket activation)
-                if is_enabled("nix-daemon.socket").await.map_err(Self::error)? {
-                    disable("nix-daemon.socket", false)
+                //
+                // Inside an alternate root, nothing is running, so there is nothing to stop.
+                let socket_was_active = if root.is_none() {
+                    if is_enabled("nix-daemon.socket", root)
                         .await
-                        .map_err(Self::error)?;
-                }
-                let socket_was_active =
-                    if is_active("nix-daemon.socket").await.map_err(Self::error)? {
-                        stop("nix-daemon.socket").await.map_err(Self::error)?;
-                        true
-                    } else {
-                        false
+                        .map_err(Self::error)?
+                    {
+                        disable("nix-daemon.socket", false, root)
+                            .await
+                            .map_err(Self::error)?;
+                    }
+                    let socket_was_active =
+                        if is_active("nix-daemon.socket").await.map_err(Self::error)? {
+                            stop("nix-daemon.socket").await.map_err(Self::error)?;
+                            true
+                        } else {
+                            false
+                        };
+                    if is_enabled("nix-daemon.service", root)
+                        .await
+                        .map_err(Self::error)?
+                    {
+                        let now = is_active("nix-daemon.service").await.map_err(Self::error)?;
+                        disable("nix-daemon.service", now, root)
+                            .await
+                            .map_err(Self::error)?;
+                    } else if is_active("nix-daemon.service").await.map_err(Self::error)? {
+                        stop("nix-daemon.service").await.map_err(Self::error)?;
                     };
-                if is_enabled("nix-daemon.service")
+                    socket_was_active
+                } else {
+                    false
+                };
+
+                tracing::trace!(src = TMPFILES_SRC, dest = %tmpfiles_dest.display(), "Symlinking");
+                if !Self::check_if_symlink_exists(TMPFILES_SRC, &tmpfiles_dest)
                     .await
                     .map_err(Self::error)?
                 {
-                    let now = is_active("nix-daemon.service").await.map_err(Self::error)?;
-                    disable("nix-daemon.service", now)
-                        .await
-                        .map_err(Self::error)?;
-                } else if is_active("nix-daemon.service").await.map_err(Self::error)? {
-                    stop("nix-daemon.service").await.map_err(Self::error)?;
-                };
-
-                tracing::trace!(src = TMPFILES_SRC, dest = TMPFILES_DEST, "Symlinking");
-                if !Path::new(TMPFILES_DEST).exists() {
-                    tokio::fs::symlink(TMPFILES_SRC, TMPFILES_DEST)
+                    tokio::fs::symlink(TMPFILES_SRC, &tmpfiles_dest)
                         .await
                         .map_err(|e| {
                             ActionErrorKind::Symlink(
                                 PathBuf::from(TMPFILES_SRC),
-                                PathBuf::from(TMPFILES_DEST),
+                                tmpfiles_dest.clone(),
                                 e,
                             )
                         })
@@ -287,9 +395,11 @@ impl Action for ConfigureInitService {
                 execute_command(
                     Command::new("systemd-tmpfiles")
                         .process_group(0)
+                        .args(root.map(|root| format!("--root={}", root.display())))
                         .arg("--create")
                         .arg("--prefix=/nix/var/nix")
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -297,31 +407,27 @@ impl Action for ConfigureInitService {
                 // TODO: once we have a way to communicate interaction between the library and the
                 // cli, interactively ask for permission to remove the file
 
-                Self::check_if_systemd_unit_exists(SERVICE_SRC, SERVICE_DEST)
+                Self::check_if_systemd_unit_exists(SERVICE_SRC, &service_dest)
                     .await
                     .map_err(Self::error)?;
-                tokio::fs::symlink(SERVICE_SRC, SERVICE_DEST)
+                tokio::fs::symlink(SERVICE_SRC, &service_dest)
                     .await
                     .map_err(|e| {
                         ActionErrorKind::Symlink(
                             PathBuf::from(SERVICE_SRC),
-                            PathBuf::from(SERVICE_DEST),
+                            service_dest.clone(),
                             e,
                         )
                     })
                     .map_err(Self::error)?;
 
-                Self::check_if_systemd_unit_exists(SOCKET_SRC, SOCKET_DEST)
+                Self::check_if_systemd_unit_exists(SOCKET_SRC, &socket_dest)
                     .await
                     .map_err(Self::error)?;
-                tokio::fs::symlink(SOCKET_SRC, SOCKET_DEST)
+                tokio::fs::symlink(SOCKET_SRC, &socket_dest)
                     .await
                     .map_err(|e| {
-                        ActionErrorKind::Symlink(
-                            PathBuf::from(SOCKET_SRC),
-                            PathBuf::from(SOCKET_DEST),
-                            e,
-                        )
+                        ActionErrorKind::Symlink(PathBuf::from(SOCKET_SRC), socket_dest.clone(), e)
                     })
                     .map_err(Self::error)?;
 
@@ -331,13 +437,15 @@ impl Action for ConfigureInitService {
                             .process_group(0)
                             .arg("daemon-reload")
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     .map_err(Self::error)?;
                 }
 
                 if let Some(ssl_cert_file) = ssl_cert_file {
-                    let service_conf_dir_path = PathBuf::from(format!("{SERVICE_DEST}.d"));
+                    let service_conf_dir_path =
+                        PathBuf::from(format!("{}.d", service_dest.display()));
                     tokio::fs::create_dir(&service_conf_dir_path)
                         .await
                         .map_err(|e| {
@@ -360,10 +468,16 @@ impl Action for ConfigureInitService {
                     .map_err(Self::error)?;
                 }
 
-                if *start_daemon || socket_was_active {
-                    enable(SOCKET_SRC, true).await.map_err(Self::error)?;
+                if root.is_some() {
+                    // The unit was symlinked into place above, `systemctl --root` cannot follow a
+                    // path to a unit outside of the root
+                    enable("nix-daemon.socket", false, root)
+                        .await
+                        .map_err(Self::error)?;
+                } else if *start_daemon || socket_was_active {
+                    enable(SOCKET_SRC, true, root).await.map_err(Self::error)?;
                 } else {
-                    enable(SOCKET_SRC, false).await.map_err(Self::error)?;
+                    enable(SOCKET_SRC, false, root).await.map_err(Self::error)?;
                 }
             },
             #[cfg(not(target_os = "macos"))]
@@ -375,10 +489,132 @@ impl Action for ConfigureInitService {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let mut drift = Vec::new();
+        match self.init {
+            #[cfg(target_os = "macos")]
+            InitSystem::Launchd => {
+                if !std::path::Path::new(DARWIN_NIX_DAEMON_DEST).exists() {
+                    drift.push(format!("`{DARWIN_NIX_DAEMON_DEST}` is missing"));
+                }
+            },
+            #[cfg(target_os = "linux")]
+            InitSystem::Systemd => {
+                for (src, dest) in [
+                    (SERVICE_SRC, SERVICE_DEST),
+                    (SOCKET_SRC, SOCKET_DEST),
+                    (TMPFILES_SRC, TMPFILES_DEST),
+                ] {
+                    drift.extend(
+                        Self::symlink_drift(src, &self.rooted(dest))
+                            .await
+                            .map_err(Self::error)?,
+                    );
+                }
+                if !is_enabled("nix-daemon.socket", self.root.as_deref())
+                    .await
+                    .map_err(Self::error)?
+                {
+                    drift.push("`nix-daemon.socket` is no longer enabled".to_string());
+                }
+            },
+            #[cfg(not(target_os = "macos"))]
+            InitSystem::None => (),
+        }
+        Ok(drift
+            .into_iter()
+            .map(|description| ActionDrift::new(self.tracing_synopsis(), description))
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
+        match self.init {
+            #[cfg(target_os = "linux")]
+            InitSystem::Systemd => {
+                // Executing again would refuse the (still present) `nix-daemon.service.d` override
+                // directory, so only the links and socket enablement are restored
+                let root = self.root.as_deref();
+                for (src, dest) in [
+                    (SERVICE_SRC, SERVICE_DEST),
+                    (SOCKET_SRC, SOCKET_DEST),
+                    (TMPFILES_SRC, TMPFILES_DEST),
+                ] {
+                    let dest = self.rooted(dest);
+                    if dest.is_symlink() {
+                        continue;
+                    }
+                    if dest.exists() {
+                        return Err(Self::error(ActionErrorKind::FileExists(dest)));
+                    }
+                    tracing::trace!(src, dest = %dest.display(), "Symlinking");
+                    tokio::fs::symlink(src, &dest)
+                        .await
+                        .map_err(|e| ActionErrorKind::Symlink(PathBuf::from(src), dest.clone(), e))
+                        .map_err(Self::error)?;
+                }
+
+                if !is_enabled("nix-daemon.socket", root)
+                    .await
+                    .map_err(Self::error)?
+                {
+                    if root.is_some() {
+                        enable("nix-daemon.socket", false, root)
+                            .await
+                            .map_err(Self::error)?;
+                    } else {
+                        execute_command(
+                            Command::new("systemctl")
+                                .process_group(0)
+                                .arg("daemon-reload")
+                                .stdin(std::process::Stdio::null()),
+                            cancel,
+                        )
+                        .await
+                        .map_err(Self::error)?;
+                        enable(SOCKET_SRC, self.start_daemon, root)
+                            .await
+                            .map_err(Self::error)?;
+                    }
+                }
+                Ok(())
+            },
+            #[cfg(target_os = "macos")]
+            InitSystem::Launchd => self.execute(cancel, journal, progress).await,
+            #[cfg(not(target_os = "macos"))]
+            InitSystem::None => Ok(()),
+        }
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         match self.init {
             #[cfg(target_os = "linux")]
             InitSystem::Systemd => {
+                if let Some(root) = &self.root {
+                    return vec![ActionDescription::new(
+                        "Unconfigure Nix daemon related settings with systemd".to_string(),
+                        vec![
+                            format!(
+                                "Run `systemctl --root={} disable nix-daemon.socket`",
+                                root.display()
+                            ),
+                            format!(
+                                "Run `systemctl --root={} disable nix-daemon.service`",
+                                root.display()
+                            ),
+                            format!(
+                                "Run `systemd-tempfiles --root={} --remove --prefix=/nix/var/nix`",
+                                root.display()
+                            ),
+                        ],
+                    )];
+                }
                 vec![ActionDescription::new(
                     "Unconfigure Nix daemon related settings with systemd".to_string(),
                     vec![
@@ -402,7 +638,12 @@ impl Action for ConfigureInitService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         #[cfg_attr(target_os = "macos", allow(unused_mut))]
         let mut errors = vec![];
 
@@ -414,25 +655,33 @@ impl Action for ConfigureInitService {
                         .process_group(0)
                         .arg("unload")
                         .arg(DARWIN_NIX_DAEMON_DEST),
+                    cancel,
                 )
                 .await
                 .map_err(|e| Self::error(e))?;
             },
             #[cfg(target_os = "linux")]
             InitSystem::Systemd => {
+                let (service_dest, tmpfiles_dest) =
+                    (self.rooted(SERVICE_DEST), self.rooted(TMPFILES_DEST));
+                let root = self.root.as_deref();
                 // We separate stop and disable (instead of using `--now`) to avoid cases where the service isn't started, but is enabled.
 
                 // These have to fail fast.
-                let socket_is_active = is_active("nix-daemon.socket")
-                    .await
-                    .map_err(|e| Self::error(e))?;
-                let socket_is_enabled = is_enabled("nix-daemon.socket")
-                    .await
-                    .map_err(|e| Self::error(e))?;
-                let service_is_active = is_active("nix-daemon.service")
+                //
+                // Inside an alternate root, nothing is running.
+                let socket_is_active = root.is_none()
+                    && is_active("nix-daemon.socket")
+                        .await
+                        .map_err(|e| Self::error(e))?;
+                let socket_is_enabled = is_enabled("nix-daemon.socket", root)
                     .await
                     .map_err(|e| Self::error(e))?;
-                let service_is_enabled = is_enabled("nix-daemon.service")
+                let service_is_active = root.is_none()
+                    && is_active("nix-daemon.service")
+                        .await
+                        .map_err(|e| Self::error(e))?;
+                let service_is_enabled = is_enabled("nix-daemon.service", root)
                     .await
                     .map_err(|e| Self::error(e))?;
 
@@ -442,6 +691,7 @@ impl Action for ConfigureInitService {
                             .process_group(0)
                             .args(["stop", "nix-daemon.socket"])
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     {
@@ -450,14 +700,7 @@ impl Action for ConfigureInitService {
                 }
 
                 if socket_is_enabled {
-                    if let Err(err) = execute_command(
-                        Command::new("systemctl")
-                            .process_group(0)
-                            .args(["disable", "nix-daemon.socket"])
-                            .stdin(std::process::Stdio::null()),
-                    )
-                    .await
-                    {
+                    if let Err(err) = disable("nix-daemon.socket", false, root).await {
                         errors.push(err);
                     }
                 }
@@ -468,6 +711,7 @@ impl Action for ConfigureInitService {
                             .process_group(0)
                             .args(["stop", "nix-daemon.service"])
                             .stdin(std::process::Stdio::null()),
+                        cancel,
                     )
                     .await
                     {
@@ -476,14 +720,7 @@ impl Action for ConfigureInitService {
                 }
 
                 if service_is_enabled {
-                    if let Err(err) = execute_command(
-                        Command::new("systemctl")
-                            .process_group(0)
-                            .args(["disable", "nix-daemon.service"])
-                            .stdin(std::process::Stdio::null()),
-                    )
-                    .await
-                    {
+                    if let Err(err) = disable("nix-daemon.service", false, root).await {
                         errors.push(err);
                     }
                 }
@@ -491,9 +728,11 @@ impl Action for ConfigureInitService {
                 if let Err(err) = execute_command(
                     Command::new("systemd-tmpfiles")
                         .process_group(0)
+                        .args(root.map(|root| format!("--root={}", root.display())))
                         .arg("--remove")
                         .arg("--prefix=/nix/var/nix")
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 {
@@ -501,7 +740,8 @@ impl Action for ConfigureInitService {
                 }
 
                 if self.ssl_cert_file.is_some() {
-                    let service_conf_dir_path = PathBuf::from(format!("{SERVICE_DEST}.d"));
+                    let service_conf_dir_path =
+                        PathBuf::from(format!("{}.d", service_dest.display()));
                     if let Err(err) = tokio::fs::remove_dir_all(&service_conf_dir_path)
                         .await
                         .map_err(|e| ActionErrorKind::Remove(service_conf_dir_path.clone(), e))
@@ -510,22 +750,25 @@ impl Action for ConfigureInitService {
                     }
                 }
 
-                if let Err(err) = tokio::fs::remove_file(TMPFILES_DEST)
+                if let Err(err) = tokio::fs::remove_file(&tmpfiles_dest)
                     .await
-                    .map_err(|e| ActionErrorKind::Remove(PathBuf::from(TMPFILES_DEST), e))
+                    .map_err(|e| ActionErrorKind::Remove(tmpfiles_dest.clone(), e))
                 {
                     errors.push(err);
                 }
 
-                if let Err(err) = execute_command(
-                    Command::new("systemctl")
-                        .process_group(0)
-                        .arg("daemon-reload")
-                        .stdin(std::process::Stdio::null()),
-                )
-                .await
-                {
-                    errors.push(err);
+                if root.is_none() {
+                    if let Err(err) = execute_command(
+                        Command::new("systemctl")
+                            .process_group(0)
+                            .arg("daemon-reload")
+                            .stdin(std::process::Stdio::null()),
+                        cancel,
+                    )
+                    .await
+                    {
+                        errors.push(err);
+                    }
                 }
             },
             #[cfg(not(target_os = "macos"))]
@@ -575,8 +818,11 @@ async fn stop(unit: &str) -> Result<(), ActionErrorKind> {
 }
 
 #[cfg(target_os = "linux")]
-async fn enable(unit: &str, now: bool) -> Result<(), ActionErrorKind> {
+async fn enable(unit: &str, now: bool, root: Option<&Path>) -> Result<(), ActionErrorKind> {
     let mut command = Command::new("systemctl");
+    if let Some(root) = root {
+        command.arg(format!("--root={}", root.display()));
+    }
     command.arg("enable");
     command.arg(unit);
     if now {
@@ -596,8 +842,11 @@ async fn enable(unit: &str, now: bool) -> Result<(), ActionErrorKind> {
 }
 
 #[cfg(target_os = "linux")]
-async fn disable(unit: &str, now: bool) -> Result<(), ActionErrorKind> {
+async fn disable(unit: &str, now: bool, root: Option<&Path>) -> Result<(), ActionErrorKind> {
     let mut command = Command::new("systemctl");
+    if let Some(root) = root {
+        command.arg(format!("--root={}", root.display()));
+    }
     command.arg("disable");
     command.arg(unit);
     if now {
@@ -635,8 +884,11 @@ async fn is_active(unit: &str) -> Result<bool, ActionErrorKind> {
 }
 
 #[cfg(target_os = "linux")]
-async fn is_enabled(unit: &str) -> Result<bool, ActionErrorKind> {
+async fn is_enabled(unit: &str, root: Option<&Path>) -> Result<bool, ActionErrorKind> {
     let mut command = Command::new("systemctl");
+    if let Some(root) = root {
+        command.arg(format!("--root={}", root.display()));
+    }
     command.arg("is-enabled");
     command.arg(unit);
     let output = command
@@ -652,3 +904,31 @@ async fn is_enabled(unit: &str) -> Result<bool, ActionErrorKind> {
         Ok(false)
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn checks_symlinks_without_following_them() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let dest = temp_dir.path().join("nix-daemon.conf");
+        assert!(!ConfigureInitService::check_if_symlink_exists(TMPFILES_SRC, &dest).await?);
+
+        // Inside a root the link dangles on a host without Nix, it is still there
+        tokio::fs::symlink(TMPFILES_SRC, &dest).await?;
+        assert!(ConfigureInitService::check_if_symlink_exists(TMPFILES_SRC, &dest).await?);
+        assert!(matches!(
+            ConfigureInitService::check_if_symlink_exists(SERVICE_SRC, &dest).await,
+            Err(ActionErrorKind::SymlinkExists(_))
+        ));
+
+        tokio::fs::remove_file(&dest).await?;
+        tokio::fs::write(&dest, "").await?;
+        assert!(matches!(
+            ConfigureInitService::check_if_symlink_exists(TMPFILES_SRC, &dest).await,
+            Err(ActionErrorKind::FileExists(_))
+        ));
+        Ok(())
+    }
+}
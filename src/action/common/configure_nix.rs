@@ -1,10 +1,9 @@
-use std::path::PathBuf;
-
 use crate::{
     action::{
         base::SetupDefaultProfile,
         common::{ConfigureShellProfile, PlaceNixConfiguration},
-        Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+        Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+        ActionTag, CancellationToken, Journal, Progress, StatefulAction,
     },
     planner::ShellProfileLocations,
     settings::{CommonSettings, SCRATCH_DIR},
@@ -28,15 +27,17 @@ impl ConfigureNix {
         shell_profile_locations: ShellProfileLocations,
         settings: &CommonSettings,
     ) -> Result<StatefulAction<Self>, ActionError> {
-        let setup_default_profile = SetupDefaultProfile::plan(PathBuf::from(SCRATCH_DIR))
-            .await
-            .map_err(Self::error)?;
+        let setup_default_profile =
+            SetupDefaultProfile::plan(settings.rooted(SCRATCH_DIR), settings.root.clone())
+                .await
+                .map_err(Self::error)?;
 
         let configure_shell_profile = if settings.modify_profile {
             Some(
                 ConfigureShellProfile::plan(
                     shell_profile_locations,
                     settings.ssl_cert_file.clone(),
+                    settings.root.clone(),
                 )
                 .await
                 .map_err(Self::error)?,
@@ -47,7 +48,12 @@ impl ConfigureNix {
         let place_nix_configuration = PlaceNixConfiguration::plan(
             settings.nix_build_group_name.clone(),
             settings.extra_conf.clone(),
+            settings.binary_caches(),
+            settings.nix_conf_preset,
+            settings.nix_conf_conflict_policy,
+            settings.nix_conf_drop_in,
             settings.force,
+            settings.root.clone(),
         )
         .await
         .map_err(Self::error)?;
@@ -75,6 +81,22 @@ impl Action for ConfigureNix {
         span!(tracing::Level::DEBUG, "configure_nix",)
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        let Self {
+            setup_default_profile,
+            place_nix_configuration,
+            configure_shell_profile,
+        } = self;
+
+        ActionDependency::combine([
+            setup_default_profile.dependencies(),
+            place_nix_configuration.dependencies(),
+            configure_shell_profile
+                .as_ref()
+                .map_or(Some(vec![]), |action| action.dependencies()),
+        ])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         let Self {
             setup_default_profile,
@@ -91,63 +113,125 @@ impl Action for ConfigureNix {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             setup_default_profile,
             place_nix_configuration,
             configure_shell_profile,
         } = self;
 
+        // If one of these fails the others are cancelled, rather than dropped part way through, so the receipt
+        // reflects how far each of them got
+        let cancel = cancel.child_token();
+        let span = tracing::Span::current().clone();
+        let results = tokio::join!(
+            cancel_siblings_on_error(
+                &cancel,
+                setup_default_profile
+                    .try_execute(&cancel, journal, progress)
+                    .instrument(span.clone())
+            ),
+            cancel_siblings_on_error(
+                &cancel,
+                place_nix_configuration
+                    .try_execute(&cancel, journal, progress)
+                    .instrument(span.clone())
+            ),
+            async {
+                match configure_shell_profile {
+                    Some(configure_shell_profile) => {
+                        cancel_siblings_on_error(
+                            &cancel,
+                            configure_shell_profile
+                                .try_execute(&cancel, journal, progress)
+                                .instrument(span.clone()),
+                        )
+                        .await
+                    },
+                    None => Ok(()),
+                }
+            },
+        );
+
+        let mut errors: Vec<_> = [results.0, results.1, results.2]
+            .into_iter()
+            .filter_map(Result::err)
+            .collect();
+        // Siblings which were cancelled because of another failure are not worth reporting
+        if errors.iter().any(|err| !err.is_cancelled()) {
+            errors.retain(|err| !err.is_cancelled());
+        }
+        if errors.len() > 1 {
+            return Err(Self::error(ActionErrorKind::MultipleChildren(errors)));
+        }
+        if let Some(err) = errors.pop() {
+            return Err(Self::error(err));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            setup_default_profile,
+            configure_shell_profile,
+            place_nix_configuration,
+        } = self;
+
+        let mut drift = setup_default_profile
+            .try_verify()
+            .await
+            .map_err(Self::error)?;
+        drift.extend(
+            place_nix_configuration
+                .try_verify()
+                .await
+                .map_err(Self::error)?,
+        );
         if let Some(configure_shell_profile) = configure_shell_profile {
-            let setup_default_profile_span = tracing::Span::current().clone();
-            let (place_nix_configuration_span, configure_shell_profile_span) = (
-                setup_default_profile_span.clone(),
-                setup_default_profile_span.clone(),
+            drift.extend(
+                configure_shell_profile
+                    .try_verify()
+                    .await
+                    .map_err(Self::error)?,
             );
-            tokio::try_join!(
-                async move {
-                    setup_default_profile
-                        .try_execute()
-                        .instrument(setup_default_profile_span)
-                        .await
-                        .map_err(Self::error)
-                },
-                async move {
-                    place_nix_configuration
-                        .try_execute()
-                        .instrument(place_nix_configuration_span)
-                        .await
-                        .map_err(Self::error)
-                },
-                async move {
-                    configure_shell_profile
-                        .try_execute()
-                        .instrument(configure_shell_profile_span)
-                        .await
-                        .map_err(Self::error)
-                },
-            )?;
-        } else {
-            let setup_default_profile_span = tracing::Span::current().clone();
-            let place_nix_configuration_span = setup_default_profile_span.clone();
-            tokio::try_join!(
-                async move {
-                    setup_default_profile
-                        .try_execute()
-                        .instrument(setup_default_profile_span)
-                        .await
-                        .map_err(Self::error)
-                },
-                async move {
-                    place_nix_configuration
-                        .try_execute()
-                        .instrument(place_nix_configuration_span)
-                        .await
-                        .map_err(Self::error)
-                },
-            )?;
-        };
+        }
+        Ok(drift)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let Self {
+            setup_default_profile,
+            configure_shell_profile,
+            place_nix_configuration,
+        } = self;
 
+        setup_default_profile
+            .try_repair(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        place_nix_configuration
+            .try_repair(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        if let Some(configure_shell_profile) = configure_shell_profile {
+            configure_shell_profile
+                .try_repair(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
         Ok(())
     }
 
@@ -169,17 +253,33 @@ impl Action for ConfigureNix {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
         if let Some(configure_shell_profile) = &mut self.configure_shell_profile {
-            if let Err(err) = configure_shell_profile.try_revert().await {
+            if let Err(err) = configure_shell_profile
+                .try_revert(cancel, journal, progress)
+                .await
+            {
                 errors.push(err);
             }
         }
-        if let Err(err) = self.place_nix_configuration.try_revert().await {
+        if let Err(err) = self
+            .place_nix_configuration
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err);
         }
-        if let Err(err) = self.setup_default_profile.try_revert().await {
+        if let Err(err) = self
+            .setup_default_profile
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err);
         }
 
@@ -195,3 +295,15 @@ impl Action for ConfigureNix {
         }
     }
 }
+
+/// Await `future` (the execution of one of several sibling actions), cancelling the siblings if it fails
+async fn cancel_siblings_on_error(
+    cancel: &CancellationToken,
+    future: impl std::future::Future<Output = Result<(), ActionError>>,
+) -> Result<(), ActionError> {
+    let res = future.await;
+    if res.is_err() {
+        cancel.cancel();
+    }
+    res
+}
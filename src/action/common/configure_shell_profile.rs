@@ -1,8 +1,10 @@
 use crate::action::base::{create_or_insert_into_file, CreateDirectory, CreateOrInsertIntoFile};
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
 use crate::planner::ShellProfileLocations;
+use crate::settings::rooted;
 
 use nix::unistd::User;
 use std::path::{Path, PathBuf};
@@ -27,6 +29,7 @@ impl ConfigureShellProfile {
     pub async fn plan(
         locations: ShellProfileLocations,
         ssl_cert_file: Option<PathBuf>,
+        root: Option<PathBuf>,
     ) -> Result<StatefulAction<Self>, ActionError> {
         let mut create_or_insert_files = Vec::default();
         let mut create_directories = Vec::default();
@@ -54,7 +57,8 @@ impl ConfigureShellProfile {
         );
 
         for profile_target in locations.bash.iter().chain(locations.zsh.iter()) {
-            let profile_target_path = Path::new(profile_target);
+            let profile_target = rooted(root.as_deref(), profile_target);
+            let profile_target_path = Path::new(&profile_target);
             if let Some(parent) = profile_target_path.parent() {
                 if !parent.exists() {
                     tracing::trace!(
@@ -90,7 +94,7 @@ impl ConfigureShellProfile {
         );
 
         for fish_prefix in &locations.fish.confd_prefixes {
-            let fish_prefix_path = PathBuf::from(fish_prefix);
+            let fish_prefix_path = rooted(root.as_deref(), fish_prefix);
 
             if !fish_prefix_path.exists() {
                 // If the prefix doesn't exist, don't create the `conf.d/nix.fish`
@@ -102,7 +106,8 @@ impl ConfigureShellProfile {
 
             if let Some(conf_d) = profile_target.parent() {
                 create_directories.push(
-                    CreateDirectory::plan(conf_d.to_path_buf(), None, None, 0o755, false).await?,
+                    CreateDirectory::plan(conf_d.to_path_buf(), None, None, 0o755, false, None)
+                        .await?,
                 );
             }
 
@@ -119,7 +124,7 @@ impl ConfigureShellProfile {
             );
         }
         for fish_prefix in &locations.fish.vendor_confd_prefixes {
-            let fish_prefix_path = PathBuf::from(fish_prefix);
+            let fish_prefix_path = rooted(root.as_deref(), fish_prefix);
 
             if !fish_prefix_path.exists() {
                 // If the prefix doesn't exist, don't create the `conf.d/nix.fish`
@@ -131,7 +136,8 @@ impl ConfigureShellProfile {
 
             if let Some(conf_d) = profile_target.parent() {
                 create_directories.push(
-                    CreateDirectory::plan(conf_d.to_path_buf(), None, None, 0o755, false).await?,
+                    CreateDirectory::plan(conf_d.to_path_buf(), None, None, 0o755, false, None)
+                        .await?,
                 );
             }
 
@@ -149,8 +155,9 @@ impl ConfigureShellProfile {
         }
 
         // If the `$GITHUB_PATH` environment exists, we're almost certainly running on Github
-        // Actions, and almost certainly wants the relevant `$PATH` additions added.
-        if let Ok(github_path) = std::env::var("GITHUB_PATH") {
+        // Actions, and almost certainly wants the relevant `$PATH` additions added. This does not
+        // apply when installing into an alternate root, as Nix will not be usable on the host.
+        if let (Ok(github_path), None) = (std::env::var("GITHUB_PATH"), &root) {
             let mut buf = "/nix/var/nix/profiles/default/bin\n".to_string();
             // Actions runners operate as `runner` user by default
             if let Ok(Some(runner)) = User::from_name("runner") {
@@ -192,6 +199,19 @@ impl Action for ConfigureShellProfile {
         span!(tracing::Level::DEBUG, "configure_shell_profile",)
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        ActionDependency::combine(
+            self.create_directories
+                .iter()
+                .map(|action| action.dependencies())
+                .chain(
+                    self.create_or_insert_into_files
+                        .iter()
+                        .map(|action| action.dependencies()),
+                ),
+        )
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             self.tracing_synopsis(),
@@ -200,9 +220,16 @@ impl Action for ConfigureShellProfile {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         for create_directory in &mut self.create_directories {
-            create_directory.try_execute().await?;
+            create_directory
+                .try_execute(cancel, journal, progress)
+                .await?;
         }
 
         let mut set = JoinSet::new();
@@ -212,23 +239,29 @@ impl Action for ConfigureShellProfile {
             self.create_or_insert_into_files.iter_mut().enumerate()
         {
             let span = tracing::Span::current().clone();
+            let cancel = cancel.clone();
+            let journal = journal.clone();
+            let progress = progress.clone();
             let mut create_or_insert_into_file_clone = create_or_insert_into_file.clone();
             let _abort_handle = set.spawn(async move {
-                create_or_insert_into_file_clone
-                    .try_execute()
+                let res = create_or_insert_into_file_clone
+                    .try_execute(&cancel, &journal, &progress)
                     .instrument(span)
                     .await
-                    .map_err(Self::error)?;
-                Result::<_, ActionError>::Ok((idx, create_or_insert_into_file_clone))
+                    .map_err(Self::error);
+                (idx, create_or_insert_into_file_clone, res)
             });
         }
 
         while let Some(result) = set.join_next().await {
             match result {
-                Ok(Ok((idx, create_or_insert_into_file))) => {
-                    self.create_or_insert_into_files[idx] = create_or_insert_into_file
+                // Keep the state of failed (or cancelled) children too, so the receipt reflects how far they got
+                Ok((idx, create_or_insert_into_file, res)) => {
+                    self.create_or_insert_into_files[idx] = create_or_insert_into_file;
+                    if let Err(e) = res {
+                        errors.push(e)
+                    }
                 },
-                Ok(Err(e)) => errors.push(e),
                 Err(e) => return Err(Self::error(e))?,
             };
         }
@@ -246,6 +279,45 @@ impl Action for ConfigureShellProfile {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let mut drift = Vec::new();
+        for create_directory in &self.create_directories {
+            drift.extend(create_directory.try_verify().await.map_err(Self::error)?);
+        }
+        for create_or_insert_into_file in &self.create_or_insert_into_files {
+            drift.extend(
+                create_or_insert_into_file
+                    .try_verify()
+                    .await
+                    .map_err(Self::error)?,
+            );
+        }
+        Ok(drift)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        for create_directory in &mut self.create_directories {
+            create_directory
+                .try_repair(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
+        for create_or_insert_into_file in &mut self.create_or_insert_into_files {
+            create_or_insert_into_file
+                .try_repair(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
+        Ok(())
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             "Unconfigure the shell profiles".to_string(),
@@ -254,33 +326,45 @@ impl Action for ConfigureShellProfile {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut set = JoinSet::new();
         let mut errors = vec![];
 
         for (idx, create_or_insert_into_file) in
             self.create_or_insert_into_files.iter_mut().enumerate()
         {
+            let cancel = cancel.clone();
+            let journal = journal.clone();
+            let progress = progress.clone();
             let mut create_or_insert_file_clone = create_or_insert_into_file.clone();
             let _abort_handle = set.spawn(async move {
-                create_or_insert_file_clone.try_revert().await?;
-                Result::<_, _>::Ok((idx, create_or_insert_file_clone))
+                let res = create_or_insert_file_clone
+                    .try_revert(&cancel, &journal, &progress)
+                    .await;
+                (idx, create_or_insert_file_clone, res)
             });
         }
 
         while let Some(result) = set.join_next().await {
             match result {
-                Ok(Ok((idx, create_or_insert_into_file))) => {
-                    self.create_or_insert_into_files[idx] = create_or_insert_into_file
+                Ok((idx, create_or_insert_into_file, res)) => {
+                    self.create_or_insert_into_files[idx] = create_or_insert_into_file;
+                    if let Err(e) = res {
+                        errors.push(e)
+                    }
                 },
-                Ok(Err(e)) => errors.push(e),
                 // This is quite rare and generally a very bad sign.
                 Err(e) => return Err(e).map_err(|e| Self::error(ActionErrorKind::from(e)))?,
             };
         }
 
         for create_directory in self.create_directories.iter_mut() {
-            if let Err(err) = create_directory.try_revert().await {
+            if let Err(err) = create_directory.try_revert(cancel, journal, progress).await {
                 errors.push(err);
             }
         }
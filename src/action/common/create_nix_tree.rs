@@ -1,9 +1,13 @@
+use std::path::PathBuf;
+
 use tracing::{span, Span};
 
 use crate::action::base::CreateDirectory;
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
+use crate::settings::rooted;
 
 const PATHS: &[&str] = &[
     "/nix/var",
@@ -31,14 +35,21 @@ pub struct CreateNixTree {
 
 impl CreateNixTree {
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn plan() -> Result<StatefulAction<Self>, ActionError> {
+    pub async fn plan(root: Option<PathBuf>) -> Result<StatefulAction<Self>, ActionError> {
         let mut create_directories = Vec::default();
         for path in PATHS {
             // We use `create_dir` over `create_dir_all` to ensure we always set permissions right
             create_directories.push(
-                CreateDirectory::plan(path, String::from("root"), None, 0o0755, false)
-                    .await
-                    .map_err(Self::error)?,
+                CreateDirectory::plan(
+                    rooted(root.as_deref(), path),
+                    String::from("root"),
+                    None,
+                    0o0755,
+                    false,
+                    root.clone(),
+                )
+                .await
+                .map_err(Self::error)?,
             )
         }
 
@@ -60,6 +71,14 @@ impl Action for CreateNixTree {
         span!(tracing::Level::DEBUG, "create_nix_tree",)
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        ActionDependency::combine(
+            self.create_directories
+                .iter()
+                .map(|action| action.dependencies()),
+        )
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         let Self { create_directories } = &self;
 
@@ -76,12 +95,45 @@ impl Action for CreateNixTree {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         // Just do sequential since parallelizing this will have little benefit
         for create_directory in self.create_directories.iter_mut() {
-            create_directory.try_execute().await.map_err(Self::error)?;
+            create_directory
+                .try_execute(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let mut drift = Vec::new();
+        for create_directory in &self.create_directories {
+            drift.extend(create_directory.try_verify().await.map_err(Self::error)?);
         }
+        Ok(drift)
+    }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        for create_directory in &mut self.create_directories {
+            create_directory
+                .try_repair(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
         Ok(())
     }
 
@@ -106,11 +158,16 @@ impl Action for CreateNixTree {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
         // Just do sequential since parallelizing this will have little benefit
         for create_directory in self.create_directories.iter_mut().rev() {
-            if let Err(err) = create_directory.try_revert().await {
+            if let Err(err) = create_directory.try_revert(cancel, journal, progress).await {
                 errors.push(err);
             }
         }
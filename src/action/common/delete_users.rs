@@ -1,7 +1,8 @@
 use crate::action::{
-    base::DeleteUser, Action, ActionDescription, ActionError, ActionErrorKind, ActionTag,
-    StatefulAction,
+    base::DeleteUser, Action, ActionDependency, ActionDescription, ActionDrift, ActionError,
+    ActionErrorKind, ActionTag, CancellationToken, Journal, Progress, StatefulAction,
 };
+use std::path::PathBuf;
 use tracing::{span, Span};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
@@ -17,10 +18,11 @@ impl DeleteUsersInGroup {
         group_name: String,
         group_id: u32,
         users: Vec<String>,
+        root: Option<PathBuf>,
     ) -> Result<StatefulAction<Self>, ActionError> {
         let mut delete_users = vec![];
         for users in users {
-            delete_users.push(DeleteUser::plan(users).await?)
+            delete_users.push(DeleteUser::plan(users, root.clone()).await?)
         }
 
         Ok(Self {
@@ -55,6 +57,14 @@ impl Action for DeleteUsersInGroup {
         )
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        let mut dependencies = ActionDependency::combine(
+            self.delete_users.iter().map(|action| action.dependencies()),
+        )?;
+        dependencies.push(ActionDependency::Group(self.group_name.clone()));
+        Some(dependencies)
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         let mut delete_users_descriptions = Vec::new();
         for delete_user in self.delete_users.iter() {
@@ -72,9 +82,42 @@ impl Action for DeleteUsersInGroup {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         for delete_user in self.delete_users.iter_mut() {
-            delete_user.try_execute().await.map_err(Self::error)?;
+            delete_user
+                .try_execute(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let mut drift = Vec::new();
+        for delete_user in &self.delete_users {
+            drift.extend(delete_user.try_verify().await.map_err(Self::error)?);
+        }
+        Ok(drift)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        for delete_user in &mut self.delete_users {
+            delete_user
+                .try_repair(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
         }
         Ok(())
     }
@@ -96,10 +139,15 @@ impl Action for DeleteUsersInGroup {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
         for delete_user in self.delete_users.iter_mut() {
-            if let Err(err) = delete_user.try_revert().await {
+            if let Err(err) = delete_user.try_revert(cancel, journal, progress).await {
                 errors.push(err);
             }
         }
@@ -13,5 +13,7 @@ pub use configure_nix::ConfigureNix;
 pub use configure_shell_profile::ConfigureShellProfile;
 pub use create_nix_tree::CreateNixTree;
 pub use delete_users::DeleteUsersInGroup;
-pub use place_nix_configuration::PlaceNixConfiguration;
+pub use place_nix_configuration::{
+    BinaryCaches, NixConfigPreset, PlaceNixConfiguration, PlaceNixConfigurationError, PresetSetting,
+};
 pub use provision_nix::ProvisionNix;
@@ -4,12 +4,13 @@ use tracing::{span, Span};
 use super::{CreateNixTree, DeleteUsersInGroup};
 use crate::{
     action::{
-        base::{CreateGroup, FetchAndUnpackNix, MoveUnpackedNix},
-        Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+        base::{CreateGroup, FetchAndUnpackNix, FetchNixOptions, MoveUnpackedNix},
+        Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+        ActionTag, CancellationToken, Journal, Progress, StatefulAction,
     },
+    os::etc_files,
     settings::{CommonSettings, SCRATCH_DIR},
 };
-use std::path::PathBuf;
 
 /**
 Place Nix and it's requirements onto the target
@@ -26,36 +27,62 @@ pub struct ProvisionNix {
 impl ProvisionNix {
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn plan(settings: &CommonSettings) -> Result<StatefulAction<Self>, ActionError> {
+        let package = settings
+            .resolve_nix_package()
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Custom(Box::new(e))))?;
+        let mut urls = package.urls.into_iter();
+        let url = urls
+            .next()
+            .expect("The resolved Nix package URL is always one of the URLs");
+        let credentials = settings.nix_package_credentials(Some(&url));
         let fetch_nix = FetchAndUnpackNix::plan(
-            settings.nix_package_url.clone(),
-            PathBuf::from(SCRATCH_DIR),
-            settings.proxy.clone(),
-            settings.ssl_cert_file.clone(),
+            url,
+            settings.rooted(SCRATCH_DIR),
+            FetchNixOptions {
+                fallback_urls: urls.collect(),
+                version: package.version,
+                sha256: package.sha256,
+                signature_url: settings.nix_package_signature_url.clone(),
+                trusted_keys: settings.nix_package_trusted_keys.clone(),
+                cache_dir: settings.nix_package_cache_dir.clone(),
+                offline: settings.offline,
+                proxy: settings.proxy.clone(),
+                ssl_cert_file: settings.ssl_cert_file.clone(),
+                credentials,
+            },
         )
         .await?;
 
-        let delete_users_in_group = if let Some(group) =
-            Group::from_name(settings.nix_build_group_name.as_str())
+        let existing_group = match &settings.root {
+            Some(root) => etc_files::find_group(root, &settings.nix_build_group_name)
+                .await
+                .map_err(Self::error)?
+                .map(|group| (group.gid, group.members)),
+            None => Group::from_name(settings.nix_build_group_name.as_str())
                 .map_err(|e| {
                     ActionErrorKind::GettingGroupId(settings.nix_build_group_name.clone(), e)
                 })
                 .map_err(Self::error)?
-        {
-            if group.gid.as_raw() != settings.nix_build_group_id {
+                .map(|group| (group.gid.as_raw(), group.mem)),
+        };
+        let delete_users_in_group = if let Some((gid, members)) = existing_group {
+            if gid != settings.nix_build_group_id {
                 return Err(Self::error(ActionErrorKind::GroupGidMismatch(
                     settings.nix_build_group_name.clone(),
-                    group.gid.as_raw(),
+                    gid,
                     settings.nix_build_group_id,
                 )));
             }
-            if group.mem.is_empty() {
+            if members.is_empty() {
                 None
             } else {
                 Some(
                     DeleteUsersInGroup::plan(
                         settings.nix_build_group_name.clone(),
                         settings.nix_build_group_id,
-                        group.mem,
+                        members,
+                        settings.root.clone(),
                     )
                     .await?,
                 )
@@ -67,12 +94,17 @@ impl ProvisionNix {
         let create_group = CreateGroup::plan(
             settings.nix_build_group_name.clone(),
             settings.nix_build_group_id,
+            settings.root.clone(),
         )
+        .await
         .map_err(Self::error)?;
-        let create_nix_tree = CreateNixTree::plan().await.map_err(Self::error)?;
-        let move_unpacked_nix = MoveUnpackedNix::plan(PathBuf::from(SCRATCH_DIR))
+        let create_nix_tree = CreateNixTree::plan(settings.root.clone())
             .await
             .map_err(Self::error)?;
+        let move_unpacked_nix =
+            MoveUnpackedNix::plan(settings.rooted(SCRATCH_DIR), settings.root.clone())
+                .await
+                .map_err(Self::error)?;
         Ok(Self {
             fetch_nix,
             delete_users_in_group,
@@ -82,6 +114,31 @@ impl ProvisionNix {
         }
         .into())
     }
+
+    /// Execute the actions which do not depend on Nix having been fetched
+    async fn execute_alongside_fetch(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        if let Some(delete_users_in_group) = &mut self.delete_users_in_group {
+            delete_users_in_group
+                .try_execute(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?;
+        }
+
+        self.create_group
+            .try_execute(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        self.create_nix_tree
+            .try_execute(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -98,6 +155,26 @@ impl Action for ProvisionNix {
         span!(tracing::Level::DEBUG, "provision_nix",)
     }
 
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        let Self {
+            fetch_nix,
+            delete_users_in_group,
+            create_group,
+            create_nix_tree,
+            move_unpacked_nix,
+        } = self;
+
+        ActionDependency::combine([
+            fetch_nix.dependencies(),
+            delete_users_in_group
+                .as_ref()
+                .map_or(Some(vec![]), |action| action.dependencies()),
+            create_group.dependencies(),
+            create_nix_tree.dependencies(),
+            move_unpacked_nix.dependencies(),
+        ])
+    }
+
     fn execute_description(&self) -> Vec<ActionDescription> {
         let Self {
             fetch_nix,
@@ -122,36 +199,108 @@ impl Action for ProvisionNix {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         // We fetch nix while doing the rest, then move it over.
+        let fetch_cancel = cancel.child_token();
         let mut fetch_nix_clone = self.fetch_nix.clone();
-        let fetch_nix_handle = tokio::task::spawn(async {
-            fetch_nix_clone.try_execute().await.map_err(Self::error)?;
-            Result::<_, ActionError>::Ok(fetch_nix_clone)
+        let fetch_nix_handle = tokio::task::spawn({
+            let fetch_cancel = fetch_cancel.clone();
+            let journal = journal.clone();
+            let progress = progress.clone();
+            async move {
+                let res = fetch_nix_clone
+                    .try_execute(&fetch_cancel, &journal, &progress)
+                    .await;
+                (fetch_nix_clone, res)
+            }
         });
 
-        if let Some(delete_users_in_group) = &mut self.delete_users_in_group {
+        let res = self
+            .execute_alongside_fetch(cancel, journal, progress)
+            .await;
+        if res.is_err() {
+            fetch_cancel.cancel();
+        }
+        // Even if the rest failed, wait for the fetch so the receipt reflects how far it got
+        let (fetch_nix, fetch_res) = fetch_nix_handle
+            .await
+            .map_err(ActionErrorKind::Join)
+            .map_err(Self::error)?;
+        self.fetch_nix = fetch_nix;
+        res?;
+        fetch_res.map_err(Self::error)?;
+
+        self.move_unpacked_nix
+            .try_execute(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self {
+            fetch_nix: _,
+            delete_users_in_group,
+            create_group,
+            create_nix_tree,
+            move_unpacked_nix,
+        } = self;
+
+        let mut drift = Vec::new();
+        if let Some(delete_users_in_group) = delete_users_in_group {
+            drift.extend(
+                delete_users_in_group
+                    .try_verify()
+                    .await
+                    .map_err(Self::error)?,
+            );
+        }
+        drift.extend(create_group.try_verify().await.map_err(Self::error)?);
+        drift.extend(create_nix_tree.try_verify().await.map_err(Self::error)?);
+        drift.extend(move_unpacked_nix.try_verify().await.map_err(Self::error)?);
+        Ok(drift)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        let Self {
+            fetch_nix: _,
+            delete_users_in_group,
+            create_group,
+            create_nix_tree,
+            move_unpacked_nix,
+        } = self;
+
+        if let Some(delete_users_in_group) = delete_users_in_group {
             delete_users_in_group
-                .try_execute()
+                .try_repair(cancel, journal, progress)
                 .await
                 .map_err(Self::error)?;
         }
-
-        self.create_group.try_execute().await.map_err(Self::error)?;
-        self.create_nix_tree
-            .try_execute()
+        create_group
+            .try_repair(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
-
-        self.fetch_nix = fetch_nix_handle
+        create_nix_tree
+            .try_repair(cancel, journal, progress)
             .await
-            .map_err(ActionErrorKind::Join)
-            .map_err(Self::error)??;
-        self.move_unpacked_nix
-            .try_execute()
+            .map_err(Self::error)?;
+        move_unpacked_nix
+            .try_repair(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
-
         Ok(())
     }
 
@@ -178,28 +327,45 @@ impl Action for ProvisionNix {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
 
-        if let Err(err) = self.fetch_nix.try_revert().await {
+        if let Err(err) = self.fetch_nix.try_revert(cancel, journal, progress).await {
             errors.push(err)
         }
 
         if let Some(delete_users_in_group) = &mut self.delete_users_in_group {
             delete_users_in_group
-                .try_revert()
+                .try_revert(cancel, journal, progress)
                 .await
                 .map_err(Self::error)?;
         }
 
-        if let Err(err) = self.create_group.try_revert().await {
+        if let Err(err) = self
+            .create_group
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
-        if let Err(err) = self.create_nix_tree.try_revert().await {
+        if let Err(err) = self
+            .create_nix_tree
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
 
-        if let Err(err) = self.move_unpacked_nix.try_revert().await {
+        if let Err(err) = self
+            .move_unpacked_nix
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
 
@@ -1,7 +1,10 @@
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionState, ActionTag, StatefulAction};
+use crate::action::{
+    ActionDrift, ActionError, ActionErrorKind, ActionState, ActionTag, CancellationToken, Journal,
+    Progress, StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -43,6 +46,8 @@ impl StartSystemdUnit {
                 enable,
             },
             state,
+            retry: None,
+            attempts: 0,
         })
     }
 }
@@ -70,7 +75,12 @@ impl Action for StartSystemdUnit {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { unit, enable } = self;
 
         match enable {
@@ -83,6 +93,7 @@ impl Action for StartSystemdUnit {
                         .arg("--now")
                         .arg(format!("{unit}"))
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -95,6 +106,7 @@ impl Action for StartSystemdUnit {
                         .arg("start")
                         .arg(format!("{unit}"))
                         .stdin(std::process::Stdio::null()),
+                    cancel,
                 )
                 .await
                 .map_err(Self::error)?;
@@ -104,6 +116,30 @@ impl Action for StartSystemdUnit {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let Self { unit, enable } = self;
+
+        if !*enable {
+            // Whether the unit is still running is not part of the lasting effect of this action
+            return Ok(vec![]);
+        }
+        let mut command = Command::new("systemctl");
+        command.arg("is-enabled");
+        command.arg(unit);
+        let output = command
+            .output()
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::command(&command, e)))?;
+        if !output.status.success() {
+            return Ok(vec![ActionDrift::new(
+                self.tracing_synopsis(),
+                format!("`{unit}` is no longer enabled"),
+            )]);
+        }
+        Ok(vec![])
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             format!("Disable (and stop) the systemd unit {}", self.unit),
@@ -112,7 +148,12 @@ impl Action for StartSystemdUnit {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
 
         if self.enable {
@@ -122,6 +163,7 @@ impl Action for StartSystemdUnit {
                     .arg("disable")
                     .arg(format!("{}", self.unit))
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)
@@ -137,6 +179,7 @@ impl Action for StartSystemdUnit {
                 .arg("stop")
                 .arg(format!("{}", self.unit))
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)
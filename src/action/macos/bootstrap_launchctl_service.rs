@@ -3,7 +3,9 @@ use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag, StatefulAction};
+use crate::action::{
+    ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress, StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -87,7 +89,12 @@ impl Action for BootstrapLaunchctlService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             domain,
             service: _,
@@ -101,6 +108,7 @@ impl Action for BootstrapLaunchctlService {
                 .arg(domain)
                 .arg(path)
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
@@ -120,7 +128,12 @@ impl Action for BootstrapLaunchctlService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         execute_command(
             Command::new("launchctl")
                 .process_group(0)
@@ -128,6 +141,7 @@ impl Action for BootstrapLaunchctlService {
                 .arg(&self.domain)
                 .arg(&self.path)
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionTag, StatefulAction};
+use crate::action::{ActionError, ActionTag, CancellationToken, Journal, Progress, StatefulAction};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -23,10 +23,12 @@ impl CreateApfsVolume {
         name: String,
         case_sensitive: bool,
     ) -> Result<StatefulAction<Self>, ActionError> {
-        let output =
-            execute_command(Command::new("/usr/sbin/diskutil").args(["apfs", "list", "-plist"]))
-                .await
-                .map_err(Self::error)?;
+        let output = execute_command(
+            Command::new("/usr/sbin/diskutil").args(["apfs", "list", "-plist"]),
+            None,
+        )
+        .await
+        .map_err(Self::error)?;
 
         let parsed: DiskUtilApfsListOutput =
             plist::from_bytes(&output.stdout).map_err(Self::error)?;
@@ -79,7 +81,12 @@ impl Action for CreateApfsVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             disk,
             name,
@@ -102,6 +109,7 @@ impl Action for CreateApfsVolume {
                     "-nomount",
                 ])
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
@@ -121,12 +129,18 @@ impl Action for CreateApfsVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         execute_command(
             Command::new("/usr/sbin/diskutil")
                 .process_group(0)
                 .args(["apfs", "deleteVolume", &self.name])
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
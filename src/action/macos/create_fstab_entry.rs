@@ -2,7 +2,8 @@ use uuid::Uuid;
 
 use super::{get_uuid_for_label, CreateApfsVolume};
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionState, ActionTag, StatefulAction,
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionState, ActionTag,
+    CancellationToken, Journal, Progress, StatefulAction,
 };
 use std::{io::SeekFrom, path::Path};
 use tokio::{
@@ -116,7 +117,12 @@ impl Action for CreateFstabEntry {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             apfs_volume_label,
             existing_entry,
@@ -232,7 +238,12 @@ impl Action for CreateFstabEntry {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let fstab_path = Path::new(FSTAB_PATH);
 
         if let Some(uuid) = get_uuid_for_label(&self.apfs_volume_label)
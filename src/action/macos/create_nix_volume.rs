@@ -4,7 +4,8 @@ use crate::action::{
         BootstrapLaunchctlService, CreateApfsVolume, CreateSyntheticObjects, EnableOwnership,
         EncryptApfsVolume, UnmountApfsVolume,
     },
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDescription, ActionDrift, ActionError, ActionErrorKind, ActionTag,
+    CancellationToken, Journal, Progress, StatefulAction,
 };
 use std::{
     path::{Path, PathBuf},
@@ -164,18 +165,26 @@ impl Action for CreateNixVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         self.create_or_append_synthetic_conf
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
         self.create_synthetic_objects
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
-        self.unmount_volume.try_execute().await.ok(); // We actually expect this may fail.
+        self.unmount_volume
+            .try_execute(cancel, journal, progress)
+            .await
+            .ok(); // We actually expect this may fail.
         self.create_volume
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
 
@@ -205,23 +214,26 @@ impl Action for CreateNixVolume {
         }
 
         self.create_fstab_entry
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
         if let Some(encrypt_volume) = &mut self.encrypt_volume {
-            encrypt_volume.try_execute().await.map_err(Self::error)?
+            encrypt_volume
+                .try_execute(cancel, journal, progress)
+                .await
+                .map_err(Self::error)?
         }
         self.setup_volume_daemon
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
 
         self.bootstrap_volume
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
         self.kickstart_launchctl_service
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
 
@@ -250,13 +262,47 @@ impl Action for CreateNixVolume {
         }
 
         self.enable_ownership
-            .try_execute()
+            .try_execute(cancel, journal, progress)
             .await
             .map_err(Self::error)?;
 
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let mut drift = self
+            .create_or_append_synthetic_conf
+            .try_verify()
+            .await
+            .map_err(Self::error)?;
+        drift.extend(
+            self.setup_volume_daemon
+                .try_verify()
+                .await
+                .map_err(Self::error)?,
+        );
+        Ok(drift)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        self.create_or_append_synthetic_conf
+            .try_repair(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        self.setup_volume_daemon
+            .try_repair(cancel, journal, progress)
+            .await
+            .map_err(Self::error)?;
+        Ok(())
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let mut explanation = vec![
             self.create_or_append_synthetic_conf.tracing_synopsis(),
@@ -285,42 +331,83 @@ impl Action for CreateNixVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
         let mut errors = vec![];
 
-        if let Err(err) = self.enable_ownership.try_revert().await {
+        if let Err(err) = self
+            .enable_ownership
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         };
-        if let Err(err) = self.kickstart_launchctl_service.try_revert().await {
+        if let Err(err) = self
+            .kickstart_launchctl_service
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         };
-        if let Err(err) = self.bootstrap_volume.try_revert().await {
+        if let Err(err) = self
+            .bootstrap_volume
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         };
-        if let Err(err) = self.setup_volume_daemon.try_revert().await {
+        if let Err(err) = self
+            .setup_volume_daemon
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         };
         if let Some(encrypt_volume) = &mut self.encrypt_volume {
-            if let Err(err) = encrypt_volume.try_revert().await {
+            if let Err(err) = encrypt_volume.try_revert(cancel, journal, progress).await {
                 errors.push(err)
             }
         }
-        if let Err(err) = self.create_fstab_entry.try_revert().await {
+        if let Err(err) = self
+            .create_fstab_entry
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
 
-        if let Err(err) = self.unmount_volume.try_revert().await {
+        if let Err(err) = self
+            .unmount_volume
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
-        if let Err(err) = self.create_volume.try_revert().await {
+        if let Err(err) = self
+            .create_volume
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
 
         // Purposefully not reversed
-        if let Err(err) = self.create_or_append_synthetic_conf.try_revert().await {
+        if let Err(err) = self
+            .create_or_append_synthetic_conf
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
-        if let Err(err) = self.create_synthetic_objects.try_revert().await {
+        if let Err(err) = self
+            .create_synthetic_objects
+            .try_revert(cancel, journal, progress)
+            .await
+        {
             errors.push(err)
         }
 
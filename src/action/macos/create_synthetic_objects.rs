@@ -3,7 +3,10 @@ use tracing::{span, Span};
 
 use crate::execute_command;
 
-use crate::action::{Action, ActionDescription, ActionError, ActionTag, StatefulAction};
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionTag, CancellationToken, Journal, Progress,
+    StatefulAction,
+};
 
 /// Create the synthetic objects defined in `/etc/synthetic.conf`
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
@@ -38,13 +41,19 @@ impl Action for CreateSyntheticObjects {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // Yup we literally call both and ignore the error! Reasoning: https://github.com/NixOS/nix/blob/95331cb9c99151cbd790ceb6ddaf49fc1c0da4b3/scripts/create-darwin-volume.sh#L261
         execute_command(
             Command::new("/System/Library/Filesystems/apfs.fs/Contents/Resources/apfs.util")
                 .process_group(0)
                 .arg("-t")
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .ok(); // Deliberate
@@ -53,6 +62,7 @@ impl Action for CreateSyntheticObjects {
                 .process_group(0)
                 .arg("-B")
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .ok(); // Deliberate
@@ -68,13 +78,19 @@ impl Action for CreateSyntheticObjects {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // Yup we literally call both and ignore the error! Reasoning: https://github.com/NixOS/nix/blob/95331cb9c99151cbd790ceb6ddaf49fc1c0da4b3/scripts/create-darwin-volume.sh#L261
         execute_command(
             Command::new("/System/Library/Filesystems/apfs.fs/Contents/Resources/apfs.util")
                 .process_group(0)
                 .arg("-t")
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .ok(); // Deliberate
@@ -83,6 +99,7 @@ impl Action for CreateSyntheticObjects {
                 .process_group(0)
                 .arg("-B")
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .ok(); // Deliberate
@@ -9,7 +9,8 @@ use tokio::{
 };
 
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDescription, ActionDrift, ActionError, ActionErrorKind, ActionTag,
+    CancellationToken, Journal, Progress, StatefulAction,
 };
 
 use super::get_uuid_for_label;
@@ -173,7 +174,12 @@ impl Action for CreateVolumeService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self {
             path,
             mount_service_label,
@@ -242,6 +248,17 @@ impl Action for CreateVolumeService {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        if !self.path.exists() {
+            return Ok(vec![ActionDrift::new(
+                self.tracing_synopsis(),
+                format!("`{}` is missing", self.path.display()),
+            )]);
+        }
+        Ok(vec![])
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             format!("Delete file `{}`", self.path.display()),
@@ -250,7 +267,12 @@ impl Action for CreateVolumeService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         remove_file(&self.path)
             .await
             .map_err(|e| Self::error(ActionErrorKind::Remove(self.path.to_owned(), e)))?;
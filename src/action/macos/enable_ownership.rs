@@ -4,7 +4,9 @@ use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag, StatefulAction};
+use crate::action::{
+    ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress, StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -51,7 +53,12 @@ impl Action for EnableOwnership {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { path } = self;
 
         let should_enable_ownership = {
@@ -61,6 +68,7 @@ impl Action for EnableOwnership {
                     .args(["info", "-plist"])
                     .arg(&path)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?
@@ -78,6 +86,7 @@ impl Action for EnableOwnership {
                     .arg("enableOwnership")
                     .arg(path)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?;
@@ -91,7 +100,12 @@ impl Action for EnableOwnership {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // noop
         Ok(())
     }
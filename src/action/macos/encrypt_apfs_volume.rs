@@ -1,7 +1,7 @@
 use crate::{
     action::{
         macos::NIX_VOLUME_MOUNTD_DEST, Action, ActionDescription, ActionError, ActionErrorKind,
-        ActionState, ActionTag, StatefulAction,
+        ActionState, ActionTag, CancellationToken, Journal, Progress, StatefulAction,
     },
     execute_command,
     os::darwin::DiskUtilApfsListOutput,
@@ -74,10 +74,12 @@ impl EncryptApfsVolume {
         }
 
         // Ensure if the disk already exists, that it's encrypted
-        let output =
-            execute_command(Command::new("/usr/sbin/diskutil").args(["apfs", "list", "-plist"]))
-                .await
-                .map_err(Self::error)?;
+        let output = execute_command(
+            Command::new("/usr/sbin/diskutil").args(["apfs", "list", "-plist"]),
+            None,
+        )
+        .await
+        .map_err(Self::error)?;
 
         let parsed: DiskUtilApfsListOutput =
             plist::from_bytes(&output.stdout).map_err(Self::error)?;
@@ -131,7 +133,12 @@ impl Action for EncryptApfsVolume {
     #[tracing::instrument(level = "debug", skip_all, fields(
         disk = %self.disk.display(),
     ))]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { disk, name } = self;
 
         // Generate a random password.
@@ -152,9 +159,12 @@ impl Action for EncryptApfsVolume {
 
         let disk_str = disk.to_str().expect("Could not turn disk into string"); /* Should not reasonably ever fail */
 
-        execute_command(Command::new("/usr/sbin/diskutil").arg("mount").arg(&name))
-            .await
-            .map_err(Self::error)?;
+        execute_command(
+            Command::new("/usr/sbin/diskutil").arg("mount").arg(&name),
+            cancel,
+        )
+        .await
+        .map_err(Self::error)?;
 
         // Add the password to the user keychain so they can unlock it later.
         execute_command(
@@ -183,20 +193,24 @@ impl Action for EncryptApfsVolume {
                 "/usr/bin/security",
                 "/Library/Keychains/System.keychain",
             ]),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
 
         // Encrypt the mounted volume
-        execute_command(Command::new("/usr/sbin/diskutil").process_group(0).args([
-            "apfs",
-            "encryptVolume",
-            name.as_str(),
-            "-user",
-            "disk",
-            "-passphrase",
-            password.as_str(),
-        ]))
+        execute_command(
+            Command::new("/usr/sbin/diskutil").process_group(0).args([
+                "apfs",
+                "encryptVolume",
+                name.as_str(),
+                "-user",
+                "disk",
+                "-passphrase",
+                password.as_str(),
+            ]),
+            cancel,
+        )
         .await
         .map_err(Self::error)?;
 
@@ -206,6 +220,7 @@ impl Action for EncryptApfsVolume {
                 .arg("unmount")
                 .arg("force")
                 .arg(&name),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
@@ -226,7 +241,12 @@ impl Action for EncryptApfsVolume {
     #[tracing::instrument(level = "debug", skip_all, fields(
         disk = %self.disk.display(),
     ))]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let disk_str = self.disk.to_str().expect("Could not turn disk into string"); /* Should not reasonably ever fail */
 
         // TODO: This seems very rough and unsafe
@@ -247,6 +267,7 @@ impl Action for EncryptApfsVolume {
                 )
                 .as_str(),
             ]),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
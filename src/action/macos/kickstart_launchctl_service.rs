@@ -3,7 +3,9 @@ use std::process::Output;
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionTag, StatefulAction};
+use crate::action::{
+    ActionError, ActionErrorKind, ActionTag, CancellationToken, Journal, Progress, StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -95,7 +97,12 @@ impl Action for KickstartLaunchctlService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { domain, service } = self;
 
         execute_command(
@@ -104,6 +111,7 @@ impl Action for KickstartLaunchctlService {
                 .args(["kickstart", "-k"])
                 .arg(format!("{domain}/{service}"))
                 .stdin(std::process::Stdio::null()),
+            cancel,
         )
         .await
         .map_err(Self::error)?;
@@ -119,7 +127,12 @@ impl Action for KickstartLaunchctlService {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        _cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         // MacOs doesn't offer an "ensure-stopped" like they do with Kickstart
         let mut command = Command::new("launchctl");
         command.process_group(0);
@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionTag, StatefulAction};
+use crate::action::{ActionError, ActionTag, CancellationToken, Journal, Progress, StatefulAction};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -54,7 +54,12 @@ impl Action for UnmountApfsVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { disk: _, name } = self;
 
         let currently_mounted = {
@@ -64,6 +69,7 @@ impl Action for UnmountApfsVolume {
                     .args(["info", "-plist"])
                     .arg(&name)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?
@@ -81,6 +87,7 @@ impl Action for UnmountApfsVolume {
                     .args(["unmount", "force"])
                     .arg(name)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?;
@@ -96,7 +103,12 @@ impl Action for UnmountApfsVolume {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        _journal: &Journal,
+        _progress: &Progress,
+    ) -> Result<(), ActionError> {
         let Self { disk: _, name } = self;
 
         let currently_mounted = {
@@ -106,6 +118,7 @@ impl Action for UnmountApfsVolume {
                     .args(["info", "-plist"])
                     .arg(&name)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?
@@ -123,6 +136,7 @@ impl Action for UnmountApfsVolume {
                     .args(["unmount", "force"])
                     .arg(name)
                     .stdin(std::process::Stdio::null()),
+                cancel,
             )
             .await
             .map_err(Self::error)?;
@@ -25,9 +25,11 @@ You can manually plan, execute, then revert an [`Action`] like so:
 ```rust,no_run
 # async fn wrapper() {
 use nix_installer::action::base::CreateDirectory;
-let mut action = CreateDirectory::plan("/nix", None, None, 0o0755, true).await.unwrap();
-action.try_execute().await.unwrap();
-action.try_revert().await.unwrap();
+use nix_installer::action::{CancellationToken, Journal, Progress};
+let mut action = CreateDirectory::plan("/nix", None, None, 0o0755, true, None).await.unwrap();
+let cancel = CancellationToken::new();
+action.try_execute(&cancel, &Journal::default(), &Progress::default()).await.unwrap();
+action.try_revert(&cancel, &Journal::default(), &Progress::default()).await.unwrap();
 # }
 ```
 
@@ -41,6 +43,24 @@ step detection is determined by the wrapping [`StatefulAction`]. If an [`Action`
 its sub-[`Action`]s can be reverted piece-by-piece. So breaking up actions into faillable units is
 ideal.
 
+The actions of an [`InstallPlan`](crate::InstallPlan) run concurrently where they can, an action starts as soon as every
+action before it which it shares a [dependency](Action::dependencies) with has completed. Actions which do not declare
+their dependencies run on their own, after every action before them.
+
+Actions are passed a [`CancellationToken`], which is cancelled when the user asks for the install (or uninstall) to
+stop. Long running actions should stop (with [`ActionErrorKind::Cancelled`]) once it is cancelled, composite actions
+pass it on to their sub-[`Action`]s, which are not started once it is cancelled. Commands run with
+`execute_command` are killed.
+
+Actions are also passed a [`Journal`], which persists the receipt as their sub-[`Action`]s transition so an interrupted
+install (or uninstall) can be resumed. Composite actions pass it on to the [`try_execute`](StatefulAction::try_execute)
+(or [`try_revert`](StatefulAction::try_revert)) of their sub-[`Action`]s, along with the [`CancellationToken`] and a
+[`Progress`] handle which reports their progress to the observer of the plan.
+
+Actions which fail for a transient reason (such as a failed download, or a command which could not lock a file) are
+executed again according to a [`RetryPolicy`], the number of attempts is recorded in the receipt. Since an action may
+be executed more than once, it should be able to pick up from wherever a previous attempt stopped.
+
 A custom [`Action`] can be created then used in a custom [`Planner`](crate::planner::Planner):
 
 ```rust,no_run
@@ -50,7 +70,7 @@ use nix_installer::{
     InstallPlan,
     settings::{CommonSettings, InstallSettingsError},
     planner::{Planner, PlannerError},
-    action::{Action, ActionError, StatefulAction, ActionDescription},
+    action::{Action, ActionError, StatefulAction, ActionDescription, CancellationToken, Journal, Progress},
 };
 
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
@@ -88,7 +108,7 @@ impl Action for MyAction {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn execute(&mut self) -> Result<(), ActionError> {
+    async fn execute(&mut self, cancel: &CancellationToken, journal: &Journal, progress: &Progress) -> Result<(), ActionError> {
         // Execute steps ...
         Ok(())
     }
@@ -98,7 +118,7 @@ impl Action for MyAction {
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn revert(&mut self) -> Result<(), ActionError> {
+    async fn revert(&mut self, cancel: &CancellationToken, journal: &Journal, progress: &Progress) -> Result<(), ActionError> {
         // Revert steps...
         Ok(())
     }
@@ -191,11 +211,21 @@ pub mod base;
 pub mod common;
 pub mod linux;
 pub mod macos;
+mod retry;
 mod stateful;
 
+pub use retry::{RetryPolicy, RetryableError};
 pub use stateful::{ActionState, StatefulAction};
-use std::{error::Error, process::Output};
+
+pub use crate::journal::Journal;
+pub use crate::progress::Progress;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::Output,
+};
 use tokio::task::JoinError;
+pub use tokio_util::sync::CancellationToken;
 use tracing::Span;
 
 use crate::{error::HasExpectedErrors, CertificateError};
@@ -238,13 +268,61 @@ pub trait Action: Send + Sync + std::fmt::Debug + dyn_clone::DynClone {
     /// If this action calls sub-[`Action`]s, care should be taken to call [`try_execute`][StatefulAction::try_execute], not [`execute`][Action::execute], so that [`ActionState`] is handled correctly and tracing is done.
     ///
     /// This is called by [`InstallPlan::install`](crate::InstallPlan::install) through [`StatefulAction::try_execute`] which handles tracing as well as if the action needs to execute based on its `action_state`.
-    async fn execute(&mut self) -> Result<(), ActionError>;
+    ///
+    /// Once `cancel` is cancelled the action should stop as soon as it safely can, returning [`ActionErrorKind::Cancelled`].
+    /// Both `cancel` and `journal` should be passed on to any sub-[`Action`]s.
+    async fn execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError>;
     /// Perform any revert steps
     ///
     /// If this action calls sub-[`Action`]s, care should be taken to call [`try_revert`][StatefulAction::try_revert], not [`revert`][Action::revert], so that [`ActionState`] is handled correctly and tracing is done.
     ///
     /// /// This is called by [`InstallPlan::uninstall`](crate::InstallPlan::uninstall) through [`StatefulAction::try_revert`] which handles tracing as well as if the action needs to revert based on its `action_state`.
-    async fn revert(&mut self) -> Result<(), ActionError>;
+    ///
+    /// Once `cancel` is cancelled the action should stop as soon as it safely can, returning [`ActionErrorKind::Cancelled`].
+    /// Both `cancel` and `journal` should be passed on to any sub-[`Action`]s.
+    async fn revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError>;
+    /// Check if the effect of this (completed) action still holds, returning each way it has drifted
+    ///
+    /// If this action calls sub-[`Action`]s, care should be taken to call [`try_verify`][StatefulAction::try_verify], not [`verify`][Action::verify], so that only completed actions are checked.
+    ///
+    /// This is called by [`InstallPlan::verify`](crate::InstallPlan::verify) through [`StatefulAction::try_verify`]. By default no drift is ever found, actions which have no lasting effect (such as fetching Nix) need not implement it.
+    async fn verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        Ok(vec![])
+    }
+    /// Re-apply the effect of this (completed) action after it has [drifted](Action::verify)
+    ///
+    /// If this action calls sub-[`Action`]s, care should be taken to call [`try_repair`][StatefulAction::try_repair], not [`repair`][Action::repair], so that only drifted actions are repaired.
+    ///
+    /// This is called by [`InstallPlan::repair`](crate::InstallPlan::repair) through [`StatefulAction::try_repair`]. By default the action is [`execute`](Action::execute)d again, actions which cannot safely be executed over their own (drifted) effect should override it.
+    async fn repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        self.execute(cancel, journal, progress).await
+    }
+
+    /// What this action reads or modifies, so that an [`InstallPlan`](crate::InstallPlan) can run it concurrently with
+    /// other actions it does not share a dependency with
+    ///
+    /// If this action calls sub-[`Action`]s, it should include their [`dependencies`][StatefulAction::dependencies].
+    ///
+    /// By default (`None`) the action may depend on anything, so it never runs concurrently with other actions.
+    /// Actions which affect the system as a whole (such as starting services) should keep the default.
+    fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        None
+    }
 
     fn stateful(self) -> StatefulAction<Self>
     where
@@ -253,6 +331,8 @@ pub trait Action: Send + Sync + std::fmt::Debug + dyn_clone::DynClone {
         StatefulAction {
             action: self,
             state: ActionState::Uncompleted,
+            retry: None,
+            attempts: 0,
         }
     }
 
@@ -286,7 +366,78 @@ impl ActionDescription {
     }
 }
 
+/**
+A way in which the effect of a completed [`Action`](crate::action::Action) no longer holds, see [`Action::verify`]
+*/
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
+pub struct ActionDrift {
+    /// The [`tracing_synopsis`](Action::tracing_synopsis) of the drifted action
+    pub synopsis: String,
+    pub description: String,
+}
+
+impl ActionDrift {
+    pub fn new(synopsis: String, description: String) -> Self {
+        Self {
+            synopsis,
+            description,
+        }
+    }
+}
+
+/**
+Something an [`Action`] reads or modifies, see [`Action::dependencies`]
+
+Two actions depend on each other if they share a [`Path`](ActionDependency::Path) (or one is inside the other), a
+[`Unit`](ActionDependency::Unit), a [`User`](ActionDependency::User) or a [`Group`](ActionDependency::Group), or if
+one depends on the other's [`Action`](ActionDependency::Action) tag.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionDependency {
+    /// Runs after any actions with this tag which come before it in the plan
+    Action(ActionTag),
+    /// Reads or modifies the path, or anything inside of it
+    Path(PathBuf),
+    /// Starts, stops, or configures the init system unit
+    Unit(String),
+    /// Creates, modifies, or deletes the user
+    User(String),
+    /// Creates, modifies, or deletes the group
+    Group(String),
+}
+
+impl ActionDependency {
+    pub fn path(path: impl AsRef<Path>) -> Self {
+        Self::Path(path.as_ref().to_path_buf())
+    }
+
+    /// If actions with these dependencies must not run concurrently
+    pub fn overlaps(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Path(path), Self::Path(other)) => {
+                path.starts_with(other) || other.starts_with(path)
+            },
+            (Self::Unit(unit), Self::Unit(other)) => unit == other,
+            (Self::User(user), Self::User(other)) => user == other,
+            (Self::Group(group), Self::Group(other)) => group == other,
+            _ => false,
+        }
+    }
+
+    /// The dependencies of an [`Action`] with sub-[`Action`]s, given the [`dependencies`][StatefulAction::dependencies]
+    /// of each of them, which is `None` if any of them is
+    pub fn combine(
+        dependencies: impl IntoIterator<Item = Option<Vec<ActionDependency>>>,
+    ) -> Option<Vec<ActionDependency>> {
+        dependencies
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(|dependencies| dependencies.concat())
+    }
+}
+
 /// A 'tag' name an action has that corresponds to the one we serialize in [`typetag]`
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ActionTag(&'static str);
 
 impl std::fmt::Display for ActionTag {
@@ -328,6 +479,11 @@ impl ActionError {
     pub fn action_tag(&self) -> &ActionTag {
         &self.action_tag
     }
+
+    /// If the action (or all of the failed sub-actions it contains) stopped because it was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.kind.is_cancelled()
+    }
 }
 
 impl std::fmt::Display for ActionError {
@@ -526,6 +682,8 @@ pub enum ActionErrorKind {
     MissingUserDeletionCommand,
     #[error("Could not find a supported command to delete groups in PATH; please install `groupdel` or `delgroup`")]
     MissingGroupDeletionCommand,
+    #[error("Could not find a supported command to change the GID of groups in PATH; please install `groupmod`")]
+    MissingGroupModificationCommand,
     #[error("Could not find a supported command to remove users from groups in PATH; please install `gpasswd` or `deluser`")]
     MissingRemoveUserFromGroupCommand,
     #[error("\
@@ -535,6 +693,9 @@ pub enum ActionErrorKind {
     SystemdMissing,
     #[error("`{command}` failed, message: {message}")]
     DiskUtilInfoError { command: String, message: String },
+    /// The action was cancelled before it completed
+    #[error("Cancelled")]
+    Cancelled,
 }
 
 impl ActionErrorKind {
@@ -554,6 +715,18 @@ impl ActionErrorKind {
             output,
         }
     }
+    /// If this is [`Cancelled`](ActionErrorKind::Cancelled), or only contains errors which are
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            Self::Cancelled => true,
+            Self::Child(child) => child.is_cancelled(),
+            Self::MultipleChildren(children) => {
+                !children.is_empty() && children.iter().all(ActionError::is_cancelled)
+            },
+            Self::Multiple(kinds) => !kinds.is_empty() && kinds.iter().all(Self::is_cancelled),
+            _ => false,
+        }
+    }
 }
 
 impl HasExpectedErrors for ActionErrorKind {
@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{base::FetchUrlError, ActionError, ActionErrorKind};
+
+/// How many times, and how eagerly, an [`Action`](crate::action::Action) which failed should be executed again
+///
+/// Some failures are transient, such as a network hiccup while fetching Nix, `systemctl` timing out, or `groupadd`
+/// finding `/etc/group` locked by another process. Only failures of a [`RetryableError`] kind listed in
+/// [`retry_on`](RetryPolicy::retry_on) are retried, after waiting for a backoff which doubles with each attempt. A
+/// retry executes the whole action again, so commands are only worth retrying for actions which can be executed more
+/// than once.
+///
+/// A policy can be set on a single action with [`StatefulAction::retry`](crate::action::StatefulAction::retry),
+/// otherwise the actions of an [`InstallPlan`](crate::InstallPlan) use the policy of its
+/// [`Planner`](crate::planner::Planner) (see [`CommonSettings`](crate::settings::CommonSettings)).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// The most times the action is executed, including the first
+    pub max_attempts: u32,
+    /// How long to wait before the first retry, in milliseconds
+    pub initial_backoff_ms: u64,
+    /// The longest to wait before any retry, in milliseconds
+    pub max_backoff_ms: u64,
+    /// The kinds of failures which are retried
+    pub retry_on: Vec<RetryableError>,
+}
+
+impl RetryPolicy {
+    /// Execute actions once, never retrying
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff_ms: 0,
+            max_backoff_ms: 0,
+            retry_on: vec![],
+        }
+    }
+
+    /// If `err`, the failure of the given (1-based) `attempt`, should be retried
+    pub fn retries(&self, err: &ActionError, attempt: u32) -> bool {
+        attempt < self.max_attempts
+            && RetryableError::of(err.kind())
+                .is_some_and(|kinds| kinds.iter().all(|kind| self.retry_on.contains(kind)))
+    }
+
+    /// How long to wait after the failure of the given (1-based) `attempt`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+/// A kind of [`ActionErrorKind`] which may be transient, and so can be retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum RetryableError {
+    /// A command could not be run ([`ActionErrorKind::Command`])
+    Command,
+    /// A command exited unsuccessfully ([`ActionErrorKind::CommandOutput`])
+    CommandOutput,
+    /// A request while fetching Nix failed ([`FetchUrlError::Reqwest`])
+    Download,
+}
+
+impl RetryableError {
+    /// The kinds of every failure contained in `kind`, if all of them are retryable
+    pub fn of(kind: &ActionErrorKind) -> Option<Vec<Self>> {
+        match kind {
+            ActionErrorKind::Command { .. } => Some(vec![Self::Command]),
+            ActionErrorKind::CommandOutput { .. } => Some(vec![Self::CommandOutput]),
+            ActionErrorKind::Custom(err) => match err.downcast_ref::<FetchUrlError>() {
+                Some(FetchUrlError::Reqwest(_)) => Some(vec![Self::Download]),
+                _ => None,
+            },
+            ActionErrorKind::Child(child) => Self::of(child.kind()),
+            ActionErrorKind::MultipleChildren(children) if !children.is_empty() => children
+                .iter()
+                .map(|child| Self::of(child.kind()))
+                .collect::<Option<Vec<_>>>()
+                .map(|kinds| kinds.concat()),
+            ActionErrorKind::Multiple(kinds) if !kinds.is_empty() => kinds
+                .iter()
+                .map(Self::of)
+                .collect::<Option<Vec<_>>>()
+                .map(|kinds| kinds.concat()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::{
+        base::{CreateGroup, DeleteUser},
+        common::DeleteUsersInGroup,
+        Action, ActionDescription, ActionState, ActionTag, CancellationToken, Journal, Progress,
+    };
+    use tracing::{span, Span};
+
+    /// Fails with a locked `/etc/group` until it has failed `failures` times
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Flaky {
+        failures: u32,
+    }
+
+    #[async_trait::async_trait]
+    #[typetag::serde(name = "flaky")]
+    impl Action for Flaky {
+        fn action_tag() -> ActionTag {
+            ActionTag("flaky")
+        }
+        fn tracing_synopsis(&self) -> String {
+            "Flaky".into()
+        }
+        fn tracing_span(&self) -> Span {
+            span!(tracing::Level::DEBUG, "flaky")
+        }
+        fn execute_description(&self) -> Vec<ActionDescription> {
+            vec![]
+        }
+        fn revert_description(&self) -> Vec<ActionDescription> {
+            vec![]
+        }
+        async fn execute(
+            &mut self,
+            _cancel: &CancellationToken,
+            _journal: &Journal,
+            _progress: &Progress,
+        ) -> Result<(), ActionError> {
+            if self.failures == 0 {
+                return Ok(());
+            }
+            self.failures -= 1;
+            Err(Self::error(command_output()))
+        }
+        async fn revert(
+            &mut self,
+            _cancel: &CancellationToken,
+            _journal: &Journal,
+            _progress: &Progress,
+        ) -> Result<(), ActionError> {
+            Ok(())
+        }
+    }
+
+    fn command_output() -> ActionErrorKind {
+        ActionErrorKind::command_output(
+            &tokio::process::Command::new("groupadd"),
+            std::process::Output {
+                status: std::process::ExitStatus::default(),
+                stdout: vec![],
+                stderr: b"groupadd: cannot lock /etc/group; try again later.".to_vec(),
+            },
+        )
+    }
+
+    #[test]
+    fn retries_only_listed_kinds_up_to_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            retry_on: vec![RetryableError::CommandOutput],
+        };
+        let err = ActionError::new(DeleteUser::action_tag(), command_output());
+        assert!(policy.retries(&err, 1));
+        assert!(policy.retries(&err, 2));
+        assert!(!policy.retries(&err, 3));
+
+        let nested = ActionError::new(
+            DeleteUsersInGroup::action_tag(),
+            ActionErrorKind::MultipleChildren(vec![
+                err,
+                ActionError::new(
+                    DeleteUser::action_tag(),
+                    ActionErrorKind::NoUser("nixbld1".into()),
+                ),
+            ]),
+        );
+        assert!(!policy.retries(&nested, 1));
+        assert!(!RetryPolicy::never().retries(
+            &ActionError::new(CreateGroup::action_tag(), command_output()),
+            1
+        ));
+    }
+
+    #[tokio::test]
+    async fn retries_action_and_records_attempts() -> eyre::Result<()> {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 0,
+            max_backoff_ms: 0,
+            retry_on: vec![RetryableError::CommandOutput],
+        };
+        let cancel = CancellationToken::new();
+
+        let mut action = Flaky { failures: 2 }.stateful().retry(policy.clone());
+        action
+            .try_execute(&cancel, &Journal::default(), &Progress::default())
+            .await?;
+        assert_eq!(action.state, ActionState::Completed);
+        assert_eq!(serde_json::to_value(&action)?["attempts"], 3);
+
+        let mut action = Flaky { failures: 3 }.stateful().retry(policy);
+        assert!(action
+            .try_execute(&cancel, &Journal::default(), &Progress::default())
+            .await
+            .is_err());
+        assert_eq!(action.state, ActionState::Progress);
+        assert_eq!(action.attempts, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            retry_on: vec![],
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1_000));
+        assert_eq!(policy.backoff(100), Duration::from_millis(1_000));
+    }
+}
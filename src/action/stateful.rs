@@ -1,7 +1,13 @@
+use std::{future::Future, pin::Pin};
+
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, Span};
 
-use super::{Action, ActionDescription, ActionError, ActionTag};
+use super::{
+    Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionErrorKind,
+    ActionTag, CancellationToken, Journal, RetryPolicy,
+};
+use crate::progress::{ActionProgress, Progress, ProgressEvent};
 
 /// A wrapper around an [`Action`](crate::action::Action) which tracks the [`ActionState`] and
 /// handles some tracing output
@@ -9,6 +15,16 @@ use super::{Action, ActionDescription, ActionError, ActionTag};
 pub struct StatefulAction<A> {
     pub(crate) action: A,
     pub(crate) state: ActionState,
+    /// The policy for retrying this action, instead of the one of the plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) retry: Option<RetryPolicy>,
+    /// How many times the action was attempted during its last execution
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub(crate) attempts: u32,
+}
+
+fn is_zero(attempts: &u32) -> bool {
+    *attempts == 0
 }
 
 impl<A> From<A> for StatefulAction<A>
@@ -19,6 +35,8 @@ where
         Self {
             action,
             state: ActionState::Uncompleted,
+            retry: None,
+            attempts: 0,
         }
     }
 }
@@ -34,6 +52,10 @@ impl StatefulAction<Box<dyn Action>> {
     pub fn tracing_span(&self) -> Span {
         self.action.tracing_span()
     }
+    /// What the action reads or modifies, see [`Action::dependencies`]
+    pub fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        self.action.dependencies()
+    }
     /// A description of what this action would do during execution
     pub fn describe_execute(&self) -> Vec<ActionDescription> {
         match self.state {
@@ -52,29 +74,150 @@ impl StatefulAction<Box<dyn Action>> {
             _ => self.action.revert_description(),
         }
     }
+    /// Describe the action for a [`ProgressEvent`]
+    fn action_progress(&self, progress: &Progress) -> ActionProgress {
+        progress.action(
+            ActionTag::from(self.action.typetag_name()),
+            self.action.tracing_synopsis(),
+        )
+    }
+    fn cancelled(&self) -> ActionError {
+        ActionError::new(
+            ActionTag::from(self.action.typetag_name()),
+            ActionErrorKind::Cancelled,
+        )
+    }
+    async fn execute_observed(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        progress.emit(ProgressEvent::Started(self.action_progress(progress)));
+        let tag = ActionTag::from(self.action.typetag_name());
+        let res = self
+            .action
+            .execute(cancel, journal, &progress.nested(tag))
+            .await;
+        if let Err(err) = &res {
+            progress.emit(ProgressEvent::Failed(
+                self.action_progress(progress),
+                err.kind().to_string(),
+            ));
+        }
+        res
+    }
+    /// Execute the action, retrying according to its own policy or else `fallback`
+    async fn execute_retrying(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+        fallback: &RetryPolicy,
+    ) -> Result<(), ActionError> {
+        let policy = self.retry.clone().unwrap_or_else(|| fallback.clone());
+        let synopsis = self.action.tracing_synopsis();
+        self.retrying(
+            cancel,
+            journal,
+            progress,
+            &policy,
+            &synopsis,
+            |this, cancel, journal, progress| {
+                Box::pin(this.execute_observed(cancel, journal, progress))
+            },
+        )
+        .await
+    }
+    async fn revert_observed(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        progress.emit(ProgressEvent::RevertStarted(self.action_progress(progress)));
+        let tag = ActionTag::from(self.action.typetag_name());
+        let res = self
+            .action
+            .revert(cancel, journal, &progress.nested(tag))
+            .await;
+        if let Err(err) = &res {
+            progress.emit(ProgressEvent::RevertFailed(
+                self.action_progress(progress),
+                err.kind().to_string(),
+            ));
+        }
+        res
+    }
     /// Perform any execution steps
     ///
     /// You should prefer this ([`try_execute`][StatefulAction::try_execute]) over [`execute`][Action::execute] as it handles [`ActionState`] and does tracing
+    pub async fn try_execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        self.try_execute_retrying(cancel, journal, progress, &RetryPolicy::never())
+            .await
+    }
+    /// Perform any execution steps, retrying according to the policy of the action or else `fallback`
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn try_execute(&mut self) -> Result<(), ActionError> {
+    pub(crate) async fn try_execute_retrying(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+        fallback: &RetryPolicy,
+    ) -> Result<(), ActionError> {
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Progress | ActionState::Uncompleted)
+        {
+            tracing::debug!(
+                "Not executing: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
         match self.state {
             ActionState::Completed => {
                 tracing::trace!(
                     "Completed: (Already done) {}",
                     self.action.tracing_synopsis()
                 );
+                progress.emit(ProgressEvent::Skipped(self.action_progress(progress)));
                 Ok(())
             },
             ActionState::Skipped => {
                 tracing::trace!("Skipped: {}", self.action.tracing_synopsis());
+                progress.emit(ProgressEvent::Skipped(self.action_progress(progress)));
                 Ok(())
             },
-            _ => {
+            ActionState::Progress => {
+                // The action was interrupted part way through, it must be run again
+                tracing::warn!(
+                    "Resuming: (Previously interrupted) {}",
+                    self.action.tracing_synopsis()
+                );
+                self.execute_retrying(cancel, &journal, progress, fallback)
+                    .await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!("Completed: {}", self.action.tracing_synopsis());
+                progress.emit(ProgressEvent::Completed(self.action_progress(progress)));
+                Ok(())
+            },
+            ActionState::Uncompleted => {
                 self.state = ActionState::Progress;
+                journal.record(self).await;
                 tracing::debug!("Executing: {}", self.action.tracing_synopsis());
-                self.action.execute().await?;
+                self.execute_retrying(cancel, &journal, progress, fallback)
+                    .await?;
                 self.state = ActionState::Completed;
+                journal.record(self).await;
                 tracing::debug!("Completed: {}", self.action.tracing_synopsis());
+                progress.emit(ProgressEvent::Completed(self.action_progress(progress)));
                 Ok(())
             },
         }
@@ -83,7 +226,22 @@ impl StatefulAction<Box<dyn Action>> {
     ///
     /// You should prefer this ([`try_revert`][StatefulAction::try_revert]) over [`revert`][Action::revert] as it handles [`ActionState`] and does tracing
     #[tracing::instrument(level = "debug", skip_all)]
-    pub async fn try_revert(&mut self) -> Result<(), ActionError> {
+    pub async fn try_revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Completed | ActionState::Progress)
+        {
+            tracing::debug!(
+                "Not reverting: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
         match self.state {
             ActionState::Uncompleted => {
                 tracing::trace!(
@@ -98,14 +256,153 @@ impl StatefulAction<Box<dyn Action>> {
             },
             _ => {
                 self.state = ActionState::Progress;
+                journal.record(self).await;
                 tracing::debug!("Reverting: {}", self.action.tracing_synopsis());
-                self.action.revert().await?;
+                self.revert_observed(cancel, &journal, progress).await?;
                 tracing::debug!("Reverted: {}", self.action.tracing_synopsis());
                 self.state = ActionState::Uncompleted;
+                journal.record(self).await;
+                progress.emit(ProgressEvent::RevertFinished(
+                    self.action_progress(progress),
+                ));
                 Ok(())
             },
         }
     }
+    /// Check if the effect of the action still holds, if it is [`Completed`](ActionState::Completed)
+    ///
+    /// You should prefer this ([`try_verify`][StatefulAction::try_verify]) over [`verify`][Action::verify] as it handles [`ActionState`] and does tracing
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn try_verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        match self.state {
+            ActionState::Completed => {
+                tracing::debug!("Verifying: {}", self.action.tracing_synopsis());
+                self.action.verify().await
+            },
+            _ => {
+                tracing::trace!(
+                    "Verifying: (Not completed) {}",
+                    self.action.tracing_synopsis()
+                );
+                Ok(vec![])
+            },
+        }
+    }
+    /// Re-apply the action if it is [`Completed`](ActionState::Completed) but has drifted, or was interrupted
+    /// part way through a previous repair, returning how it had drifted
+    ///
+    /// You should prefer this ([`try_repair`][StatefulAction::try_repair]) over [`repair`][Action::repair] as it handles [`ActionState`] and does tracing
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn try_repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<Vec<ActionDrift>, ActionError> {
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Completed | ActionState::Progress)
+        {
+            tracing::debug!(
+                "Not repairing: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
+        match self.state {
+            ActionState::Completed => {
+                let drift = self.action.verify().await?;
+                if drift.is_empty() {
+                    tracing::trace!("Repaired: (Not drifted) {}", self.action.tracing_synopsis());
+                    return Ok(drift);
+                }
+                self.state = ActionState::Progress;
+                journal.record(self).await;
+                tracing::debug!("Repairing: {}", self.action.tracing_synopsis());
+                self.action.repair(cancel, &journal, progress).await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!("Repaired: {}", self.action.tracing_synopsis());
+                Ok(drift)
+            },
+            ActionState::Progress => {
+                // The action was interrupted part way through, it must be run again
+                tracing::warn!(
+                    "Resuming: (Previously interrupted) {}",
+                    self.action.tracing_synopsis()
+                );
+                let drift = self.action.verify().await?;
+                self.action.repair(cancel, &journal, progress).await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!("Repaired: {}", self.action.tracing_synopsis());
+                Ok(drift)
+            },
+            ActionState::Uncompleted | ActionState::Skipped => {
+                tracing::trace!(
+                    "Repairing: (Not completed) {}",
+                    self.action.tracing_synopsis()
+                );
+                Ok(vec![])
+            },
+        }
+    }
+}
+
+/// An attempt at executing a [`StatefulAction`], see [`StatefulAction::retrying`]
+type Attempt<'a> = Pin<Box<dyn Future<Output = Result<(), ActionError>> + Send + 'a>>;
+
+impl<A> StatefulAction<A>
+where
+    A: Send,
+    Self: Serialize,
+{
+    /// Attempt executing the action (described by `synopsis`) with `attempt` until it succeeds, or fails in a way
+    /// `policy` does not retry, recording the attempts made
+    async fn retrying(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+        policy: &RetryPolicy,
+        synopsis: &str,
+        attempt: for<'a> fn(
+            &'a mut Self,
+            &'a CancellationToken,
+            &'a Journal,
+            &'a Progress,
+        ) -> Attempt<'a>,
+    ) -> Result<(), ActionError> {
+        self.attempts = 0;
+        loop {
+            self.attempts += 1;
+            let Err(err) = attempt(self, cancel, journal, progress).await else {
+                return Ok(());
+            };
+            if cancel.is_cancelled() || !policy.retries(&err, self.attempts) {
+                if self.attempts > 1 {
+                    tracing::error!(
+                        "Failed: (Gave up after {} attempts) {synopsis}",
+                        self.attempts,
+                    );
+                }
+                return Err(err);
+            }
+            let backoff = policy.backoff(self.attempts);
+            tracing::warn!(
+                "Retrying: (Attempt {} of {} failed, retrying in {}ms) {synopsis}: {}",
+                self.attempts,
+                policy.max_attempts,
+                backoff.as_millis(),
+                err.kind()
+            );
+            journal.record(self).await;
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => (),
+                _ = cancel.cancelled() => return Err(err),
+            }
+        }
+    }
 }
 
 impl<A> StatefulAction<A>
@@ -126,11 +423,21 @@ where
     pub fn tracing_span(&self) -> Span {
         self.action.tracing_span()
     }
+    /// What the action reads or modifies, see [`Action::dependencies`]
+    pub fn dependencies(&self) -> Option<Vec<ActionDependency>> {
+        self.action.dependencies()
+    }
 
     pub fn inner(&self) -> &A {
         &self.action
     }
 
+    /// Retry the action according to `policy`, instead of the policy of the plan
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
     pub fn boxed(self) -> StatefulAction<Box<dyn Action>>
     where
         Self: 'static,
@@ -138,6 +445,8 @@ where
         StatefulAction {
             action: Box::new(self.action),
             state: self.state,
+            retry: self.retry,
+            attempts: self.attempts,
         }
     }
     /// A description of what this action would do during execution
@@ -154,11 +463,99 @@ where
         }
         return self.action.revert_description();
     }
+    /// Describe the action for a [`ProgressEvent`]
+    fn action_progress(&self, progress: &Progress) -> ActionProgress {
+        progress.action(A::action_tag(), self.action.tracing_synopsis())
+    }
+    fn cancelled(&self) -> ActionError {
+        ActionError::new(A::action_tag(), ActionErrorKind::Cancelled)
+    }
+    async fn execute_observed(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        progress.emit(ProgressEvent::Started(self.action_progress(progress)));
+        let res = self
+            .action
+            .execute(cancel, journal, &progress.nested(A::action_tag()))
+            .await;
+        if let Err(err) = &res {
+            progress.emit(ProgressEvent::Failed(
+                self.action_progress(progress),
+                err.kind().to_string(),
+            ));
+        }
+        res
+    }
+    /// Execute the action, retrying according to its policy
+    async fn execute_retrying(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError>
+    where
+        Self: Serialize,
+    {
+        let policy = self.retry.clone().unwrap_or_default();
+        let synopsis = self.action.tracing_synopsis();
+        self.retrying(
+            cancel,
+            journal,
+            progress,
+            &policy,
+            &synopsis,
+            |this, cancel, journal, progress| {
+                Box::pin(this.execute_observed(cancel, journal, progress))
+            },
+        )
+        .await
+    }
+    async fn revert_observed(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError> {
+        progress.emit(ProgressEvent::RevertStarted(self.action_progress(progress)));
+        let res = self
+            .action
+            .revert(cancel, journal, &progress.nested(A::action_tag()))
+            .await;
+        if let Err(err) = &res {
+            progress.emit(ProgressEvent::RevertFailed(
+                self.action_progress(progress),
+                err.kind().to_string(),
+            ));
+        }
+        res
+    }
     /// Perform any execution steps
     ///
     /// You should prefer this ([`try_execute`][StatefulAction::try_execute]) over [`execute`][Action::execute] as it handles [`ActionState`] and does tracing
-    pub async fn try_execute(&mut self) -> Result<(), ActionError> {
+    pub async fn try_execute(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError>
+    where
+        Self: Serialize,
+    {
         let span = self.action.tracing_span();
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Progress | ActionState::Uncompleted)
+        {
+            tracing::debug!(
+                parent: &span,
+                "Not executing: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
         match self.state {
             ActionState::Completed => {
                 tracing::trace!(
@@ -166,26 +563,53 @@ where
                     "Completed: (Already done) {}",
                     self.action.tracing_synopsis()
                 );
+                progress.emit(ProgressEvent::Skipped(self.action_progress(progress)));
                 Ok(())
             },
             ActionState::Skipped => {
                 tracing::trace!(parent: &span, "Skipped: {}", self.action.tracing_synopsis());
+                progress.emit(ProgressEvent::Skipped(self.action_progress(progress)));
                 Ok(())
             },
-            _ => {
+            ActionState::Progress => {
+                // The action was interrupted part way through, it must be run again
+                tracing::warn!(
+                    parent: &span,
+                    "Resuming: (Previously interrupted) {}",
+                    self.action.tracing_synopsis()
+                );
+                self.execute_retrying(cancel, &journal, progress)
+                    .instrument(span.clone())
+                    .await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!(
+                    parent: &span,
+                    "Completed: {}",
+                    self.action.tracing_synopsis()
+                );
+                progress.emit(ProgressEvent::Completed(self.action_progress(progress)));
+                Ok(())
+            },
+            ActionState::Uncompleted => {
                 self.state = ActionState::Progress;
+                journal.record(self).await;
                 tracing::debug!(
                     parent: &span,
                     "Executing: {}",
                     self.action.tracing_synopsis()
                 );
-                self.action.execute().instrument(span.clone()).await?;
+                self.execute_retrying(cancel, &journal, progress)
+                    .instrument(span.clone())
+                    .await?;
                 self.state = ActionState::Completed;
+                journal.record(self).await;
                 tracing::debug!(
                     parent: &span,
                     "Completed: {}",
                     self.action.tracing_synopsis()
                 );
+                progress.emit(ProgressEvent::Completed(self.action_progress(progress)));
                 Ok(())
             },
         }
@@ -193,8 +617,27 @@ where
     /// Perform any revert steps
     ///
     /// You should prefer this ([`try_revert`][StatefulAction::try_revert]) over [`revert`][Action::revert] as it handles [`ActionState`] and does tracing
-    pub async fn try_revert(&mut self) -> Result<(), ActionError> {
+    pub async fn try_revert(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), ActionError>
+    where
+        Self: Serialize,
+    {
         let span = self.action.tracing_span();
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Completed | ActionState::Progress)
+        {
+            tracing::debug!(
+                parent: &span,
+                "Not reverting: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
         match self.state {
             ActionState::Uncompleted => {
                 tracing::trace!(
@@ -210,27 +653,141 @@ where
             },
             _ => {
                 self.state = ActionState::Progress;
+                journal.record(self).await;
                 tracing::debug!(
                     parent: &span,
                     "Reverting: {}",
                     self.action.tracing_synopsis()
                 );
-                self.action.revert().instrument(span.clone()).await?;
+                self.revert_observed(cancel, &journal, progress)
+                    .instrument(span.clone())
+                    .await?;
                 tracing::debug!(
                     parent: &span,
                     "Reverted: {}",
                     self.action.tracing_synopsis()
                 );
                 self.state = ActionState::Uncompleted;
+                journal.record(self).await;
+                progress.emit(ProgressEvent::RevertFinished(
+                    self.action_progress(progress),
+                ));
                 Ok(())
             },
         }
     }
 
+    /// Check if the effect of the action still holds, if it is [`Completed`](ActionState::Completed)
+    ///
+    /// You should prefer this ([`try_verify`][StatefulAction::try_verify]) over [`verify`][Action::verify] as it handles [`ActionState`] and does tracing
+    pub async fn try_verify(&self) -> Result<Vec<ActionDrift>, ActionError> {
+        let span = self.action.tracing_span();
+        match self.state {
+            ActionState::Completed => {
+                tracing::debug!(
+                    parent: &span,
+                    "Verifying: {}",
+                    self.action.tracing_synopsis()
+                );
+                self.action.verify().instrument(span.clone()).await
+            },
+            _ => {
+                tracing::trace!(
+                    parent: &span,
+                    "Verifying: (Not completed) {}",
+                    self.action.tracing_synopsis()
+                );
+                Ok(vec![])
+            },
+        }
+    }
+
+    /// Re-apply the action if it is [`Completed`](ActionState::Completed) but has drifted, or was interrupted
+    /// part way through a previous repair, returning how it had drifted
+    ///
+    /// You should prefer this ([`try_repair`][StatefulAction::try_repair]) over [`repair`][Action::repair] as it handles [`ActionState`] and does tracing
+    pub async fn try_repair(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<Vec<ActionDrift>, ActionError>
+    where
+        Self: Serialize,
+    {
+        let span = self.action.tracing_span();
+        if cancel.is_cancelled()
+            && matches!(self.state, ActionState::Completed | ActionState::Progress)
+        {
+            tracing::debug!(
+                parent: &span,
+                "Not repairing: (Cancelled) {}",
+                self.action.tracing_synopsis()
+            );
+            return Err(self.cancelled());
+        }
+        let journal = journal.enter(self).await;
+        match self.state {
+            ActionState::Completed => {
+                let drift = self.action.verify().instrument(span.clone()).await?;
+                if drift.is_empty() {
+                    tracing::trace!(
+                        parent: &span,
+                        "Repaired: (Not drifted) {}",
+                        self.action.tracing_synopsis()
+                    );
+                    return Ok(drift);
+                }
+                self.state = ActionState::Progress;
+                journal.record(self).await;
+                tracing::debug!(
+                    parent: &span,
+                    "Repairing: {}",
+                    self.action.tracing_synopsis()
+                );
+                self.action
+                    .repair(cancel, &journal, progress)
+                    .instrument(span.clone())
+                    .await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!(parent: &span, "Repaired: {}", self.action.tracing_synopsis());
+                Ok(drift)
+            },
+            ActionState::Progress => {
+                // The action was interrupted part way through, it must be run again
+                tracing::warn!(
+                    parent: &span,
+                    "Resuming: (Previously interrupted) {}",
+                    self.action.tracing_synopsis()
+                );
+                let drift = self.action.verify().instrument(span.clone()).await?;
+                self.action
+                    .repair(cancel, &journal, progress)
+                    .instrument(span.clone())
+                    .await?;
+                self.state = ActionState::Completed;
+                journal.record(self).await;
+                tracing::debug!(parent: &span, "Repaired: {}", self.action.tracing_synopsis());
+                Ok(drift)
+            },
+            ActionState::Uncompleted | ActionState::Skipped => {
+                tracing::trace!(
+                    parent: &span,
+                    "Repairing: (Not completed) {}",
+                    self.action.tracing_synopsis()
+                );
+                Ok(vec![])
+            },
+        }
+    }
+
     pub fn completed(action: A) -> Self {
         Self {
             state: ActionState::Completed,
             action,
+            retry: None,
+            attempts: 0,
         }
     }
 
@@ -238,6 +795,8 @@ where
         Self {
             state: ActionState::Skipped,
             action,
+            retry: None,
+            attempts: 0,
         }
     }
 
@@ -245,6 +804,8 @@ where
         Self {
             state: ActionState::Uncompleted,
             action,
+            retry: None,
+            attempts: 0,
         }
     }
 }
@@ -262,7 +823,9 @@ pub enum ActionState {
     If [`Progress`](ActionState::Progress) an [`Action`](crate::action::Action) will be run on
     [`InstallPlan::install`](crate::InstallPlan::install) and [`InstallPlan::uninstall`](crate::InstallPlan::uninstall)
 
-    Only applicable to meta-actions that contain other multiple sub-actions.
+    An action is in this state while it is executing (or being repaired), so a receipt containing it was recorded by
+    an install (or repair) which was interrupted. When resumed the action is executed again, meta-actions skip any sub-actions which already
+    [`Completed`](ActionState::Completed).
     */
     Progress,
     /**
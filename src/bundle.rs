@@ -0,0 +1,306 @@
+/*! Self-contained installers, a `nix-installer` executable with a Nix package embedded into it
+
+A bundle is the `nix-installer` executable, followed by the Nix package, then a JSON [`EmbeddedNixPackage`]
+describing it, the length of that JSON (as a little endian `u64`), and finally a magic trailer. The executable still
+runs as usual, and when it plans an install the embedded package is used instead of fetching one (see
+[`CommonSettings::resolve_nix_package`](crate::settings::CommonSettings::resolve_nix_package)).
+
+Bundles are made with `nix-installer bundle`, or [`bundle`].
+*/
+
+use std::{
+    io::SeekFrom,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use url::Url;
+
+/// The URL of the Nix package embedded into the running `nix-installer`, see [`EmbeddedNixPackage::current`]
+pub const EMBEDDED_NIX_PACKAGE_URL: &str = "embedded:nix-package";
+
+/// The end of every bundle
+const MAGIC: &[u8; 16] = b"nix-installer-v1";
+
+/// The length of the trailer of a bundle, the length of the [`EmbeddedNixPackage`] JSON and the [`MAGIC`]
+const TRAILER_LEN: u64 = 8 + MAGIC.len() as u64;
+
+/// A Nix package embedded into a `nix-installer` executable
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EmbeddedNixPackage {
+    /// The version of Nix the package is, if it is known
+    pub version: Option<String>,
+    /// The SHA-256 hash (in hex) of the package
+    pub sha256: String,
+    /// Where the package starts in the executable, which is also the length of the executable without it
+    pub offset: u64,
+    /// The length of the package
+    pub len: u64,
+}
+
+impl EmbeddedNixPackage {
+    /// The Nix package embedded into the running `nix-installer`, if any
+    pub async fn current() -> Result<Option<Self>, BundleError> {
+        let path = std::env::current_exe().map_err(BundleError::CurrentExe)?;
+        Self::read(&path).await
+    }
+
+    /// The Nix package embedded into the executable at `path`, if any
+    pub async fn read(path: &Path) -> Result<Option<Self>, BundleError> {
+        let read = |e| BundleError::Read(path.to_path_buf(), e);
+        let mut file = tokio::fs::File::open(path).await.map_err(read)?;
+        let len = file.metadata().await.map_err(read)?.len();
+        if len < TRAILER_LEN {
+            return Ok(None);
+        }
+
+        let mut trailer = [0; TRAILER_LEN as usize];
+        file.seek(SeekFrom::Start(len - TRAILER_LEN))
+            .await
+            .map_err(read)?;
+        file.read_exact(&mut trailer).await.map_err(read)?;
+        let (json_len, magic) = trailer.split_at(8);
+        if magic != MAGIC {
+            return Ok(None);
+        }
+        let json_len =
+            u64::from_le_bytes(json_len.try_into().expect("The trailer starts with a u64"));
+
+        let malformed = || BundleError::Malformed(path.to_path_buf());
+        let json_offset = (len - TRAILER_LEN)
+            .checked_sub(json_len)
+            .ok_or_else(malformed)?;
+        let mut json = vec![0; json_len as usize];
+        file.seek(SeekFrom::Start(json_offset))
+            .await
+            .map_err(read)?;
+        file.read_exact(&mut json).await.map_err(read)?;
+        let embedded: Self = serde_json::from_slice(&json).map_err(|_| malformed())?;
+        if embedded.offset.checked_add(embedded.len) != Some(json_offset) {
+            return Err(malformed());
+        }
+        Ok(Some(embedded))
+    }
+
+    /// The URL the embedded package is fetched from, see [`EMBEDDED_NIX_PACKAGE_URL`]
+    pub fn url() -> Url {
+        Url::parse(EMBEDDED_NIX_PACKAGE_URL).expect("The embedded Nix package URL is valid")
+    }
+}
+
+/// Write a bundle of the `nix-installer` executable at `installer` with the Nix `package` embedded into it, to
+/// `output`
+///
+/// If `installer` is already a bundle, the package embedded into it is replaced. If `sha256` is set, the package must
+/// have that hash.
+///
+/// The bundle is written next to `output` then renamed into place, so `output` is left as it was if bundling fails, and
+/// it may be `installer` itself.
+pub async fn bundle(
+    installer: &Path,
+    package: &Path,
+    version: Option<String>,
+    sha256: Option<&str>,
+    output: &Path,
+) -> Result<EmbeddedNixPackage, BundleError> {
+    let offset = match EmbeddedNixPackage::read(installer).await? {
+        Some(embedded) => embedded.offset,
+        None => tokio::fs::metadata(installer)
+            .await
+            .map_err(|e| BundleError::Read(installer.to_path_buf(), e))?
+            .len(),
+    };
+
+    let write = |e| BundleError::Write(output.to_path_buf(), e);
+    let temp_path = crate::journal::temp_path(output);
+    let embedded = match write_bundle(
+        installer, offset, package, version, sha256, output, &temp_path,
+    )
+    .await
+    {
+        Ok(embedded) => embedded,
+        Err(err) => {
+            if let Err(err) = tokio::fs::remove_file(&temp_path).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Could not remove `{}`: {err}", temp_path.display());
+                }
+            }
+            return Err(err);
+        },
+    };
+    tokio::fs::rename(&temp_path, output).await.map_err(write)?;
+    if let Some(parent) = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        tokio::fs::File::open(parent)
+            .await
+            .map_err(write)?
+            .sync_all()
+            .await
+            .map_err(write)?;
+    }
+    Ok(embedded)
+}
+
+/// Write the bundle [`bundle`] makes to `temp_path`, describing errors writing it as errors writing `output`
+async fn write_bundle(
+    installer: &Path,
+    offset: u64,
+    package: &Path,
+    version: Option<String>,
+    sha256: Option<&str>,
+    output: &Path,
+    temp_path: &Path,
+) -> Result<EmbeddedNixPackage, BundleError> {
+    let write = |e| BundleError::Write(output.to_path_buf(), e);
+    let mut out = tokio::fs::File::create(temp_path).await.map_err(write)?;
+    let mut executable = tokio::fs::File::open(installer)
+        .await
+        .map_err(|e| BundleError::Read(installer.to_path_buf(), e))?
+        .take(offset);
+    tokio::io::copy(&mut executable, &mut out)
+        .await
+        .map_err(write)?;
+
+    let mut package_file = tokio::fs::File::open(package)
+        .await
+        .map_err(|e| BundleError::Read(package.to_path_buf(), e))?;
+    let mut hasher = Sha256::new();
+    let mut len = 0;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = package_file
+            .read(&mut buf)
+            .await
+            .map_err(|e| BundleError::Read(package.to_path_buf(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        out.write_all(&buf[..read]).await.map_err(write)?;
+        len += read as u64;
+    }
+    let actual = format!("{:x}", hasher.finalize());
+    if let Some(expected) = sha256 {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(BundleError::HashMismatch {
+                path: package.to_path_buf(),
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+    }
+
+    let embedded = EmbeddedNixPackage {
+        version,
+        sha256: actual,
+        offset,
+        len,
+    };
+    let json = serde_json::to_vec(&embedded)?;
+    out.write_all(&json).await.map_err(write)?;
+    out.write_all(&(json.len() as u64).to_le_bytes())
+        .await
+        .map_err(write)?;
+    out.write_all(MAGIC).await.map_err(write)?;
+    out.flush().await.map_err(write)?;
+    out.set_permissions(std::fs::Permissions::from_mode(0o0755))
+        .await
+        .map_err(write)?;
+    out.sync_all().await.map_err(write)?;
+    Ok(embedded)
+}
+
+/// An error making or reading a bundle
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("Finding the running executable")]
+    CurrentExe(#[source] std::io::Error),
+    #[error("Reading `{0}`")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("Writing `{0}`")]
+    Write(PathBuf, #[source] std::io::Error),
+    #[error("`{0}` ends like a bundle, but its embedded Nix package could not be found")]
+    Malformed(PathBuf),
+    #[error(
+        "The Nix package `{path}` has the SHA-256 hash `{actual}`, but `{expected}` was expected"
+    )]
+    HashMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    #[error("JSON serialization or deserialization error")]
+    SerdeJson(
+        #[source]
+        #[from]
+        serde_json::Error,
+    ),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn bundles_and_rebundles() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let installer = temp_dir.path().join("nix-installer");
+        tokio::fs::write(&installer, b"#!/bin/sh\n").await?;
+        let package = temp_dir.path().join("nix.tar.xz");
+        tokio::fs::write(&package, b"Nix").await?;
+        let sha256 = format!("{:x}", Sha256::digest(b"Nix"));
+
+        assert_eq!(EmbeddedNixPackage::read(&installer).await?, None);
+        let bundled = temp_dir.path().join("bundled");
+        let embedded = bundle(
+            &installer,
+            &package,
+            Some("2.15.0".into()),
+            Some(&sha256.to_uppercase()),
+            &bundled,
+        )
+        .await?;
+        assert_eq!(
+            EmbeddedNixPackage::read(&bundled).await?,
+            Some(embedded.clone())
+        );
+        assert_eq!(embedded.sha256, sha256);
+        let contents = tokio::fs::read(&bundled).await?;
+        assert!(contents.starts_with(b"#!/bin/sh\nNix"));
+        assert_eq!(
+            &contents[embedded.offset as usize..][..embedded.len as usize],
+            b"Nix"
+        );
+
+        // Bundling a bundle replaces the package embedded into it
+        tokio::fs::write(&package, b"Nix 2.16.0").await?;
+        let rebundled = temp_dir.path().join("rebundled");
+        let embedded = bundle(&bundled, &package, None, None, &rebundled).await?;
+        assert_eq!(embedded.offset, 10);
+        assert_eq!(embedded.version, None);
+        assert!(tokio::fs::read(&rebundled)
+            .await?
+            .starts_with(b"#!/bin/sh\nNix 2.16.0{"));
+
+        // A failed bundle leaves the output as it was
+        assert!(matches!(
+            bundle(&installer, &package, None, Some(&sha256), &rebundled).await,
+            Err(BundleError::HashMismatch { .. })
+        ));
+        assert!(tokio::fs::read(&rebundled)
+            .await?
+            .starts_with(b"#!/bin/sh\nNix 2.16.0{"));
+        assert!(!crate::journal::temp_path(&rebundled).exists());
+
+        // The installer can be bundled in place
+        let embedded = bundle(&installer, &package, None, None, &installer).await?;
+        assert_eq!(embedded.offset, 10);
+        assert_eq!(EmbeddedNixPackage::read(&installer).await?, Some(embedded));
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 
 pub(crate) mod arg;
 mod interaction;
+mod progress;
 pub(crate) mod subcommand;
 
 use clap::Parser;
@@ -45,6 +46,10 @@ impl CommandExecute for NixInstallerCli {
             NixInstallerSubcommand::Plan(plan) => plan.execute().await,
             NixInstallerSubcommand::Install(install) => install.execute().await,
             NixInstallerSubcommand::Uninstall(revert) => revert.execute().await,
+            NixInstallerSubcommand::Receipt(receipt) => receipt.execute().await,
+            NixInstallerSubcommand::Verify(verify) => verify.execute().await,
+            NixInstallerSubcommand::Repair(repair) => repair.execute().await,
+            NixInstallerSubcommand::Bundle(bundle) => bundle.execute().await,
         }
     }
 }
@@ -0,0 +1,70 @@
+use owo_colors::OwoColorize;
+use tokio::{sync::mpsc::UnboundedReceiver, task::JoinHandle};
+
+use crate::progress::ProgressEvent;
+
+/// Render the progress of an install or uninstall as it happens, finishing once the receiver does
+pub(crate) fn render(mut progress: UnboundedReceiver<ProgressEvent>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // The last tenth of the download which was shown
+        let mut download_shown = None;
+        while let Some(event) = progress.recv().await {
+            match event {
+                ProgressEvent::Started(action) if action.path.is_empty() => {
+                    eprintln!("{} {}", "Step:".bold(), action.synopsis)
+                },
+                ProgressEvent::Started(action) => eprintln!(
+                    "{}{}",
+                    "  ".repeat(action.path.len()),
+                    action.synopsis.dimmed()
+                ),
+                ProgressEvent::Skipped(action) if action.path.is_empty() => eprintln!(
+                    "{} {}",
+                    "Step: (Already done)".bold().dimmed(),
+                    action.synopsis.dimmed()
+                ),
+                ProgressEvent::Failed(action, error) => eprintln!(
+                    "{}{} {}: {error}",
+                    "  ".repeat(action.path.len()),
+                    "Failed:".red().bold(),
+                    action.synopsis
+                ),
+                ProgressEvent::RevertStarted(action) if action.path.is_empty() => {
+                    eprintln!("{} {}", "Revert:".bold(), action.synopsis)
+                },
+                ProgressEvent::RevertFailed(action, error) => eprintln!(
+                    "{}{} {}: {error}",
+                    "  ".repeat(action.path.len()),
+                    "Failed to revert:".red().bold(),
+                    action.synopsis
+                ),
+                ProgressEvent::Download {
+                    url: _,
+                    bytes,
+                    total,
+                } => {
+                    let shown = total
+                        .filter(|total| *total > 0)
+                        .map(|total| bytes * 10 / total);
+                    if shown.is_some() && shown != download_shown {
+                        download_shown = shown;
+                        eprintln!(
+                            "    {}",
+                            format!(
+                                "Downloaded {:.1} of {:.1} MiB",
+                                mebibytes(bytes),
+                                mebibytes(total.unwrap_or_default())
+                            )
+                            .dimmed()
+                        );
+                    }
+                },
+                _ => (),
+            }
+        }
+    })
+}
+
+fn mebibytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
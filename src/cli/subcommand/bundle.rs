@@ -0,0 +1,65 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use eyre::WrapErr;
+use owo_colors::OwoColorize;
+
+use crate::cli::CommandExecute;
+
+/// Make a self-contained `nix-installer` which installs the given Nix package without fetching anything
+///
+/// The package is embedded into a copy of this `nix-installer`
+#[derive(Debug, Parser)]
+pub struct Bundle {
+    /// The Nix package (such as `nix-2.15.0-x86_64-linux.tar.xz`) to embed
+    #[clap(long, env = "NIX_INSTALLER_BUNDLE_NIX_PACKAGE")]
+    pub nix_package: PathBuf,
+    /// The version of Nix the package is, shown when installing
+    #[clap(long, env = "NIX_INSTALLER_BUNDLE_NIX_VERSION")]
+    pub nix_version: Option<String>,
+    /// The SHA-256 hash (in hex) the package must have
+    #[clap(long, env = "NIX_INSTALLER_BUNDLE_NIX_PACKAGE_SHA256")]
+    pub nix_package_sha256: Option<String>,
+    /// Where to write the bundled `nix-installer`
+    #[clap(
+        long = "out-file",
+        env = "NIX_INSTALLER_BUNDLE_OUT_FILE",
+        default_value = "nix-installer-bundle"
+    )]
+    pub output: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for Bundle {
+    #[tracing::instrument(level = "debug", skip_all, fields())]
+    async fn execute(self) -> eyre::Result<ExitCode> {
+        let Self {
+            nix_package,
+            nix_version,
+            nix_package_sha256,
+            output,
+        } = self;
+
+        let installer = std::env::current_exe().wrap_err("Finding the running executable")?;
+        let embedded = crate::bundle::bundle(
+            &installer,
+            &nix_package,
+            nix_version,
+            nix_package_sha256.as_deref(),
+            &output,
+        )
+        .await?;
+
+        eprintln!(
+            "Bundled {} (SHA-256 `{}`) into `{}`",
+            match &embedded.version {
+                Some(version) => format!("Nix {version}"),
+                None => format!("`{}`", nix_package.display()),
+            },
+            embedded.sha256,
+            output.display().bold(),
+        );
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
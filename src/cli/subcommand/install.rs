@@ -6,15 +6,16 @@ use std::{
 
 use crate::{
     action::ActionState,
+    bundle::EmbeddedNixPackage,
     cli::{
         ensure_root,
         interaction::{self, PromptChoice},
-        signal_channel, CommandExecute,
+        progress, signal_channel, CommandExecute,
     },
     error::HasExpectedErrors,
     plan::RECEIPT_LOCATION,
     planner::Planner,
-    settings::CommonSettings,
+    settings::{rooted, CommonSettings},
     BuiltinPlanner, InstallPlan, NixInstallerError,
 };
 use clap::{ArgAction, Parser};
@@ -23,6 +24,7 @@ use color_eyre::{
     Section,
 };
 use owo_colors::OwoColorize;
+use tokio::io::AsyncReadExt;
 
 /// Execute an install (possibly using an existing plan)
 ///
@@ -72,20 +74,35 @@ impl CommandExecute for Install {
 
         ensure_root()?;
 
-        let existing_receipt: Option<InstallPlan> = match Path::new(RECEIPT_LOCATION).exists() {
+        let root = match &planner {
+            Some(planner) => planner.root(),
+            None => settings.root.clone(),
+        };
+        let receipt_path = rooted(root.as_deref(), RECEIPT_LOCATION);
+        let receipt_location = receipt_path.display();
+
+        let existing_receipt: Option<InstallPlan> = match receipt_path.exists() {
             true => {
                 tracing::trace!("Reading existing receipt");
-                let install_plan_string = tokio::fs::read_to_string(&RECEIPT_LOCATION)
+                let install_plan_string = tokio::fs::read_to_string(&receipt_path)
                     .await
                     .wrap_err("Reading plan")?;
-                Some(serde_json::from_str(&install_plan_string)?)
+                let existing_receipt: InstallPlan = serde_json::from_str(&install_plan_string)?;
+                if existing_receipt.uninstalled() {
+                    tracing::debug!("Existing receipt was fully uninstalled, ignoring it");
+                    None
+                } else {
+                    Some(existing_receipt)
+                }
             },
             false => None,
         };
 
-        let uninstall_command = match Path::new("/nix/nix-installer").exists() {
-            true => "/nix/nix-installer uninstall".into(),
-            false => format!("curl --proto '=https' --tlsv1.2 -sSf -L https://install.determinate.systems/nix/tag/v{} | sh -s -- uninstall", env!("CARGO_PKG_VERSION")),
+        let installer_path = rooted(root.as_deref(), "/nix/nix-installer");
+        let uninstall_command = match (installer_path.exists(), &root) {
+            (true, None) => format!("{} uninstall", installer_path.display()),
+            (true, Some(_)) => format!("{} uninstall {receipt_location}", installer_path.display()),
+            (false, _) => format!("curl --proto '=https' --tlsv1.2 -sSf -L https://install.determinate.systems/nix/tag/v{} | sh -s -- uninstall", env!("CARGO_PKG_VERSION")),
         };
 
         let mut install_plan = match (planner, plan) {
@@ -95,15 +112,19 @@ impl CommandExecute for Install {
                 match existing_receipt {
                     Some(existing_receipt) => {
                         if existing_receipt.planner.typetag_name() != chosen_planner.typetag_name() {
-                            eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}` which used a different planner, try uninstalling the existing install with `{uninstall_command}`").red());
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}` which used a different planner, try uninstalling the existing install with `{uninstall_command}`").red());
                             return Ok(ExitCode::FAILURE)
                         }
                         if existing_receipt.planner.settings().map_err(|e| eyre!(e))? != chosen_planner.settings().map_err(|e| eyre!(e))? {
-                            eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}` which used different planner settings, try uninstalling the existing install with `{uninstall_command}`").red());
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}` which used different planner settings, try uninstalling the existing install with `{uninstall_command}`").red());
                             return Ok(ExitCode::FAILURE)
                         }
-                        eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}`, with the same settings, already completed, try uninstalling (`{uninstall_command}`) and reinstalling if Nix isn't working").red());
-                        return Ok(ExitCode::FAILURE)
+                        if existing_receipt.actions.iter().all(|v| v.state == ActionState::Completed) {
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}`, with the same settings, already completed, try uninstalling (`{uninstall_command}`) and reinstalling if Nix isn't working").red());
+                            return Ok(ExitCode::FAILURE)
+                        }
+                        eprintln!("{}", format!("Found existing plan in `{receipt_location}` with the same settings which did not complete, resuming it").yellow());
+                        existing_receipt
                     } ,
                     None => {
                         let res = planner.plan().await;
@@ -134,17 +155,18 @@ impl CommandExecute for Install {
                 match existing_receipt {
                     Some(existing_receipt) => {
                         if existing_receipt.planner.typetag_name() != builtin_planner.typetag_name() {
-                            eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}` which used a different planner, try uninstalling the existing install with `{uninstall_command}`").red());
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}` which used a different planner, try uninstalling the existing install with `{uninstall_command}`").red());
                             return Ok(ExitCode::FAILURE)
                         }
                         if existing_receipt.planner.settings().map_err(|e| eyre!(e))? != builtin_planner.settings().map_err(|e| eyre!(e))? {
-                            eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}` which used different planner settings, try uninstalling the existing install with `{uninstall_command}`").red());
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}` which used different planner settings, try uninstalling the existing install with `{uninstall_command}`").red());
                             return Ok(ExitCode::FAILURE)
                         }
                         if existing_receipt.actions.iter().all(|v| v.state == ActionState::Completed) {
-                            eprintln!("{}", format!("Found existing plan in `{RECEIPT_LOCATION}`, with the same settings, already completed, try uninstalling (`{uninstall_command}`) and reinstalling if Nix isn't working").yellow());
+                            eprintln!("{}", format!("Found existing plan in `{receipt_location}`, with the same settings, already completed, try uninstalling (`{uninstall_command}`) and reinstalling if Nix isn't working").yellow());
                             return Ok(ExitCode::SUCCESS)
                         }
+                        eprintln!("{}", format!("Found existing plan in `{receipt_location}` with the same settings which did not complete, resuming it").yellow());
                         existing_receipt
                     },
                     None => {
@@ -189,11 +211,14 @@ impl CommandExecute for Install {
 
         let (tx, rx1) = signal_channel().await?;
 
-        match install_plan.install(rx1).await {
+        let progress = progress::render(install_plan.progress());
+        let res = install_plan.install(rx1).await;
+        progress.await?;
+        match res {
             Err(err) => {
                 if !no_confirm {
                     // Attempt to copy self to the store if possible, but since the install failed, this might not work, that's ok.
-                    copy_self_to_nix_store().await.ok();
+                    copy_self_to_nix_store(&installer_path).await.ok();
 
                     let mut was_expected = false;
                     if let Some(expected) = err.expected() {
@@ -229,7 +254,9 @@ impl CommandExecute for Install {
                         }
                     }
                     let rx2 = tx.subscribe();
+                    let progress = progress::render(install_plan.progress());
                     let res = install_plan.uninstall(rx2).await;
+                    progress.await?;
 
                     match res {
                         Err(NixInstallerError::ActionRevert(errs)) => {
@@ -267,9 +294,11 @@ impl CommandExecute for Install {
                 }
             },
             Ok(_) => {
-                copy_self_to_nix_store()
+                copy_self_to_nix_store(&installer_path)
                     .await
-                    .wrap_err("Copying `nix-installer` to `/nix/nix-installer`")?;
+                    .wrap_err_with(|| {
+                        format!("Copying `nix-installer` to `{}`", installer_path.display())
+                    })?;
                 println!(
                     "\
                     {success}\n\
@@ -301,9 +330,19 @@ impl CommandExecute for Install {
 }
 
 #[tracing::instrument(level = "debug")]
-async fn copy_self_to_nix_store() -> Result<(), std::io::Error> {
+async fn copy_self_to_nix_store(dest: &Path) -> Result<(), std::io::Error> {
     let path = std::env::current_exe()?;
-    tokio::fs::copy(path, "/nix/nix-installer").await?;
-    tokio::fs::set_permissions("/nix/nix-installer", PermissionsExt::from_mode(0o0755)).await?;
+    match EmbeddedNixPackage::read(&path).await {
+        // The embedded Nix package is already unpacked into the store, only the executable itself is needed
+        Ok(Some(embedded)) => {
+            let mut executable = tokio::fs::File::open(&path).await?.take(embedded.offset);
+            let mut out = tokio::fs::File::create(dest).await?;
+            tokio::io::copy(&mut executable, &mut out).await?;
+        },
+        _ => {
+            tokio::fs::copy(path, dest).await?;
+        },
+    }
+    tokio::fs::set_permissions(dest, PermissionsExt::from_mode(0o0755)).await?;
     Ok(())
 }
@@ -4,10 +4,22 @@ mod install;
 use install::Install;
 mod uninstall;
 use uninstall::Uninstall;
+mod receipt;
+use receipt::Receipt;
+mod verify;
+use verify::Verify;
+mod repair;
+use repair::Repair;
+mod bundle;
+use bundle::Bundle;
 
 #[derive(Debug, clap::Subcommand)]
 pub enum NixInstallerSubcommand {
     Plan(Plan),
     Install(Install),
     Uninstall(Uninstall),
+    Receipt(Receipt),
+    Verify(Verify),
+    Repair(Repair),
+    Bundle(Bundle),
 }
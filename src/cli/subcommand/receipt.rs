@@ -0,0 +1,96 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use crate::{
+    migrations,
+    plan::{current_version, RECEIPT_LOCATION},
+    InstallPlan,
+};
+use clap::{Parser, Subcommand};
+use eyre::WrapErr;
+use owo_colors::OwoColorize;
+
+use crate::cli::CommandExecute;
+
+/// Work with the receipt of an existing install
+#[derive(Debug, Parser)]
+pub struct Receipt {
+    #[clap(subcommand)]
+    pub subcommand: ReceiptSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ReceiptSubcommand {
+    Migrate(Migrate),
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for Receipt {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(self) -> eyre::Result<ExitCode> {
+        let Self { subcommand } = self;
+
+        match subcommand {
+            ReceiptSubcommand::Migrate(migrate) => migrate.execute().await,
+        }
+    }
+}
+
+/// Upgrade a receipt written by an older `nix-installer` into the current format, and show it
+///
+/// The receipt itself is not changed
+#[derive(Debug, Parser)]
+pub struct Migrate {
+    #[clap(default_value = RECEIPT_LOCATION)]
+    pub receipt: PathBuf,
+    /// Where to write the upgraded receipt (in JSON format)
+    #[clap(
+        long = "out-file",
+        env = "NIX_INSTALLER_RECEIPT_OUT_FILE",
+        default_value = "/dev/stdout"
+    )]
+    pub output: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for Migrate {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(self) -> eyre::Result<ExitCode> {
+        let Self { receipt, output } = self;
+
+        let receipt_string = tokio::fs::read_to_string(&receipt)
+            .await
+            .wrap_err("Reading receipt")?;
+        let mut receipt_value: serde_json::Value = serde_json::from_str(&receipt_string)?;
+
+        let applied = match migrations::migrate(&mut receipt_value, &current_version()?) {
+            Ok(applied) => applied,
+            Err(err) => {
+                eprintln!("{}", err.to_string().red());
+                return Ok(ExitCode::FAILURE);
+            },
+        };
+        if applied.is_empty() {
+            eprintln!(
+                "{}",
+                "Receipt is already in the current format, no migrations were needed".yellow()
+            );
+        }
+        for migration in applied {
+            eprintln!(
+                "* {}: {}",
+                format!("v{}", migration.version).bold(),
+                migration.description
+            );
+        }
+
+        // Ensure the upgraded receipt is actually valid
+        let install_plan = InstallPlan::try_from(receipt_value)?;
+
+        let json = serde_json::to_string_pretty(&install_plan)?;
+        tokio::fs::write(output, format!("{json}\n"))
+            .await
+            .wrap_err("Writing receipt")?;
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
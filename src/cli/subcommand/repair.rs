@@ -0,0 +1,95 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use crate::{
+    cli::{ensure_root, signal_channel},
+    error::HasExpectedErrors,
+    plan::RECEIPT_LOCATION,
+    InstallPlan,
+};
+use clap::{ArgAction, Parser};
+use owo_colors::OwoColorize;
+
+use crate::cli::CommandExecute;
+
+/// Re-apply the steps of an existing install (only `nix-installer` done installs supported) which have drifted
+///
+/// For example, restoring the shell profile snippets after an OS upgrade removed them, or relinking the
+/// Nix daemon's units. The receipt is updated as steps are repaired.
+#[derive(Debug, Parser)]
+pub struct Repair {
+    /// Only list what would be repaired, without changing anything
+    #[clap(
+        long,
+        env = "NIX_INSTALLER_DRY_RUN",
+        action(ArgAction::SetTrue),
+        default_value = "false"
+    )]
+    pub dry_run: bool,
+
+    #[clap(default_value = RECEIPT_LOCATION)]
+    pub receipt: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for Repair {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(self) -> eyre::Result<ExitCode> {
+        let Self { dry_run, receipt } = self;
+
+        if !dry_run {
+            ensure_root()?;
+        }
+
+        let mut plan = InstallPlan::from_receipt(receipt).await?;
+
+        if plan.uninstalled() {
+            println!(
+                "{}",
+                "Nix is not installed, there is nothing to repair".yellow()
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+
+        let res = if dry_run {
+            plan.verify().await
+        } else {
+            let (_tx, rx) = signal_channel().await?;
+            plan.repair(rx).await
+        };
+        let drift = match res {
+            Ok(drift) => drift,
+            Err(err) => {
+                if let Some(expected) = err.expected() {
+                    println!("{}", expected.red());
+                    return Ok(ExitCode::FAILURE);
+                }
+                return Err(err)?;
+            },
+        };
+
+        if drift.is_empty() {
+            println!(
+                "{}",
+                "The Nix install is intact, there is nothing to repair"
+                    .green()
+                    .bold()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        println!(
+            "{}",
+            if dry_run {
+                "The following would be repaired:"
+            } else {
+                "Repaired the following:"
+            }
+            .bold()
+        );
+        for drift in drift {
+            println!("* {}: {}", drift.synopsis.bold(), drift.description);
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    cli::{ensure_root, interaction::PromptChoice, signal_channel},
+    cli::{ensure_root, interaction::PromptChoice, progress, signal_channel},
     error::HasExpectedErrors,
     plan::RECEIPT_LOCATION,
     InstallPlan, NixInstallerError,
@@ -38,6 +38,17 @@ pub struct Uninstall {
     )]
     pub explain: bool,
 
+    /// Also remove the Nix package (and its signature) the install kept in its cache directory (if any), and that
+    /// directory if nothing else is left in it
+    #[clap(
+        long,
+        env = "NIX_INSTALLER_PURGE_CACHE",
+        action(ArgAction::SetTrue),
+        default_value = "false",
+        global = true
+    )]
+    pub purge_cache: bool,
+
     #[clap(default_value = RECEIPT_LOCATION)]
     pub receipt: PathBuf,
 }
@@ -50,6 +61,7 @@ impl CommandExecute for Uninstall {
             no_confirm,
             receipt,
             explain,
+            purge_cache,
         } = self;
 
         ensure_root()?;
@@ -96,10 +108,15 @@ impl CommandExecute for Uninstall {
             }
         }
 
-        let install_receipt_string = tokio::fs::read_to_string(receipt)
-            .await
-            .wrap_err("Reading receipt")?;
-        let mut plan: InstallPlan = serde_json::from_str(&install_receipt_string)?;
+        let mut plan = InstallPlan::from_receipt(receipt).await?;
+
+        if plan.uninstalled() {
+            println!(
+                "{}",
+                "Nix was already uninstalled, there is nothing to do".yellow()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
 
         if !no_confirm {
             let mut currently_explaining = explain;
@@ -124,7 +141,9 @@ impl CommandExecute for Uninstall {
 
         let (_tx, rx) = signal_channel().await?;
 
+        let progress = progress::render(plan.progress());
         let res = plan.uninstall(rx).await;
+        progress.await?;
         match res {
             Err(err @ NixInstallerError::ActionRevert(_)) => {
                 tracing::error!("Uninstallation complete, some errors encountered");
@@ -140,6 +159,10 @@ impl CommandExecute for Uninstall {
             _ => (),
         }
 
+        if purge_cache {
+            plan.purge_nix_package_cache().await?;
+        }
+
         println!(
             "\
             {success}\n\
@@ -0,0 +1,60 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use crate::{error::HasExpectedErrors, plan::RECEIPT_LOCATION, InstallPlan};
+use clap::Parser;
+use owo_colors::OwoColorize;
+
+use crate::cli::CommandExecute;
+
+/// Check that an existing install (only `nix-installer` done installs supported) is still intact
+///
+/// Each completed step of the receipt is compared against the system, nothing is changed.
+/// Exits with a failure if anything has drifted.
+#[derive(Debug, Parser)]
+pub struct Verify {
+    #[clap(default_value = RECEIPT_LOCATION)]
+    pub receipt: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl CommandExecute for Verify {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(self) -> eyre::Result<ExitCode> {
+        let Self { receipt } = self;
+
+        let plan = InstallPlan::from_receipt(receipt).await?;
+
+        if plan.uninstalled() {
+            println!(
+                "{}",
+                "Nix is not installed, there is nothing to verify".yellow()
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+
+        let drift = match plan.verify().await {
+            Ok(drift) => drift,
+            Err(err) => {
+                if let Some(expected) = err.expected() {
+                    println!("{}", expected.red());
+                    return Ok(ExitCode::FAILURE);
+                }
+                return Err(err)?;
+            },
+        };
+
+        if drift.is_empty() {
+            println!("{}", "The Nix install is intact".green().bold());
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        println!(
+            "{}",
+            "The system has drifted from the Nix install:".red().bold()
+        );
+        for drift in drift {
+            println!("* {}: {}", drift.synopsis.bold(), drift.description);
+        }
+        Ok(ExitCode::FAILURE)
+    }
+}
@@ -18,9 +18,16 @@ pub enum NixInstallerError {
         }
     }).collect::<Vec<_>>().join("\n"))]
     ActionRevert(Vec<ActionError>),
+    /// An error while reading the [`InstallPlan`](crate::InstallPlan)
+    #[error("Reading install receipt `{0}`")]
+    ReadingReceipt(PathBuf, #[source] std::io::Error),
     /// An error while writing the [`InstallPlan`](crate::InstallPlan)
     #[error("Recording install receipt")]
     RecordingReceipt(PathBuf, #[source] std::io::Error),
+    /// An error while removing what was kept in the
+    /// [`nix_package_cache_dir`](crate::settings::CommonSettings::nix_package_cache_dir)
+    #[error("Purging the Nix package cache `{0}`")]
+    PurgingCache(PathBuf, #[source] std::io::Error),
     /// An error while writing copying the binary into the `/nix` folder
     #[error("Copying `nix-installer` binary into `/nix`")]
     CopyingSelf(
@@ -79,7 +86,9 @@ impl HasExpectedErrors for NixInstallerError {
         match self {
             NixInstallerError::Action(action_error) => action_error.kind().expected(),
             NixInstallerError::ActionRevert(_) => None,
+            NixInstallerError::ReadingReceipt(_, _) => None,
             NixInstallerError::RecordingReceipt(_, _) => None,
+            NixInstallerError::PurgingCache(_, _) => None,
             NixInstallerError::CopyingSelf(_) => None,
             NixInstallerError::SerializingReceipt(_) => None,
             this @ NixInstallerError::Cancelled => Some(Box::new(this)),
@@ -0,0 +1,398 @@
+/*! Crash-safe journaling of the install receipt
+
+While an [`InstallPlan`](crate::InstallPlan) is being installed or uninstalled, the receipt is persisted before and after
+every [`StatefulAction`] transition, including transitions of actions nested inside of other actions. That way,
+if the installer is killed (or the machine loses power) part way through, the receipt on disk still records
+which actions completed, allowing the install to be resumed or uninstalled.
+
+Nested actions have no access to the plan they are part of, so the journal keeps its own JSON copy of the
+receipt, and a [`Journal`] handle is passed down along with each action. The plan hands each top level action a
+handle pointing at its index in the receipt, when a [`StatefulAction`] starts a transition it narrows the handle it
+was given to the sub-[`Action`](crate::action::Action) it is (by comparing everything but the states of it), which
+it then passes to its own sub-[`Action`](crate::action::Action)s.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+use crate::action::StatefulAction;
+
+/// A handle to the journal of a running [`InstallPlan`](crate::InstallPlan), pointing at the [`StatefulAction`]
+/// (or its parent) being run
+///
+/// A [`Default`] handle journals nothing, such as when an action is run on its own.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    receipt: Option<Arc<Mutex<JournaledReceipt>>>,
+    /// A JSON pointer to where the action is in the receipt
+    pointer: String,
+}
+
+#[derive(Debug)]
+struct JournaledReceipt {
+    location: PathBuf,
+    /// Where to persist the receipt once the directory of the `location` no longer exists, if anywhere
+    fallback: Option<PathBuf>,
+    receipt: serde_json::Value,
+}
+
+impl Journal {
+    /// Start journaling `receipt` (a serialized [`InstallPlan`](crate::InstallPlan)) to `location` (or to
+    /// `fallback`, once the directory of `location` no longer exists), persisting it
+    pub(crate) async fn begin(
+        location: PathBuf,
+        fallback: Option<PathBuf>,
+        receipt: serde_json::Value,
+    ) -> Self {
+        let journaled = JournaledReceipt {
+            location,
+            fallback,
+            receipt,
+        };
+        journaled.persist().await;
+        Self {
+            receipt: Some(Arc::new(Mutex::new(journaled))),
+            pointer: String::new(),
+        }
+    }
+
+    /// A handle pointing at the top level action at `index`
+    pub(crate) fn action(&self, index: usize) -> Self {
+        Self {
+            receipt: self.receipt.clone(),
+            pointer: format!("/actions/{index}"),
+        }
+    }
+
+    /// Replace the journaled receipt, eg. after the plan itself has been written with
+    /// [`write_receipt`](crate::plan::write_receipt)
+    pub(crate) async fn replace(&self, receipt: serde_json::Value) {
+        if let Some(journaled) = &self.receipt {
+            journaled.lock().await.receipt = receipt;
+        }
+    }
+
+    /// Replace the journaled receipt and persist it, eg. after a top level action was executed or reverted
+    pub(crate) async fn checkpoint(&self, receipt: serde_json::Value) {
+        if let Some(journaled) = &self.receipt {
+            let mut journaled = journaled.lock().await;
+            journaled.receipt = receipt;
+            journaled.persist().await;
+        }
+    }
+
+    /// A handle pointing at `action`, which is either the action this handle points at or one nested inside of it
+    ///
+    /// If `action` cannot be found its transitions are not journaled.
+    pub(crate) async fn enter<A>(&self, action: &StatefulAction<A>) -> Self
+    where
+        StatefulAction<A>: Serialize,
+    {
+        let Some(journaled) = &self.receipt else {
+            return Self::default();
+        };
+        let key = match serde_json::to_value(action) {
+            Ok(value) => without_states(&value),
+            Err(err) => {
+                tracing::error!("Error serializing action for receipt: {:?}", err);
+                return Self::default();
+            },
+        };
+
+        let guard = journaled.lock().await;
+        let Some(scope) = guard.receipt.pointer(&self.pointer) else {
+            return Self::default();
+        };
+        let found = if is_stateful_action(scope) && without_states(scope) == key {
+            Some(String::new())
+        } else {
+            find_matching(scope, &key)
+        };
+        match found {
+            Some(relative) => Self {
+                receipt: self.receipt.clone(),
+                pointer: format!("{}{relative}", self.pointer),
+            },
+            None => {
+                tracing::trace!("Action not found in receipt, not journaling its transitions");
+                Self::default()
+            },
+        }
+    }
+
+    /// Persist the current state of `action`, which this handle was [entered](Journal::enter) for, if journaling
+    ///
+    /// Errors are logged rather than returned, a failure to journal should not fail the action itself.
+    pub(crate) async fn record<A>(&self, action: &StatefulAction<A>)
+    where
+        StatefulAction<A>: Serialize,
+    {
+        let Some(journaled) = &self.receipt else {
+            return;
+        };
+        let updated = match serde_json::to_value(action) {
+            Ok(updated) => updated,
+            Err(err) => {
+                tracing::error!("Error serializing action for receipt: {:?}", err);
+                return;
+            },
+        };
+
+        let mut guard = journaled.lock().await;
+        let Some(slot) = guard.receipt.pointer_mut(&self.pointer) else {
+            return;
+        };
+        *slot = updated;
+        guard.persist().await;
+    }
+}
+
+/// Where an uninstall journals the receipt at `location` once the directory containing it was removed, next to that
+/// directory (eg. `/nix-receipt.json` for `/nix/receipt.json`)
+pub(crate) fn fallback_location(location: &Path) -> PathBuf {
+    let (Some(directory), Some(file_name)) = (location.parent(), location.file_name()) else {
+        return location.to_path_buf();
+    };
+    let (Some(parent), Some(directory_name)) = (directory.parent(), directory.file_name()) else {
+        return location.to_path_buf();
+    };
+    parent.join(format!(
+        "{}-{}",
+        directory_name.to_string_lossy(),
+        file_name.to_string_lossy()
+    ))
+}
+
+impl JournaledReceipt {
+    /// Write the receipt to the location (or the fallback), if the directory containing it exists
+    ///
+    /// The directory is never created, before it is created during an install (eg. `/nix` on Mac, which
+    /// requires a volume to be created first) or after it is removed during an uninstall, the journal has
+    /// nowhere to go but the fallback.
+    async fn persist(&self) {
+        let Self {
+            location,
+            fallback,
+            receipt,
+            ..
+        } = self;
+        let exists = |path: &Path| path.parent().map(Path::exists).unwrap_or(true);
+        let location = match fallback {
+            _ if exists(location) => location,
+            Some(fallback) if exists(fallback) => fallback,
+            _ => {
+                tracing::trace!("Receipt directory does not exist, not journaling");
+                return;
+            },
+        };
+
+        let buf = match serde_json::to_string_pretty(receipt) {
+            Ok(buf) => buf,
+            Err(err) => {
+                tracing::error!("Error serializing receipt: {:?}", err);
+                return;
+            },
+        };
+        if let Err(err) = write_atomically(location, format!("{buf}\n").as_bytes()).await {
+            tracing::error!("Error saving receipt: {:?}", err);
+        }
+    }
+}
+
+/// Write `buf` to `path` such that `path` always contains either the old or the new content
+///
+/// The content is written to a temporary file next to `path`, synced, and then renamed over `path`. The
+/// containing directory is then synced so the rename itself is durable.
+pub(crate) async fn write_atomically(path: &Path, buf: &[u8]) -> Result<(), std::io::Error> {
+    let temp_path = temp_path(path);
+    let mut file = tokio::fs::File::create(&temp_path).await?;
+    file.write_all(buf).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&temp_path, path).await?;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::File::open(parent).await?.sync_all().await?;
+    }
+    Ok(())
+}
+
+/// The temporary file `path` is written to before it is renamed into place, in the same directory so the rename is
+/// atomic
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.to_path_buf();
+    temp_path.set_file_name(format!(
+        ".{}.tmp",
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    ));
+    temp_path
+}
+
+/// If `value` is a serialized [`StatefulAction`]
+pub(crate) fn is_stateful_action(value: &serde_json::Value) -> bool {
+    match value.as_object() {
+        Some(map) => {
+            map.contains_key("action")
+                && map.contains_key("state")
+                && map
+                    .keys()
+                    .all(|key| ["action", "state", "retry", "attempts"].contains(&key.as_str()))
+        },
+        None => false,
+    }
+}
+
+/// A copy of `value` with the `state` (and everything else but the `action`) of every serialized [`StatefulAction`]
+/// removed
+fn without_states(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) if is_stateful_action(value) => {
+            serde_json::json!({ "action": without_states(&map["action"]) })
+        },
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.clone(), without_states(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Array(values) => values.iter().map(without_states).collect(),
+        other => other.clone(),
+    }
+}
+
+/// A JSON pointer (relative to `value`) to the first serialized [`StatefulAction`] nested inside of `value` which
+/// matches `key` (ignoring states)
+fn find_matching(value: &serde_json::Value, key: &serde_json::Value) -> Option<String> {
+    let children: Box<dyn Iterator<Item = (String, &serde_json::Value)>> = match value {
+        serde_json::Value::Object(map) => Box::new(
+            map.iter()
+                .map(|(name, child)| (name.replace('~', "~0").replace('/', "~1"), child)),
+        ),
+        serde_json::Value::Array(values) => Box::new(
+            values
+                .iter()
+                .enumerate()
+                .map(|(index, child)| (index.to_string(), child)),
+        ),
+        _ => return None,
+    };
+    for (name, child) in children {
+        if is_stateful_action(child) && without_states(child) == *key {
+            return Some(format!("/{name}"));
+        }
+        if let Some(found) = find_matching(child, key) {
+            return Some(format!("/{name}{found}"));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_nested_action_ignoring_states() {
+        let receipt = serde_json::json!({
+            "actions": [{
+                "action": {
+                    "action": "parent",
+                    "children": [
+                        { "action": { "action": "child", "path": "/a" }, "state": "Completed" },
+                        { "action": { "action": "child", "path": "/b" }, "state": "Uncompleted" },
+                    ],
+                },
+                "state": "Progress",
+            }],
+        });
+        let child = serde_json::json!({
+            "action": { "action": "child", "path": "/b" },
+            "state": "Progress",
+        });
+        assert_eq!(
+            find_matching(&receipt, &without_states(&child)).as_deref(),
+            Some("/actions/0/action/children/1")
+        );
+
+        // The parent can still be found even though the state of its children changed
+        let parent = serde_json::json!({
+            "action": {
+                "action": "parent",
+                "children": [
+                    { "action": { "action": "child", "path": "/a" }, "state": "Completed" },
+                    { "action": { "action": "child", "path": "/b" }, "state": "Completed" },
+                ],
+            },
+            "state": "Completed",
+        });
+        assert_eq!(
+            find_matching(&receipt, &without_states(&parent)).as_deref(),
+            Some("/actions/0")
+        );
+
+        let missing = serde_json::json!({
+            "action": { "action": "child", "path": "/c" },
+            "state": "Progress",
+        });
+        assert_eq!(find_matching(&receipt, &without_states(&missing)), None);
+    }
+
+    #[tokio::test]
+    async fn records_nested_action_through_handle() -> eyre::Result<()> {
+        use crate::action::{base::CreateDirectory, CancellationToken, Progress};
+
+        let temp_dir = tempfile::tempdir()?;
+        let location = temp_dir.path().join("receipt.json");
+        let mut child =
+            CreateDirectory::plan(temp_dir.path().join("child"), None, None, None, false, None)
+                .await?;
+        let receipt = serde_json::json!({
+            "actions": [
+                { "action": { "action": "other", "children": [serde_json::to_value(&child)?] }, "state": "Uncompleted" },
+                { "action": { "action": "parent", "children": [serde_json::to_value(&child)?] }, "state": "Progress" },
+            ],
+        });
+
+        let journal = Journal::begin(location.clone(), None, receipt).await;
+        child
+            .try_execute(
+                &CancellationToken::new(),
+                &journal.action(1),
+                &Progress::default(),
+            )
+            .await?;
+
+        let persisted: serde_json::Value =
+            serde_json::from_str(&tokio::fs::read_to_string(&location).await?)?;
+        // Only the action at the index the handle was given is recorded, even though an identical one comes first
+        assert_eq!(
+            persisted["actions"][0]["action"]["children"][0]["state"],
+            "Uncompleted"
+        );
+        assert_eq!(
+            persisted["actions"][1]["action"]["children"][0]["state"],
+            "Completed"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_atomically_replaces_contents() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("receipt.json");
+        tokio::fs::write(&path, "old").await?;
+
+        write_atomically(&path, b"new").await?;
+
+        assert_eq!(tokio::fs::read_to_string(&path).await?, "new");
+        assert!(!temp_dir.path().join(".receipt.json.tmp").exists());
+        Ok(())
+    }
+}
@@ -70,14 +70,20 @@ match plan.install(None).await {
 */
 
 pub mod action;
+pub mod bundle;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod credentials;
 #[cfg(feature = "diagnostics")]
 pub mod diagnostics;
 mod error;
+mod journal;
+mod migrations;
 mod os;
 mod plan;
 pub mod planner;
+pub mod progress;
+mod schedule;
 pub mod settings;
 
 use std::{ffi::OsStr, path::Path, process::Output};
@@ -89,15 +95,32 @@ use planner::BuiltinPlanner;
 use reqwest::Certificate;
 use tokio::process::Command;
 
-use crate::action::{Action, ActionErrorKind};
+use crate::action::{Action, ActionErrorKind, CancellationToken};
 
+/// Run `command` to completion, erroring if it did not succeed
+///
+/// If `cancel` is cancelled before the command exits, the command is killed.
 #[tracing::instrument(level = "debug", skip_all, fields(command = %format!("{:?}", command.as_std())))]
-async fn execute_command(command: &mut Command) -> Result<Output, ActionErrorKind> {
+async fn execute_command(
+    command: &mut Command,
+    cancel: impl Into<Option<&CancellationToken>>,
+) -> Result<Output, ActionErrorKind> {
     tracing::trace!("Executing");
-    let output = command
-        .output()
-        .await
-        .map_err(|e| ActionErrorKind::command(command, e))?;
+    let output = match cancel.into() {
+        Some(cancel) => {
+            // The command is killed when the future waiting on it is dropped
+            command.kill_on_drop(true);
+            tokio::select! {
+                output = command.output() => output,
+                _ = cancel.cancelled() => {
+                    tracing::debug!("Killed (Cancelled)");
+                    return Err(ActionErrorKind::Cancelled);
+                },
+            }
+        },
+        None => command.output().await,
+    }
+    .map_err(|e| ActionErrorKind::command(command, e))?;
     match output.status.success() {
         true => Ok(output),
         false => Err(ActionErrorKind::command_output(command, output)),
@@ -137,3 +160,25 @@ pub enum CertificateError {
     #[error("Unknown certificate format, `der` and `pem` supported")]
     UnknownCertFormat,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn execute_command_kills_cancelled_command() -> eyre::Result<()> {
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let res = execute_command(Command::new("sleep").arg("30"), &cancel).await;
+
+        assert!(matches!(res, Err(ActionErrorKind::Cancelled)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+/*! Migrations of receipts recorded by older versions of `nix-installer`
+
+The structure of an [`InstallPlan`](crate::InstallPlan) changes between versions, actions are renamed, gain
+fields, or are restructured. So that an install done by an older version can still be uninstalled by the current
+one, receipts are upgraded (as JSON) into the current structure before they are deserialized.
+
+Each [`Migration`] upgrades a receipt recorded before its [`version`](Migration::version) into the structure
+of that version. Migrations are applied in order, and must be idempotent.
+*/
+
+use semver::Version;
+use serde_json::{json, Value};
+
+/// The oldest receipt version which can be migrated
+pub(crate) const MINIMUM_VERSION: Version = Version::new(0, 7, 0);
+
+pub(crate) struct Migration {
+    /// Receipts recorded before this version (including by its pre-releases) are migrated
+    pub(crate) version: Version,
+    pub(crate) description: &'static str,
+    migrate: fn(&mut Value),
+}
+
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: Version::new(0, 8, 0),
+        description: "Restructure the `delete_users` of `provision_nix` into `delete_users_in_group`, add `force_prune_on_revert` to `create_directory`, and `force` to the planner settings",
+        migrate: to_0_8_0,
+    },
+    Migration {
+        version: Version::new(0, 8, 1),
+        description: "Add `root` to the `move_unpacked_nix` of `provision_nix`, renaming it from `mount_unpacked_nix` where it is tagged",
+        migrate: to_0_8_1,
+    },
+];
+
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("Receipt has no `version`, it may not be a receipt")]
+    MissingVersion,
+    #[error("Parsing receipt version `{0}`")]
+    Version(String, #[source] semver::Error),
+    #[error("This version of `nix-installer` ({current}) is not compatible with this plan's version ({receipt}), check for a compatible version at `/nix/nix-installer` or download the matching release from https://github.com/DeterminateSystems/nix-installer/releases")]
+    TooNew { receipt: Version, current: Version },
+    #[error("This plan's version ({receipt}) is older than the oldest version this version of `nix-installer` ({current}) can migrate ({MINIMUM_VERSION}), check for a compatible version at `/nix/nix-installer` or download the matching release from https://github.com/DeterminateSystems/nix-installer/releases")]
+    TooOld { receipt: Version, current: Version },
+}
+
+/// Upgrade `receipt` to the structure of `current`, returning the migrations which were applied
+pub(crate) fn migrate(
+    receipt: &mut Value,
+    current: &Version,
+) -> Result<Vec<&'static Migration>, MigrationError> {
+    let receipt_version = receipt
+        .get("version")
+        .and_then(Value::as_str)
+        .ok_or(MigrationError::MissingVersion)?;
+    let receipt_version = Version::parse(receipt_version)
+        .map_err(|e| MigrationError::Version(receipt_version.to_string(), e))?;
+
+    // A receipt recorded by a pre-release of `current` may predate some of its migrations, so is still migrated
+    if receipt_version > *current {
+        return Err(MigrationError::TooNew {
+            receipt: receipt_version,
+            current: current.clone(),
+        });
+    }
+    if receipt_version < MINIMUM_VERSION {
+        return Err(MigrationError::TooOld {
+            receipt: receipt_version,
+            current: current.clone(),
+        });
+    }
+
+    let mut applied = vec![];
+    for migration in MIGRATIONS {
+        let Migration {
+            version, migrate, ..
+        } = migration;
+        // A pre-release precedes its release, so its receipts are migrated to the release
+        if receipt_version < *version {
+            tracing::debug!(
+                "Migrating receipt from {receipt_version} to {version}: {}",
+                migration.description
+            );
+            migrate(receipt);
+            applied.push(migration);
+        }
+    }
+    receipt["version"] = json!(current.to_string());
+
+    Ok(applied)
+}
+
+fn to_0_8_0(receipt: &mut Value) {
+    for_each_action(receipt, &mut |tag, action| match tag {
+        Some("provision_nix") => {
+            let Some(delete_users) = action.remove("delete_users") else {
+                return;
+            };
+            let delete_users = delete_users.as_array().cloned().unwrap_or_default();
+            if delete_users.is_empty() {
+                action.insert("delete_users_in_group".into(), Value::Null);
+                return;
+            }
+            let state = combined_state(&delete_users);
+            let group_name = action["create_group"]["action"]["name"].clone();
+            let group_id = action["create_group"]["action"]["gid"].clone();
+            action.insert(
+                "delete_users_in_group".into(),
+                json!({
+                    "action": {
+                        "group_name": group_name,
+                        "group_id": group_id,
+                        "delete_users": delete_users,
+                    },
+                    "state": state,
+                }),
+            );
+        },
+        _ => {
+            // `create_directory`, possibly untagged when nested
+            if action.contains_key("path")
+                && action.contains_key("user")
+                && action.contains_key("group")
+                && action.contains_key("mode")
+                && !action.contains_key("buf")
+            {
+                action
+                    .entry("force_prune_on_revert")
+                    .or_insert(Value::Bool(false));
+            }
+        },
+    });
+
+    if let Some(settings) = receipt
+        .get_mut("planner")
+        .and_then(|planner| planner.get_mut("settings"))
+        .and_then(Value::as_object_mut)
+    {
+        settings.entry("force").or_insert(Value::Bool(false));
+        // Build users were replaced by `auto-allocate-uids`
+        settings.remove("nix_build_user_count");
+        settings.remove("nix_build_user_prefix");
+        settings.remove("nix_build_user_id_base");
+    }
+}
+
+fn to_0_8_1(receipt: &mut Value) {
+    for_each_action(receipt, &mut |tag, action| match tag {
+        // Nested inside `provision_nix` it is not tagged, so is found by where it is
+        Some("provision_nix") => {
+            if let Some(move_unpacked_nix) = action
+                .get_mut("move_unpacked_nix")
+                .and_then(|stateful| stateful.get_mut("action"))
+                .and_then(Value::as_object_mut)
+            {
+                move_unpacked_nix.entry("root").or_insert(Value::Null);
+            }
+        },
+        Some("mount_unpacked_nix") => {
+            action.insert("action".into(), json!("move_unpacked_nix"));
+            action.entry("root").or_insert(Value::Null);
+        },
+        _ => (),
+    });
+}
+
+/// Call `f` on every action (the inner `action` of a serialized [`StatefulAction`](crate::action::StatefulAction)),
+/// along with its typetag, if it has one (nested actions of a concrete type do not)
+fn for_each_action<F>(value: &mut Value, f: &mut F)
+where
+    F: FnMut(Option<&str>, &mut serde_json::Map<String, Value>),
+{
+    let is_stateful_action = crate::journal::is_stateful_action(value);
+    match value {
+        Value::Object(map) => {
+            if is_stateful_action {
+                if let Some(Value::Object(action)) = map.get_mut("action") {
+                    let tag = action
+                        .get("action")
+                        .and_then(Value::as_str)
+                        .map(ToString::to_string);
+                    f(tag.as_deref(), action);
+                }
+            }
+            for child in map.values_mut() {
+                for_each_action(child, f);
+            }
+        },
+        Value::Array(values) => {
+            for child in values {
+                for_each_action(child, f);
+            }
+        },
+        _ => (),
+    }
+}
+
+/// The state of a composite action made of actions in `children`
+fn combined_state(children: &[Value]) -> &'static str {
+    let all = |state: &str| children.iter().all(|child| child["state"] == state);
+    if all("Completed") {
+        "Completed"
+    } else if children
+        .iter()
+        .all(|child| child["state"] == "Uncompleted" || child["state"] == "Skipped")
+    {
+        "Uncompleted"
+    } else {
+        "Progress"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrations_are_ordered() {
+        assert!(MIGRATIONS
+            .windows(2)
+            .all(|pair| pair[0].version < pair[1].version));
+    }
+
+    #[test]
+    fn migrates_nested_move_unpacked_nix() -> Result<(), MigrationError> {
+        let unpacked = json!({ "unpacked_path": "/nix/temp-install-dir" });
+        let mut receipt = json!({
+            "version": "0.8.0",
+            "actions": [
+                {
+                    "action": {
+                        "action": "provision_nix",
+                        "move_unpacked_nix": { "action": unpacked, "state": "Completed" },
+                    },
+                    "state": "Completed",
+                },
+                {
+                    "action": { "action": "mount_unpacked_nix", "unpacked_path": "/nix/temp-install-dir" },
+                    "state": "Completed",
+                },
+                {
+                    // Which has the same fields, but is not moving Nix
+                    "action": {
+                        "action": "configure_nix",
+                        "setup_default_profile": { "action": unpacked, "state": "Completed" },
+                    },
+                    "state": "Completed",
+                },
+            ],
+        });
+        let applied = migrate(&mut receipt, &Version::new(0, 8, 1))?;
+
+        assert_eq!(applied.len(), 1);
+        assert_eq!(receipt["version"], "0.8.1");
+        let move_unpacked_nix = &receipt["actions"][0]["action"]["move_unpacked_nix"]["action"];
+        assert_eq!(move_unpacked_nix.get("root"), Some(&Value::Null));
+        assert_eq!(
+            receipt["actions"][1]["action"]["action"],
+            "move_unpacked_nix"
+        );
+        let setup_default_profile =
+            &receipt["actions"][2]["action"]["setup_default_profile"]["action"];
+        assert_eq!(setup_default_profile, &unpacked);
+        Ok(())
+    }
+
+    #[test]
+    fn migrates_pre_releases() -> Result<(), MigrationError> {
+        let mut receipt = json!({
+            "version": "0.8.1-unreleased",
+            "actions": [{
+                "action": {
+                    "action": "provision_nix",
+                    "move_unpacked_nix": {
+                        "action": { "unpacked_path": "/nix/temp-install-dir" },
+                        "state": "Completed",
+                    },
+                },
+                "state": "Completed",
+            }],
+        });
+        let current = Version::parse("0.8.1-unreleased").unwrap();
+        let applied = migrate(&mut receipt, &current)?;
+
+        assert_eq!(applied.len(), 1);
+        assert_eq!(receipt["version"], "0.8.1-unreleased");
+        assert_eq!(
+            receipt["actions"][0]["action"]["move_unpacked_nix"]["action"].get("root"),
+            Some(&Value::Null)
+        );
+
+        // A receipt of a release is already in its structure
+        let mut receipt = json!({ "version": "0.8.1", "actions": [] });
+        assert!(migrate(&mut receipt, &Version::new(0, 8, 1))?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn restructures_delete_users() -> Result<(), MigrationError> {
+        let mut receipt = json!({
+            "version": "0.7.0",
+            "actions": [{
+                "action": {
+                    "action": "provision_nix",
+                    "delete_users": [
+                        { "action": { "name": "nixbld1" }, "state": "Completed" },
+                        { "action": { "name": "nixbld2" }, "state": "Uncompleted" },
+                    ],
+                    "create_group": { "action": { "name": "nixbld", "gid": 30000 }, "state": "Completed" },
+                },
+                "state": "Progress",
+            }],
+        });
+        migrate(&mut receipt, &Version::new(0, 8, 1))?;
+
+        let provision_nix = &receipt["actions"][0]["action"];
+        assert!(provision_nix.get("delete_users").is_none());
+        assert_eq!(provision_nix["delete_users_in_group"]["state"], "Progress");
+        assert_eq!(
+            provision_nix["delete_users_in_group"]["action"]["group_id"],
+            30000
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_newer_and_ancient() {
+        let current = Version::new(0, 8, 1);
+        assert!(matches!(
+            migrate(&mut json!({ "version": "0.9.0" }), &current),
+            Err(MigrationError::TooNew { .. })
+        ));
+        assert!(matches!(
+            migrate(&mut json!({ "version": "0.6.0" }), &current),
+            Err(MigrationError::TooOld { .. })
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+/*! Offline manipulation of the user & group databases of an alternate root
+
+When installing into an alternate root (see [`CommonSettings::root`](crate::settings::CommonSettings::root)) the
+host's `groupadd`/`userdel` style tools cannot be used, as they operate on the host. Instead, the plain text
+`etc/group`, `etc/gshadow`, `etc/passwd` and `etc/shadow` files inside the root are edited directly.
+*/
+
+use std::{
+    os::unix::prelude::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use rand::Rng;
+
+use crate::action::ActionErrorKind;
+
+/// An entry of an `etc/group` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GroupEntry {
+    pub(crate) name: String,
+    pub(crate) gid: u32,
+    pub(crate) members: Vec<String>,
+}
+
+impl GroupEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split(':');
+        let name = fields.next()?.to_string();
+        let _password = fields.next()?;
+        let gid = fields.next()?.parse().ok()?;
+        let members = fields
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|member| !member.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        Some(Self { name, gid, members })
+    }
+}
+
+/// Find the group called `name` in `{root}/etc/group`
+pub(crate) async fn find_group(
+    root: &Path,
+    name: &str,
+) -> Result<Option<GroupEntry>, ActionErrorKind> {
+    let path = root.join("etc/group");
+    let buf = read_if_exists(&path).await?;
+    Ok(buf
+        .lines()
+        .filter_map(GroupEntry::parse)
+        .find(|entry| entry.name == name))
+}
+
+/// Find the UID of the user called `name` in `{root}/etc/passwd`
+pub(crate) async fn find_user_uid(root: &Path, name: &str) -> Result<Option<u32>, ActionErrorKind> {
+    let path = root.join("etc/passwd");
+    let buf = read_if_exists(&path).await?;
+    Ok(buf
+        .lines()
+        .filter(|line| entry_name(line) == Some(name))
+        .find_map(|line| line.split(':').nth(2)?.parse().ok()))
+}
+
+/// Find if the user called `name` exists in `{root}/etc/passwd`
+pub(crate) async fn user_exists(root: &Path, name: &str) -> Result<bool, ActionErrorKind> {
+    let path = root.join("etc/passwd");
+    let buf = read_if_exists(&path).await?;
+    Ok(buf.lines().any(|line| entry_name(line) == Some(name)))
+}
+
+/// Add a system group called `name` with `gid` to `{root}/etc/group` (and `{root}/etc/gshadow`, if present)
+pub(crate) async fn add_group(root: &Path, name: &str, gid: u32) -> Result<(), ActionErrorKind> {
+    append_line(&root.join("etc/group"), &format!("{name}:x:{gid}:"), true).await?;
+    append_line(&root.join("etc/gshadow"), &format!("{name}:!::"), false).await?;
+    Ok(())
+}
+
+/// Change the GID of the group called `name` in `{root}/etc/group` to `gid`
+pub(crate) async fn set_group_gid(
+    root: &Path,
+    name: &str,
+    gid: u32,
+) -> Result<(), ActionErrorKind> {
+    let path = root.join("etc/group");
+    let buf = read_if_exists(&path).await?;
+    let updated = buf
+        .lines()
+        .map(|line| match entry_name(line) {
+            Some(entry) if entry == name => {
+                let mut fields = line.split(':').map(ToString::to_string).collect::<Vec<_>>();
+                if let Some(field) = fields.get_mut(2) {
+                    *field = gid.to_string();
+                }
+                format!("{}\n", fields.join(":"))
+            },
+            _ => format!("{line}\n"),
+        })
+        .collect::<String>();
+    if updated != buf {
+        replace_contents(&path, &updated).await?;
+    }
+    Ok(())
+}
+
+/// Remove the group called `name` from `{root}/etc/group` (and `{root}/etc/gshadow`, if present)
+pub(crate) async fn remove_group(root: &Path, name: &str) -> Result<(), ActionErrorKind> {
+    remove_entry(&root.join("etc/group"), name).await?;
+    remove_entry(&root.join("etc/gshadow"), name).await?;
+    Ok(())
+}
+
+/// Remove the user called `name` from `{root}/etc/passwd` (and `{root}/etc/shadow`, if present)
+pub(crate) async fn remove_user(root: &Path, name: &str) -> Result<(), ActionErrorKind> {
+    remove_entry(&root.join("etc/passwd"), name).await?;
+    remove_entry(&root.join("etc/shadow"), name).await?;
+    Ok(())
+}
+
+fn entry_name(line: &str) -> Option<&str> {
+    line.split(':').next().filter(|name| !name.is_empty())
+}
+
+async fn read_if_exists(path: &Path) -> Result<String, ActionErrorKind> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(buf) => Ok(buf),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(ActionErrorKind::Read(path.to_path_buf(), e)),
+    }
+}
+
+async fn append_line(path: &Path, line: &str, create: bool) -> Result<(), ActionErrorKind> {
+    if !create && !path.exists() {
+        return Ok(());
+    }
+    let mut buf = read_if_exists(path).await?;
+    if !buf.is_empty() && !buf.ends_with('\n') {
+        buf.push('\n');
+    }
+    buf.push_str(line);
+    buf.push('\n');
+    replace_contents(path, &buf).await
+}
+
+async fn remove_entry(path: &Path, name: &str) -> Result<(), ActionErrorKind> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let buf = read_if_exists(path).await?;
+    let kept = buf
+        .lines()
+        .filter(|line| entry_name(line) != Some(name))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    if kept != buf {
+        replace_contents(path, &kept).await?;
+    }
+    Ok(())
+}
+
+/// Atomically replace the contents of `path`, preserving its mode
+async fn replace_contents(path: &Path, buf: &str) -> Result<(), ActionErrorKind> {
+    let mode = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.permissions().mode(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0o644,
+        Err(e) => return Err(ActionErrorKind::GettingMetadata(path.to_path_buf(), e)),
+    };
+
+    let temp_path = {
+        let mut temp_path = PathBuf::from(path);
+        temp_path.set_file_name(format!(
+            ".{}.nix-installer-tmp.{}",
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            rand::thread_rng().gen::<u32>()
+        ));
+        temp_path
+    };
+
+    tokio::fs::write(&temp_path, buf)
+        .await
+        .map_err(|e| ActionErrorKind::Write(temp_path.clone(), e))?;
+    tokio::fs::set_permissions(&temp_path, PermissionsExt::from_mode(mode))
+        .await
+        .map_err(|e| ActionErrorKind::SetPermissions(mode, temp_path.clone(), e))?;
+    tokio::fs::rename(&temp_path, path)
+        .await
+        .map_err(|e| ActionErrorKind::Rename(temp_path, path.to_path_buf(), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn adds_and_removes_groups() -> eyre::Result<()> {
+        let root = tempfile::tempdir()?;
+        tokio::fs::create_dir(root.path().join("etc")).await?;
+        let existing = "root:x:0:\nwheel:x:10:alice,bob\n";
+        tokio::fs::write(root.path().join("etc/group"), existing).await?;
+
+        assert_eq!(
+            find_group(root.path(), "wheel").await?,
+            Some(GroupEntry {
+                name: "wheel".into(),
+                gid: 10,
+                members: vec!["alice".into(), "bob".into()],
+            })
+        );
+        assert_eq!(find_group(root.path(), "nixbld").await?, None);
+
+        add_group(root.path(), "nixbld", 30_000).await?;
+        assert_eq!(
+            find_group(root.path(), "nixbld")
+                .await?
+                .map(|entry| entry.gid),
+            Some(30_000)
+        );
+        // No `gshadow` existed, so none should be made
+        assert!(!root.path().join("etc/gshadow").exists());
+
+        set_group_gid(root.path(), "nixbld", 30_001).await?;
+        assert_eq!(
+            find_group(root.path(), "nixbld")
+                .await?
+                .map(|entry| entry.gid),
+            Some(30_001)
+        );
+
+        remove_group(root.path(), "nixbld").await?;
+        assert_eq!(
+            tokio::fs::read_to_string(root.path().join("etc/group")).await?,
+            existing
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn removes_users() -> eyre::Result<()> {
+        let root = tempfile::tempdir()?;
+        tokio::fs::create_dir(root.path().join("etc")).await?;
+        tokio::fs::write(
+            root.path().join("etc/passwd"),
+            "root:x:0:0::/root:/bin/sh\nnixbld1:x:30001:30000::/var/empty:/sbin/nologin\n",
+        )
+        .await?;
+
+        assert!(user_exists(root.path(), "nixbld1").await?);
+        assert_eq!(find_user_uid(root.path(), "nixbld1").await?, Some(30001));
+        assert_eq!(find_user_uid(root.path(), "nixbld2").await?, None);
+        remove_user(root.path(), "nixbld1").await?;
+        assert!(!user_exists(root.path(), "nixbld1").await?);
+        assert!(user_exists(root.path(), "root").await?);
+
+        Ok(())
+    }
+}
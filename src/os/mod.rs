@@ -1 +1,2 @@
 pub mod darwin;
+pub(crate) mod etc_files;
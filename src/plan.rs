@@ -1,14 +1,26 @@
 use std::{path::PathBuf, str::FromStr};
 
 use crate::{
-    action::{Action, ActionDescription, StatefulAction},
+    action::{
+        Action, ActionDependency, ActionDescription, ActionDrift, ActionError, ActionState,
+        ActionTag, CancellationToken, Journal, StatefulAction,
+    },
     planner::{BuiltinPlanner, Planner},
+    progress::{Progress, ProgressEvent},
+    schedule::Schedule,
+    settings::rooted,
     NixInstallerError,
 };
 use owo_colors::OwoColorize;
-use semver::{Version, VersionReq};
-use serde::{de::Error, Deserialize, Deserializer};
-use tokio::sync::broadcast::Receiver;
+use semver::Version;
+use serde::de::Error;
+use tokio::{
+    sync::{
+        broadcast::Receiver,
+        mpsc::{UnboundedReceiver, UnboundedSender},
+    },
+    task::{JoinHandle, JoinSet},
+};
 
 pub const RECEIPT_LOCATION: &str = "/nix/receipt.json";
 
@@ -17,8 +29,8 @@ A set of [`Action`]s, along with some metadata, which can be carried out to driv
 revert
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+#[serde(try_from = "serde_json::Value")]
 pub struct InstallPlan {
-    #[serde(deserialize_with = "ensure_version")]
     pub(crate) version: Version,
 
     pub(crate) actions: Vec<StatefulAction<Box<dyn Action>>>,
@@ -27,6 +39,13 @@ pub struct InstallPlan {
 
     #[cfg(feature = "diagnostics")]
     pub(crate) diagnostic_data: Option<crate::diagnostics::DiagnosticData>,
+
+    #[serde(skip)]
+    pub(crate) progress: Option<UnboundedSender<ProgressEvent>>,
+
+    /// Where the receipt was loaded from, see [`InstallPlan::from_receipt`]
+    #[serde(skip)]
+    pub(crate) loaded_from: Option<PathBuf>,
 }
 
 impl InstallPlan {
@@ -45,6 +64,8 @@ impl InstallPlan {
             version: current_version()?,
             #[cfg(feature = "diagnostics")]
             diagnostic_data,
+            progress: None,
+            loaded_from: None,
         })
     }
 
@@ -62,8 +83,155 @@ impl InstallPlan {
             version: current_version()?,
             #[cfg(feature = "diagnostics")]
             diagnostic_data,
+            progress: None,
+            loaded_from: None,
         })
     }
+
+    /// If no action of the plan is applied to the system, eg. after a successful [`uninstall`](InstallPlan::uninstall)
+    pub fn uninstalled(&self) -> bool {
+        self.actions
+            .iter()
+            .all(|v| matches!(v.state, ActionState::Uncompleted | ActionState::Skipped))
+    }
+
+    /// Load the receipt recorded at `location`, which is where the plan's receipt is then recorded
+    ///
+    /// If an uninstall was interrupted after removing the receipt's directory, the receipt is loaded from where it was
+    /// journaled instead, see [`journal::fallback_location`](crate::journal::fallback_location).
+    pub async fn from_receipt(location: impl Into<PathBuf>) -> Result<Self, NixInstallerError> {
+        let location = location.into();
+        let buf = match tokio::fs::read_to_string(&location).await {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let fallback = crate::journal::fallback_location(&location);
+                match tokio::fs::read_to_string(&fallback).await {
+                    Ok(buf) => {
+                        tracing::debug!(
+                            "Resuming from the receipt journaled to `{}`",
+                            fallback.display()
+                        );
+                        buf
+                    },
+                    Err(_) => return Err(NixInstallerError::ReadingReceipt(location, err)),
+                }
+            },
+            Err(err) => return Err(NixInstallerError::ReadingReceipt(location, err)),
+        };
+        let mut plan: Self = serde_json::from_str(&buf)?;
+        plan.loaded_from = Some(location);
+        Ok(plan)
+    }
+
+    /// Where the receipt of this plan is recorded, where it was [loaded from](InstallPlan::from_receipt), otherwise
+    /// inside of the [`Planner::root`], if any
+    pub fn receipt_location(&self) -> PathBuf {
+        match &self.loaded_from {
+            Some(location) => location.clone(),
+            None => rooted(self.planner.root().as_deref(), RECEIPT_LOCATION),
+        }
+    }
+
+    /// The [`nix_package_cache_dir`](crate::settings::CommonSettings::nix_package_cache_dir) of the plan, if any
+    ///
+    /// The cache outlives the install, so it is left alone by [`uninstall`](InstallPlan::uninstall).
+    pub fn nix_package_cache_dir(&self) -> Option<PathBuf> {
+        let settings = self.planner.settings().ok()?;
+        serde_json::from_value(settings.get("nix_package_cache_dir")?.clone()).ok()?
+    }
+
+    /// The entries of the [`nix_package_cache_dir`](InstallPlan::nix_package_cache_dir) the Nix package (and its
+    /// signature) were kept in by the install
+    fn nix_package_cache_entries(&self) -> Vec<PathBuf> {
+        // They are recorded by `FetchAndUnpackNix`, which is nested inside other actions
+        fn find(value: &serde_json::Value, entries: &mut Vec<PathBuf>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    if let (Some(_), Some(cached)) = (map.get("cache_dir"), map.get("cached")) {
+                        if let Ok(cached) = serde_json::from_value::<Vec<PathBuf>>(cached.clone()) {
+                            entries.extend(cached);
+                        }
+                    }
+                    map.values().for_each(|value| find(value, entries));
+                },
+                serde_json::Value::Array(values) => {
+                    values.iter().for_each(|value| find(value, entries))
+                },
+                _ => (),
+            }
+        }
+
+        let mut entries = vec![];
+        if let Ok(actions) = serde_json::to_value(&self.actions) {
+            find(&actions, &mut entries);
+        }
+        entries
+    }
+
+    /// Remove the Nix package (and its signature) the install kept in the
+    /// [`nix_package_cache_dir`](InstallPlan::nix_package_cache_dir), then the directory if nothing else is left in it
+    pub async fn purge_nix_package_cache(&self) -> Result<(), NixInstallerError> {
+        let Some(cache_dir) = self.nix_package_cache_dir() else {
+            return Ok(());
+        };
+        for entry in self.nix_package_cache_entries() {
+            // Nothing outside the cache directory is the cache's
+            if entry.parent() != Some(cache_dir.as_path()) {
+                continue;
+            }
+            match tokio::fs::remove_file(&entry).await {
+                Ok(()) => tracing::debug!("Removed `{}` from the cache", entry.display()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+                Err(err) => return Err(NixInstallerError::PurgingCache(entry, err)),
+            }
+        }
+
+        let mut remaining = match tokio::fs::read_dir(&cache_dir).await {
+            Ok(remaining) => remaining,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(NixInstallerError::PurgingCache(cache_dir, err)),
+        };
+        match remaining.next_entry().await {
+            Ok(None) => tokio::fs::remove_dir(&cache_dir)
+                .await
+                .map_err(|e| NixInstallerError::PurgingCache(cache_dir, e)),
+            Ok(Some(_)) => {
+                tracing::debug!(
+                    "Leaving the cache `{}`, as it has more in it",
+                    cache_dir.display()
+                );
+                Ok(())
+            },
+            Err(err) => Err(NixInstallerError::PurgingCache(cache_dir, err)),
+        }
+    }
+
+    /// Which of the actions depend on each other, see [`Action::dependencies`]
+    ///
+    /// Every running action journals into the directory of the receipt, so an action which creates or removes that
+    /// directory (eg. `/nix`) runs on its own.
+    fn schedule(&self) -> Schedule {
+        let receipt_location = self.receipt_location();
+        Schedule::new(self.actions.iter().map(|action| {
+            let dependencies = action.dependencies().filter(|dependencies| {
+                !dependencies.iter().any(|dependency| match dependency {
+                    ActionDependency::Path(path) => receipt_location.starts_with(path),
+                    _ => false,
+                })
+            });
+            (ActionTag::from(action.inner_typetag_name()), dependencies)
+        }))
+    }
+
+    /// Observe the progress of the next [`install`](InstallPlan::install) or [`uninstall`](InstallPlan::uninstall)
+    ///
+    /// The receiver finishes once that install or uninstall returns, and only receives the events of this plan.
+    pub fn progress(&mut self) -> UnboundedReceiver<ProgressEvent> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.progress = Some(sender);
+        receiver
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn describe_install(&self, explain: bool) -> Result<String, NixInstallerError> {
         let Self {
@@ -139,64 +307,137 @@ impl InstallPlan {
         Ok(buf)
     }
 
+    /// Execute the plan, resuming from the first action which has not [`Completed`](ActionState::Completed)
+    ///
+    /// The receipt is journaled after every action transition, see [`ActionState::Progress`] for how interrupted
+    /// actions are handled.
+    ///
+    /// Anything sent on `cancel_channel` cancels the install, including any actions currently executing, see
+    /// [`Action::execute`].
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn install(
         &mut self,
         cancel_channel: impl Into<Option<Receiver<()>>>,
     ) -> Result<(), NixInstallerError> {
-        let Self { actions, .. } = self;
-        let mut cancel_channel = cancel_channel.into();
-
-        // This is **deliberately sequential**.
-        // Actions which are parallelizable are represented by "group actions" like CreateUsers
-        // The plan itself represents the concept of the sequence of stages.
-        for action in actions {
-            if let Some(ref mut cancel_channel) = cancel_channel {
-                if cancel_channel.try_recv()
-                    != Err(tokio::sync::broadcast::error::TryRecvError::Empty)
-                {
-                    if let Err(err) = write_receipt(self.clone()).await {
-                        tracing::error!("Error saving receipt: {:?}", err);
-                    }
+        let cancel = CancellationToken::new();
+        let forwarding = forward_cancellation(cancel_channel.into(), &cancel);
 
-                    #[cfg(feature = "diagnostics")]
-                    if let Some(diagnostic_data) = &self.diagnostic_data {
-                        diagnostic_data
-                            .clone()
-                            .send(
-                                crate::diagnostics::DiagnosticAction::Install,
-                                crate::diagnostics::DiagnosticStatus::Cancelled,
-                            )
-                            .await?;
-                    }
+        // Persist the receipt before anything is done, then journal every transition, so even if the process is
+        // killed part way through the receipt records what was done.
+        let journal =
+            Journal::begin(self.receipt_location(), None, serde_json::to_value(&*self)?).await;
+        let progress = Progress::new(self.progress.take());
+        let res = self.install_journaled(&cancel, &journal, &progress).await;
+        if let Some(forwarding) = forwarding {
+            forwarding.abort();
+        }
+        res
+    }
 
-                    return Err(NixInstallerError::Cancelled);
-                }
-            }
+    async fn install_journaled(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), NixInstallerError> {
+        // Actions run in order, except that those which do not depend on each other run concurrently
+        let schedule = self.schedule();
+        let mut started = vec![false; self.actions.len()];
+        let mut finished = vec![false; self.actions.len()];
+        // If an action fails, the others running alongside it are cancelled
+        let siblings = cancel.child_token();
+        let retry_policy = self.planner.retry_policy();
+        let mut running = JoinSet::new();
+        let mut failure: Option<ActionError> = None;
+
+        loop {
+            while failure.is_none() && !siblings.is_cancelled() {
+                let Some(index) = schedule.next(&started, &finished) else {
+                    break;
+                };
+                started[index] = true;
 
-            tracing::info!("Step: {}", action.tracing_synopsis());
-            if let Err(err) = action.try_execute().await {
-                if let Err(err) = write_receipt(self.clone()).await {
-                    tracing::error!("Error saving receipt: {:?}", err);
+                let action = &self.actions[index];
+                if action.state == ActionState::Completed {
+                    tracing::debug!("Step: (Already done) {}", action.tracing_synopsis());
+                    progress.emit(ProgressEvent::Skipped(progress.action(
+                        action.inner_typetag_name().into(),
+                        action.tracing_synopsis(),
+                    )));
+                    finished[index] = true;
+                    continue;
                 }
-                let err = NixInstallerError::Action(err);
-                #[cfg(feature = "diagnostics")]
-                if let Some(diagnostic_data) = &self.diagnostic_data {
-                    diagnostic_data
-                        .clone()
-                        .failure(&err)
-                        .send(
-                            crate::diagnostics::DiagnosticAction::Install,
-                            crate::diagnostics::DiagnosticStatus::Failure,
-                        )
-                        .await?;
+                if action.state == ActionState::Progress {
+                    tracing::debug!("Step: (Resuming) {}", action.tracing_synopsis());
+                } else {
+                    tracing::debug!("Step: {}", action.tracing_synopsis());
                 }
 
-                return Err(err);
+                let mut action = action.clone();
+                let cancel = siblings.clone();
+                let journal = journal.action(index);
+                let progress = progress.clone();
+                let retry_policy = retry_policy.clone();
+                running.spawn(async move {
+                    let res = action
+                        .try_execute_retrying(&cancel, &journal, &progress, &retry_policy)
+                        .await;
+                    (index, action, res)
+                });
             }
+
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (index, action, res) = match joined {
+                Ok(joined) => joined,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            };
+            self.actions[index] = action;
+            match res {
+                Ok(()) => finished[index] = true,
+                Err(err) => {
+                    // Prefer reporting a failure over the cancellation of the actions running alongside it
+                    if failure.as_ref().is_none_or(ActionError::is_cancelled) {
+                        failure = Some(err);
+                    }
+                    siblings.cancel();
+                },
+            }
+
+            // Running actions are journaled as they go, so only checkpoint when none are running
+            if running.is_empty() {
+                journal.checkpoint(serde_json::to_value(&*self)?).await;
+            }
+        }
+
+        if let Some(err) = failure {
+            if err.is_cancelled() {
+                return self.install_cancelled(journal).await;
+            }
+            if let Err(err) = write_receipt(self.clone(), journal).await {
+                tracing::error!("Error saving receipt: {:?}", err);
+            }
+            let err = NixInstallerError::Action(err);
+            #[cfg(feature = "diagnostics")]
+            if let Some(diagnostic_data) = &self.diagnostic_data {
+                diagnostic_data
+                    .clone()
+                    .failure(&err)
+                    .send(
+                        crate::diagnostics::DiagnosticAction::Install,
+                        crate::diagnostics::DiagnosticStatus::Failure,
+                    )
+                    .await?;
+            }
+
+            return Err(err);
+        }
+        if finished.contains(&false) {
+            return self.install_cancelled(journal).await;
         }
 
-        write_receipt(self.clone()).await?;
+        write_receipt(self.clone(), journal).await?;
         #[cfg(feature = "diagnostics")]
         if let Some(diagnostic_data) = &self.diagnostic_data {
             diagnostic_data
@@ -211,6 +452,26 @@ impl InstallPlan {
         Ok(())
     }
 
+    /// Record how far a cancelled install got, which may be part way through an action
+    async fn install_cancelled(&self, journal: &Journal) -> Result<(), NixInstallerError> {
+        if let Err(err) = write_receipt(self.clone(), journal).await {
+            tracing::error!("Error saving receipt: {:?}", err);
+        }
+
+        #[cfg(feature = "diagnostics")]
+        if let Some(diagnostic_data) = &self.diagnostic_data {
+            diagnostic_data
+                .clone()
+                .send(
+                    crate::diagnostics::DiagnosticAction::Install,
+                    crate::diagnostics::DiagnosticStatus::Cancelled,
+                )
+                .await?;
+        }
+
+        Err(NixInstallerError::Cancelled)
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn describe_uninstall(&self, explain: bool) -> Result<String, NixInstallerError> {
         let Self {
@@ -288,47 +549,128 @@ impl InstallPlan {
         Ok(buf)
     }
 
+    /// Revert the plan, skipping any actions which are already [`Uncompleted`](ActionState::Uncompleted)
+    ///
+    /// Like [`install`](InstallPlan::install), the receipt is journaled after every action transition, so an
+    /// interrupted or partially failed uninstall can be run again and will only revert what remains. A fully
+    /// successful uninstall leaves (if the receipt's directory still exists) a receipt where every action is
+    /// [`Uncompleted`](ActionState::Uncompleted), see [`InstallPlan::uninstalled`].
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn uninstall(
         &mut self,
         cancel_channel: impl Into<Option<Receiver<()>>>,
     ) -> Result<(), NixInstallerError> {
-        let Self { actions, .. } = self;
-        let mut cancel_channel = cancel_channel.into();
+        let cancel = CancellationToken::new();
+        let forwarding = forward_cancellation(cancel_channel.into(), &cancel);
+
+        // Removing the receipt's directory (eg. `/nix`) would lose the journal, so it moves outside of it
+        let location = self.receipt_location();
+        let fallback = crate::journal::fallback_location(&location);
+        let journal = Journal::begin(
+            location.clone(),
+            Some(fallback.clone()),
+            serde_json::to_value(&*self)?,
+        )
+        .await;
+        let progress = Progress::new(self.progress.take());
+        let res = self.uninstall_journaled(&cancel, &journal, &progress).await;
+        if let Some(forwarding) = forwarding {
+            forwarding.abort();
+        }
+        match &res {
+            // Nothing is left to resume
+            Ok(()) => match tokio::fs::remove_file(&fallback).await {
+                Ok(()) => (),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+                Err(err) => tracing::warn!(
+                    "Could not remove the journaled receipt `{}`: {err}",
+                    fallback.display()
+                ),
+            },
+            Err(_) if fallback.exists() => tracing::warn!(
+                "The receipt was journaled to `{}` once `{}` was removed, uninstall `{}` again to resume",
+                fallback.display(),
+                location.parent().unwrap_or(&location).display(),
+                location.display(),
+            ),
+            Err(_) => (),
+        }
+        res
+    }
+
+    async fn uninstall_journaled(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+        progress: &Progress,
+    ) -> Result<(), NixInstallerError> {
         let mut errors = vec![];
 
-        // This is **deliberately sequential**.
-        // Actions which are parallelizable are represented by "group actions" like CreateUsers
-        // The plan itself represents the concept of the sequence of stages.
-        for action in actions.iter_mut().rev() {
-            if let Some(ref mut cancel_channel) = cancel_channel {
-                if cancel_channel.try_recv()
-                    != Err(tokio::sync::broadcast::error::TryRecvError::Empty)
-                {
-                    if let Err(err) = write_receipt(self.clone()).await {
-                        tracing::error!("Error saving receipt: {:?}", err);
-                    }
+        // Actions are reverted in reverse, except that those which do not depend on each other are reverted
+        // concurrently. Unlike during install, a failure does not stop the others from being reverted.
+        let schedule = self.schedule();
+        let mut started = vec![false; self.actions.len()];
+        let mut finished = vec![false; self.actions.len()];
+        let mut running = JoinSet::new();
+        let mut cancelled = false;
 
-                    #[cfg(feature = "diagnostics")]
-                    if let Some(diagnostic_data) = &self.diagnostic_data {
-                        diagnostic_data
-                            .clone()
-                            .send(
-                                crate::diagnostics::DiagnosticAction::Uninstall,
-                                crate::diagnostics::DiagnosticStatus::Cancelled,
-                            )
-                            .await?;
-                    }
-                    return Err(NixInstallerError::Cancelled);
+        loop {
+            while !cancel.is_cancelled() {
+                let Some(index) = schedule.next_reverse(&started, &finished) else {
+                    break;
+                };
+                started[index] = true;
+
+                let action = &self.actions[index];
+                match action.state {
+                    ActionState::Uncompleted | ActionState::Skipped => {
+                        tracing::debug!("Revert: (Already done) {}", action.tracing_synopsis());
+                        finished[index] = true;
+                        continue;
+                    },
+                    ActionState::Progress => {
+                        tracing::debug!("Revert: (Resuming) {}", action.tracing_synopsis())
+                    },
+                    ActionState::Completed => {
+                        tracing::debug!("Revert: {}", action.tracing_synopsis())
+                    },
                 }
+
+                let mut action = action.clone();
+                let cancel = cancel.clone();
+                let journal = journal.action(index);
+                let progress = progress.clone();
+                running.spawn(async move {
+                    let res = action.try_revert(&cancel, &journal, &progress).await;
+                    (index, action, res)
+                });
+            }
+
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (index, action, res) = match joined {
+                Ok(joined) => joined,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            };
+            self.actions[index] = action;
+            finished[index] = true;
+            match res {
+                Err(err) if err.is_cancelled() => cancelled = true,
+                Err(err) => errors.push(err),
+                Ok(()) => (),
             }
 
-            tracing::info!("Revert: {}", action.tracing_synopsis());
-            if let Err(errs) = action.try_revert().await {
-                errors.push(errs);
+            // Running actions are journaled as they go, so only checkpoint when none are running
+            if running.is_empty() {
+                journal.checkpoint(serde_json::to_value(&*self)?).await;
             }
         }
 
+        if cancelled || finished.contains(&false) {
+            return self.uninstall_cancelled(journal).await;
+        }
+
         if errors.is_empty() {
             #[cfg(feature = "diagnostics")]
             if let Some(diagnostic_data) = &self.diagnostic_data {
@@ -356,47 +698,176 @@ impl InstallPlan {
                     .await?;
             }
 
-            return Err(error);
+            Err(error)
+        }
+    }
+
+    /// Record how far a cancelled uninstall got, which may be part way through reverting an action
+    async fn uninstall_cancelled(&self, journal: &Journal) -> Result<(), NixInstallerError> {
+        // Unlike during install, the receipt directory is not recreated if it was removed
+        journal.checkpoint(serde_json::to_value(self)?).await;
+
+        #[cfg(feature = "diagnostics")]
+        if let Some(diagnostic_data) = &self.diagnostic_data {
+            diagnostic_data
+                .clone()
+                .send(
+                    crate::diagnostics::DiagnosticAction::Uninstall,
+                    crate::diagnostics::DiagnosticStatus::Cancelled,
+                )
+                .await?;
+        }
+        Err(NixInstallerError::Cancelled)
+    }
+
+    /// Check that the effects of all [`Completed`](ActionState::Completed) actions still hold, returning each
+    /// way the system has drifted from what was installed
+    ///
+    /// Nothing is changed, see [`Action::verify`].
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn verify(&self) -> Result<Vec<ActionDrift>, NixInstallerError> {
+        let mut drift = vec![];
+        for action in &self.actions {
+            drift.extend(
+                action
+                    .try_verify()
+                    .await
+                    .map_err(NixInstallerError::Action)?,
+            );
+        }
+        Ok(drift)
+    }
+
+    /// Re-apply every [`Completed`](ActionState::Completed) action which has [drifted](InstallPlan::verify),
+    /// returning each way the system had drifted
+    ///
+    /// Like [`install`](InstallPlan::install), the receipt is journaled after every action transition, see
+    /// [`Action::repair`].
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn repair(
+        &mut self,
+        cancel_channel: impl Into<Option<Receiver<()>>>,
+    ) -> Result<Vec<ActionDrift>, NixInstallerError> {
+        let cancel = CancellationToken::new();
+        let forwarding = forward_cancellation(cancel_channel.into(), &cancel);
+
+        let journal =
+            Journal::begin(self.receipt_location(), None, serde_json::to_value(&*self)?).await;
+        let res = self.repair_journaled(&cancel, &journal).await;
+        if let Some(forwarding) = forwarding {
+            forwarding.abort();
+        }
+        res
+    }
+
+    async fn repair_journaled(
+        &mut self,
+        cancel: &CancellationToken,
+        journal: &Journal,
+    ) -> Result<Vec<ActionDrift>, NixInstallerError> {
+        let mut repaired = vec![];
+        for index in 0..self.actions.len() {
+            if cancel.is_cancelled() {
+                journal.checkpoint(serde_json::to_value(&*self)?).await;
+                return Err(NixInstallerError::Cancelled);
+            }
+
+            let action = &mut self.actions[index];
+            // Progress is only observed during install and uninstall
+            let res = action
+                .try_repair(cancel, &journal.action(index), &Progress::default())
+                .await;
+            journal.checkpoint(serde_json::to_value(&*self)?).await;
+            match res {
+                Ok(drift) => {
+                    if !drift.is_empty() {
+                        tracing::info!("Repaired: {}", self.actions[index].tracing_synopsis());
+                    }
+                    repaired.extend(drift)
+                },
+                Err(err) if err.is_cancelled() => return Err(NixInstallerError::Cancelled),
+                Err(err) => return Err(NixInstallerError::Action(err)),
+            }
         }
+
+        Ok(repaired)
     }
 }
 
-async fn write_receipt(plan: InstallPlan) -> Result<(), NixInstallerError> {
-    tokio::fs::create_dir_all("/nix")
-        .await
-        .map_err(|e| NixInstallerError::RecordingReceipt(PathBuf::from("/nix"), e))?;
-    let install_receipt_path = PathBuf::from(RECEIPT_LOCATION);
+/// Cancel `cancel` once anything is sent on `cancel_channel` (or every sender is dropped), until the returned
+/// task is aborted
+fn forward_cancellation(
+    cancel_channel: Option<Receiver<()>>,
+    cancel: &CancellationToken,
+) -> Option<JoinHandle<()>> {
+    let mut cancel_channel = cancel_channel?;
+    let cancel = cancel.clone();
+    Some(tokio::spawn(async move {
+        let _ = cancel_channel.recv().await;
+        tracing::debug!("Cancelling");
+        cancel.cancel();
+    }))
+}
+
+async fn write_receipt(plan: InstallPlan, journal: &Journal) -> Result<(), NixInstallerError> {
+    let install_receipt_path = plan.receipt_location();
+    if let Some(receipt_dir) = install_receipt_path.parent() {
+        tokio::fs::create_dir_all(receipt_dir)
+            .await
+            .map_err(|e| NixInstallerError::RecordingReceipt(receipt_dir.to_path_buf(), e))?;
+    }
     let self_json =
         serde_json::to_string_pretty(&plan).map_err(NixInstallerError::SerializingReceipt)?;
-    tokio::fs::write(&install_receipt_path, format!("{self_json}\n"))
+    crate::journal::write_atomically(&install_receipt_path, format!("{self_json}\n").as_bytes())
         .await
         .map_err(|e| NixInstallerError::RecordingReceipt(install_receipt_path, e))?;
+    // Keep any journal in sync with what was just written
+    journal.replace(serde_json::to_value(&plan)?).await;
     Result::<(), NixInstallerError>::Ok(())
 }
 
-fn current_version() -> Result<Version, semver::Error> {
+pub(crate) fn current_version() -> Result<Version, semver::Error> {
     let nix_installer_version_str = env!("CARGO_PKG_VERSION");
     Version::from_str(nix_installer_version_str)
 }
 
-fn ensure_version<'de, D: Deserializer<'de>>(d: D) -> Result<Version, D::Error> {
-    let plan_version = Version::deserialize(d)?;
-    let req = VersionReq::parse(&plan_version.to_string()).map_err(|_e| {
-        D::Error::custom(&format!(
-            "Could not parse version `{plan_version}` as a version requirement, please report this",
-        ))
-    })?;
-    let nix_installer_version = current_version().map_err(|_e| {
-        D::Error::custom(&format!(
-            "Could not parse `nix-installer`'s version `{}` as a valid version according to Semantic Versioning, therefore the plan version ({plan_version}) compatibility cannot be checked", env!("CARGO_PKG_VERSION")
-        ))
-    })?;
-    if req.matches(&nix_installer_version) {
-        Ok(plan_version)
-    } else {
-        Err(D::Error::custom(&format!(
-            "This version of `nix-installer` ({nix_installer_version}) is not compatible with this plan's version ({plan_version}), check for a compatible version at `/nix/nix-installer` or download the matching release from https://github.com/DeterminateSystems/nix-installer/releases",
-        )))
+/// The fields of an [`InstallPlan`], once any [`migrations`](crate::migrations) have been applied
+#[derive(serde::Deserialize)]
+struct MigratedInstallPlan {
+    version: Version,
+    actions: Vec<StatefulAction<Box<dyn Action>>>,
+    planner: Box<dyn Planner>,
+    #[cfg(feature = "diagnostics")]
+    diagnostic_data: Option<crate::diagnostics::DiagnosticData>,
+}
+
+impl TryFrom<serde_json::Value> for InstallPlan {
+    type Error = serde_json::Error;
+
+    fn try_from(mut value: serde_json::Value) -> Result<Self, Self::Error> {
+        let current = current_version().map_err(|_e| {
+            serde_json::Error::custom(format!(
+                "Could not parse `nix-installer`'s version `{}` as a valid version according to Semantic Versioning, therefore the plan version compatibility cannot be checked", env!("CARGO_PKG_VERSION")
+            ))
+        })?;
+        crate::migrations::migrate(&mut value, &current).map_err(serde_json::Error::custom)?;
+
+        let MigratedInstallPlan {
+            version,
+            actions,
+            planner,
+            #[cfg(feature = "diagnostics")]
+            diagnostic_data,
+        } = serde_json::from_value(value)?;
+        Ok(Self {
+            version,
+            actions,
+            planner,
+            #[cfg(feature = "diagnostics")]
+            diagnostic_data,
+            progress: None,
+            loaded_from: None,
+        })
     }
 }
 
@@ -404,7 +875,16 @@ fn ensure_version<'de, D: Deserializer<'de>>(d: D) -> Result<Version, D::Error>
 mod test {
     use semver::Version;
 
-    use crate::{planner::BuiltinPlanner, InstallPlan, NixInstallerError};
+    use crate::{
+        action::{
+            base::{CreateDirectory, FetchAndUnpackNix, FetchNixOptions},
+            ActionState, CancellationToken, Journal, Progress, StatefulAction,
+        },
+        planner::BuiltinPlanner,
+        progress::{ActionProgress, ProgressEvent},
+        settings::CommonSettings,
+        InstallPlan, NixInstallerError,
+    };
 
     #[tokio::test]
     async fn ensure_version_allows_compatible() -> Result<(), NixInstallerError> {
@@ -435,4 +915,197 @@ mod test {
         assert!(err.is_data());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn install_and_uninstall_resume_from_receipt() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut settings = CommonSettings::default().await?;
+        settings.root = Some(temp_dir.path().to_path_buf());
+        let planner = BuiltinPlanner::from_common_settings(settings).await?;
+
+        let already_done = temp_dir.path().join("already_done");
+        let not_done = temp_dir.path().join("not_done");
+        let mut plan = InstallPlan {
+            version: Version::parse(env!("CARGO_PKG_VERSION"))?,
+            actions: vec![
+                StatefulAction::completed(
+                    CreateDirectory::plan(&already_done, None, None, None, false, None)
+                        .await?
+                        .action,
+                )
+                .boxed(),
+                CreateDirectory::plan(&not_done, None, None, None, false, None)
+                    .await?
+                    .boxed(),
+            ],
+            planner: planner.boxed(),
+            #[cfg(feature = "diagnostics")]
+            diagnostic_data: None,
+            progress: None,
+            loaded_from: None,
+        };
+
+        let mut progress = plan.progress();
+        plan.install(None).await?;
+
+        let mut events = vec![];
+        while let Some(event) = progress.recv().await {
+            events.push(event);
+        }
+        let not_done_progress = ActionProgress {
+            tag: "create_directory".into(),
+            path: vec![],
+            synopsis: plan.actions[1].tracing_synopsis(),
+        };
+        assert!(events.contains(&ProgressEvent::Started(not_done_progress.clone())));
+        assert!(events.contains(&ProgressEvent::Completed(not_done_progress)));
+        assert!(events.contains(&ProgressEvent::Skipped(ActionProgress {
+            tag: "create_directory".into(),
+            path: vec![],
+            synopsis: plan.actions[0].tracing_synopsis(),
+        })));
+
+        // Completed actions are not executed again
+        assert!(!already_done.exists());
+        assert!(not_done.exists());
+
+        let receipt: InstallPlan =
+            serde_json::from_str(&tokio::fs::read_to_string(plan.receipt_location()).await?)?;
+        assert!(receipt
+            .actions
+            .iter()
+            .all(|action| action.state == ActionState::Completed));
+
+        // Simulate an uninstall which was interrupted after reverting the last action, where the first action was
+        // completed by an earlier install
+        tokio::fs::create_dir(&already_done).await?;
+        tokio::fs::remove_dir(&not_done).await?;
+        plan.actions[1].state = ActionState::Uncompleted;
+
+        // Already reverted actions are not reverted again
+        plan.uninstall(None).await?;
+
+        let receipt: InstallPlan =
+            serde_json::from_str(&tokio::fs::read_to_string(plan.receipt_location()).await?)?;
+        assert!(receipt.uninstalled());
+        assert!(!already_done.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purges_only_what_was_cached() -> eyre::Result<()> {
+        use sha2::Digest;
+
+        let temp_dir = tempfile::tempdir()?;
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "nix-unpacked/README", &b"Nix"[..])?;
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        std::io::Write::write_all(&mut encoder, &tar.into_inner()?)?;
+        let tarball = encoder.finish()?;
+        let sha256 = format!("{:x}", sha2::Sha256::digest(&tarball));
+        let package = temp_dir.path().join("nix.tar.xz");
+        tokio::fs::write(&package, &tarball).await?;
+
+        let cache_dir = temp_dir.path().join("cache");
+        let mut fetch_nix = FetchAndUnpackNix::plan(
+            url::Url::from_file_path(&package).unwrap(),
+            temp_dir.path().join("scratch"),
+            FetchNixOptions {
+                sha256: Some(sha256.clone()),
+                cache_dir: Some(cache_dir.clone()),
+                ..Default::default()
+            },
+        )
+        .await?;
+        fetch_nix
+            .try_execute(
+                &CancellationToken::new(),
+                &Journal::default(),
+                &Progress::default(),
+            )
+            .await?;
+        assert!(cache_dir.join(&sha256).exists());
+        // Something else kept in the same directory
+        let unrelated = cache_dir.join("unrelated");
+        tokio::fs::write(&unrelated, "").await?;
+
+        let mut settings = CommonSettings::default().await?;
+        settings.nix_package_cache_dir = Some(cache_dir.clone());
+        let plan = InstallPlan {
+            version: Version::parse(env!("CARGO_PKG_VERSION"))?,
+            actions: vec![fetch_nix.boxed()],
+            planner: BuiltinPlanner::from_common_settings(settings)
+                .await?
+                .boxed(),
+            #[cfg(feature = "diagnostics")]
+            diagnostic_data: None,
+            progress: None,
+            loaded_from: None,
+        };
+
+        plan.purge_nix_package_cache().await?;
+        assert!(!cache_dir.join(&sha256).exists());
+        assert!(unrelated.exists());
+
+        tokio::fs::remove_file(&unrelated).await?;
+        plan.purge_nix_package_cache().await?;
+        assert!(!cache_dir.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resumes_uninstall_interrupted_after_removing_receipt_directory() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut settings = CommonSettings::default().await?;
+        settings.root = Some(temp_dir.path().to_path_buf());
+        let planner = BuiltinPlanner::from_common_settings(settings).await?;
+
+        let other = temp_dir.path().join("other");
+        let nix = temp_dir.path().join("nix");
+        let mut plan = InstallPlan {
+            version: Version::parse(env!("CARGO_PKG_VERSION"))?,
+            actions: vec![
+                CreateDirectory::plan(&other, None, None, None, false, None)
+                    .await?
+                    .boxed(),
+                CreateDirectory::plan(&nix, None, None, None, true, None)
+                    .await?
+                    .boxed(),
+            ],
+            planner: planner.boxed(),
+            #[cfg(feature = "diagnostics")]
+            diagnostic_data: None,
+            progress: None,
+            loaded_from: None,
+        };
+        plan.install(None).await?;
+        let location = plan.receipt_location();
+        assert!(location.exists());
+
+        // Reverting `other` fails, after `/nix` (and the receipt in it) was removed
+        tokio::fs::remove_dir(&other).await?;
+        tokio::fs::write(&other, "").await?;
+        let mut plan = InstallPlan::from_receipt(&location).await?;
+        assert!(plan.uninstall(None).await.is_err());
+        assert!(!nix.exists());
+        let fallback = crate::journal::fallback_location(&location);
+        assert_eq!(fallback, temp_dir.path().join("nix-receipt.json"));
+
+        // The uninstall resumes from the journaled receipt, only reverting what remains
+        tokio::fs::remove_file(&other).await?;
+        tokio::fs::create_dir(&other).await?;
+        let mut plan = InstallPlan::from_receipt(&location).await?;
+        assert_eq!(plan.receipt_location(), location);
+        assert_eq!(plan.actions[1].state, ActionState::Uncompleted);
+        assert_ne!(plan.actions[0].state, ActionState::Uncompleted);
+        plan.uninstall(None).await?;
+        assert!(!other.exists());
+        assert!(!nix.exists());
+        assert!(!fallback.exists());
+        Ok(())
+    }
 }
@@ -2,15 +2,18 @@ use crate::{
     action::{
         base::{CreateDirectory, RemoveDirectory},
         common::{ConfigureInitService, ConfigureNix, ProvisionNix},
-        StatefulAction,
+        RetryPolicy, StatefulAction,
     },
     error::HasExpectedErrors,
     planner::{Planner, PlannerError},
     settings::CommonSettings,
-    settings::{InitSettings, InitSystem, InstallSettingsError},
+    settings::{rooted, InitSettings, InitSystem, InstallSettingsError},
     Action, BuiltinPlanner,
 };
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 use tokio::process::Command;
 
 use super::ShellProfileLocations;
@@ -36,20 +39,23 @@ impl Planner for Linux {
     }
 
     async fn plan(&self) -> Result<Vec<StatefulAction<Box<dyn Action>>>, PlannerError> {
-        check_not_nixos()?;
+        check_not_nixos(self.settings.root.as_deref())?;
 
-        check_nix_not_already_installed().await?;
+        // The host is irrelevant when installing into an alternate root
+        if self.settings.root.is_none() {
+            check_nix_not_already_installed().await?;
 
-        check_not_wsl1()?;
+            check_not_wsl1()?;
 
-        check_not_selinux().await?;
+            check_not_selinux().await?;
 
-        if self.init.init == InitSystem::Systemd && self.init.start_daemon {
-            check_systemd_active()?;
+            if self.init.init == InitSystem::Systemd && self.init.start_daemon {
+                check_systemd_active()?;
+            }
         }
 
         Ok(vec![
-            CreateDirectory::plan("/nix", None, None, 0o0755, true)
+            CreateDirectory::plan(self.settings.rooted("/nix"), None, None, 0o0755, true, None)
                 .await
                 .map_err(PlannerError::Action)?
                 .boxed(),
@@ -65,11 +71,12 @@ impl Planner for Linux {
                 self.init.init,
                 self.init.start_daemon,
                 self.settings.ssl_cert_file.clone(),
+                self.settings.root.clone(),
             )
             .await
             .map_err(PlannerError::Action)?
             .boxed(),
-            RemoveDirectory::plan(crate::settings::SCRATCH_DIR)
+            RemoveDirectory::plan(self.settings.rooted(crate::settings::SCRATCH_DIR))
                 .await
                 .map_err(PlannerError::Action)?
                 .boxed(),
@@ -102,6 +109,14 @@ impl Planner for Linux {
         Ok(settings)
     }
 
+    fn root(&self) -> Option<PathBuf> {
+        self.settings.root.clone()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.settings.retry_policy()
+    }
+
     #[cfg(feature = "diagnostics")]
     async fn diagnostic_data(&self) -> Result<crate::diagnostics::DiagnosticData, PlannerError> {
         Ok(crate::diagnostics::DiagnosticData::new(
@@ -123,9 +138,9 @@ impl Into<BuiltinPlanner> for Linux {
 }
 
 // If on NixOS, running `nix_installer` is pointless
-fn check_not_nixos() -> Result<(), PlannerError> {
+fn check_not_nixos(root: Option<&Path>) -> Result<(), PlannerError> {
     // NixOS always sets up this file as part of setting up /etc itself: https://github.com/NixOS/nixpkgs/blob/bdd39e5757d858bd6ea58ed65b4a2e52c8ed11ca/nixos/modules/system/etc/setup-etc.pl#L145
-    if Path::new("/etc/NIXOS").exists() {
+    if rooted(root, "/etc/NIXOS").exists() {
         return Err(PlannerError::NixOs);
     }
     Ok(())
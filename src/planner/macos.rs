@@ -11,7 +11,7 @@ use crate::{
         base::RemoveDirectory,
         common::{ConfigureInitService, ConfigureNix, ProvisionNix},
         macos::CreateNixVolume,
-        StatefulAction,
+        RetryPolicy, StatefulAction,
     },
     execute_command,
     os::darwin::DiskUtilInfoOutput,
@@ -66,6 +66,7 @@ async fn default_root_disk() -> Result<String, PlannerError> {
         Command::new("/usr/sbin/diskutil")
             .args(["info", "-plist", "/"])
             .stdin(std::process::Stdio::null()),
+        None,
     )
     .await
     .unwrap()
@@ -89,6 +90,10 @@ impl Planner for Macos {
     }
 
     async fn plan(&self) -> Result<Vec<StatefulAction<Box<dyn Action>>>, PlannerError> {
+        if self.settings.root.is_some() {
+            return Err(PlannerError::RootUnsupported(self.typetag_name()));
+        }
+
         ensure_not_running_in_rosetta().await?;
 
         let root_disk = match &self.root_disk {
@@ -98,6 +103,7 @@ impl Planner for Macos {
                     Command::new("/usr/sbin/diskutil")
                         .args(["info", "-plist", "/"])
                         .stdin(std::process::Stdio::null()),
+                    None,
                 )
                 .await
                 .unwrap()
@@ -155,6 +161,7 @@ impl Planner for Macos {
                 InitSystem::Launchd,
                 true,
                 self.settings.ssl_cert_file.clone(),
+                None,
             )
             .await
             .map_err(PlannerError::Action)?
@@ -204,6 +211,10 @@ impl Planner for Macos {
         Ok(settings)
     }
 
+    fn retry_policy(&self) -> RetryPolicy {
+        self.settings.retry_policy()
+    }
+
     #[cfg(feature = "diagnostics")]
     async fn diagnostic_data(&self) -> Result<crate::diagnostics::DiagnosticData, PlannerError> {
         Ok(crate::diagnostics::DiagnosticData::new(
@@ -114,7 +114,7 @@ use std::{collections::HashMap, path::PathBuf, string::FromUtf8Error};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    action::{ActionError, StatefulAction},
+    action::{ActionError, RetryPolicy, StatefulAction},
     error::HasExpectedErrors,
     settings::{CommonSettings, InstallSettingsError},
     Action, InstallPlan, NixInstallerError,
@@ -136,6 +136,19 @@ pub trait Planner: std::fmt::Debug + Send + Sync + dyn_clone::DynClone {
     async fn configured_settings(&self)
         -> Result<HashMap<String, serde_json::Value>, PlannerError>;
 
+    /// The alternate root the [`InstallPlan`] installs into, if not `/`
+    ///
+    /// The receipt of the [`InstallPlan`] is written inside of this root.
+    fn root(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// How the actions of the [`InstallPlan`] are retried if they fail, unless they have their own
+    /// [`RetryPolicy`]
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::never()
+    }
+
     /// A boxed, type erased planner
     fn boxed(self) -> Box<dyn Planner>
     where
@@ -254,6 +267,17 @@ impl BuiltinPlanner {
         }
     }
 
+    pub fn root(&self) -> Option<PathBuf> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BuiltinPlanner::Linux(i) => i.root(),
+            #[cfg(target_os = "linux")]
+            BuiltinPlanner::SteamDeck(i) => i.root(),
+            #[cfg(target_os = "macos")]
+            BuiltinPlanner::Macos(i) => i.root(),
+        }
+    }
+
     pub fn settings(&self) -> Result<HashMap<String, serde_json::Value>, InstallSettingsError> {
         match self {
             #[cfg(target_os = "linux")]
@@ -386,6 +410,11 @@ pub enum PlannerError {
     NixExists,
     #[error("WSL1 is not supported, please upgrade to WSL2: https://learn.microsoft.com/en-us/windows/wsl/install#upgrade-version-from-wsl-1-to-wsl-2")]
     Wsl1,
+    /// The planner cannot install into an alternate root
+    #[error(
+        "The `{0}` planner does not support installing into an alternate root, unset `--root`"
+    )]
+    RootUnsupported(&'static str),
     #[cfg(feature = "diagnostics")]
     #[error(transparent)]
     Diagnostic(#[from] crate::diagnostics::DiagnosticError),
@@ -412,6 +441,7 @@ impl HasExpectedErrors for PlannerError {
             this @ PlannerError::NixOs => Some(Box::new(this)),
             this @ PlannerError::NixExists => Some(Box::new(this)),
             this @ PlannerError::Wsl1 => Some(Box::new(this)),
+            this @ PlannerError::RootUnsupported(_) => Some(Box::new(this)),
             #[cfg(feature = "diagnostics")]
             PlannerError::Diagnostic(diagnostic_error) => Some(Box::new(diagnostic_error)),
         }
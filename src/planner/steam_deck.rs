@@ -66,7 +66,7 @@ use crate::{
         base::{CreateDirectory, CreateFile, RemoveDirectory},
         common::{ConfigureInitService, ConfigureNix, ProvisionNix},
         linux::StartSystemdUnit,
-        Action, StatefulAction,
+        Action, RetryPolicy, StatefulAction,
     },
     planner::{Planner, PlannerError},
     settings::{CommonSettings, InitSystem, InstallSettingsError},
@@ -102,6 +102,10 @@ impl Planner for SteamDeck {
     }
 
     async fn plan(&self) -> Result<Vec<StatefulAction<Box<dyn Action>>>, PlannerError> {
+        if self.settings.root.is_some() {
+            return Err(PlannerError::RootUnsupported(self.typetag_name()));
+        }
+
         let persistence = &self.persistence;
         if !persistence.is_absolute() {
             return Err(PlannerError::Custom(Box::new(
@@ -224,7 +228,7 @@ impl Planner for SteamDeck {
         }
 
         Ok(vec![
-            CreateDirectory::plan(&persistence, None, None, 0o0755, true)
+            CreateDirectory::plan(&persistence, None, None, 0o0755, true, None)
                 .await
                 .map_err(PlannerError::Action)?
                 .boxed(),
@@ -248,6 +252,7 @@ impl Planner for SteamDeck {
                 InitSystem::Systemd,
                 true,
                 self.settings.ssl_cert_file.clone(),
+                None,
             )
             .await
             .map_err(PlannerError::Action)?
@@ -295,6 +300,10 @@ impl Planner for SteamDeck {
         Ok(settings)
     }
 
+    fn retry_policy(&self) -> RetryPolicy {
+        self.settings.retry_policy()
+    }
+
     #[cfg(feature = "diagnostics")]
     async fn diagnostic_data(&self) -> Result<crate::diagnostics::DiagnosticData, PlannerError> {
         Ok(crate::diagnostics::DiagnosticData::new(
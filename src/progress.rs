@@ -0,0 +1,147 @@
+/*! Structured progress of an [`InstallPlan`](crate::InstallPlan) being installed or uninstalled
+
+Use [`InstallPlan::progress`](crate::InstallPlan::progress) to receive [`ProgressEvent`]s during the next
+[`install`](crate::InstallPlan::install) or [`uninstall`](crate::InstallPlan::uninstall) of a plan:
+
+```rust,no_run
+use nix_installer::{InstallPlan, progress::ProgressEvent};
+
+# async fn progress() -> color_eyre::Result<()> {
+let mut plan = InstallPlan::default().await?;
+let mut progress = plan.progress();
+let observer = tokio::spawn(async move {
+    while let Some(event) = progress.recv().await {
+        if let ProgressEvent::Started(action) = event {
+            println!("{} (inside {:?}): {}", action.tag, action.path, action.synopsis);
+        }
+    }
+});
+plan.install(None).await?;
+observer.await?;
+#
+# Ok(())
+# }
+```
+
+Like the [`Journal`](crate::action::Journal), a [`Progress`] handle is passed down along with each action, so the
+events of each plan only ever reach its own observer. Each nested action is passed a handle which also records the
+actions it is nested inside of.
+*/
+
+use reqwest::Url;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::action::ActionTag;
+
+/// A handle to the observer of a running [`InstallPlan`](crate::InstallPlan), for the actions nested inside of the
+/// ones listed in its path
+///
+/// A [`Default`] handle sends events nowhere, such as when an action is run on its own.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    observer: Option<UnboundedSender<ProgressEvent>>,
+    /// The tags of the actions enclosing the action this handle is passed to, outermost first
+    path: Vec<ActionTag>,
+}
+
+/// An [`Action`](crate::action::Action) which made progress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionProgress {
+    pub tag: ActionTag,
+    /// The tags of the actions this action is nested inside of, outermost first (empty for actions of the plan itself)
+    pub path: Vec<ActionTag>,
+    /// The [`tracing_synopsis`](crate::action::Action::tracing_synopsis) of the action
+    pub synopsis: String,
+}
+
+/// Progress of an [`InstallPlan`](crate::InstallPlan), see [`InstallPlan::progress`](crate::InstallPlan::progress)
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// An action started executing
+    Started(ActionProgress),
+    /// An action finished executing
+    Completed(ActionProgress),
+    /// An action failed to execute, with a description of the error
+    Failed(ActionProgress, String),
+    /// An action was not executed, as it was already completed or was skipped during planning
+    Skipped(ActionProgress),
+    /// An action started reverting
+    RevertStarted(ActionProgress),
+    /// An action finished reverting
+    RevertFinished(ActionProgress),
+    /// An action failed to revert, with a description of the error
+    RevertFailed(ActionProgress, String),
+    /// Part of the Nix tarball was fetched, `total` is known if the server reported it
+    Download {
+        url: Url,
+        bytes: u64,
+        total: Option<u64>,
+    },
+}
+
+impl Progress {
+    /// Send events to `observer`, if there is one, until every handle is dropped
+    pub(crate) fn new(observer: Option<UnboundedSender<ProgressEvent>>) -> Self {
+        Self {
+            observer,
+            path: vec![],
+        }
+    }
+
+    /// Send `event` to the observer, if there is one
+    pub(crate) fn emit(&self, event: ProgressEvent) {
+        if let Some(observer) = &self.observer {
+            // The receiver being dropped should not fail the install
+            observer.send(event).ok();
+        }
+    }
+
+    /// Describe the action tagged `tag` at the nesting of this handle
+    pub(crate) fn action(&self, tag: ActionTag, synopsis: String) -> ActionProgress {
+        ActionProgress {
+            tag,
+            path: self.path.clone(),
+            synopsis,
+        }
+    }
+
+    /// A handle for the sub-actions of the action tagged `tag`
+    pub(crate) fn nested(&self, tag: ActionTag) -> Self {
+        let mut path = self.path.clone();
+        path.push(tag);
+        Self {
+            observer: self.observer.clone(),
+            path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn tracks_nesting() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let progress = Progress::new(Some(sender));
+        let inner = progress
+            .nested(ActionTag::from("outer"))
+            .nested(ActionTag::from("inner"));
+        inner.emit(ProgressEvent::Started(
+            inner.action(ActionTag::from("leaf"), "Leaf".into()),
+        ));
+        drop((progress, inner));
+
+        assert_eq!(
+            receiver.recv().await,
+            Some(ProgressEvent::Started(ActionProgress {
+                tag: ActionTag::from("leaf"),
+                path: vec![ActionTag::from("outer"), ActionTag::from("inner")],
+                synopsis: "Leaf".into(),
+            }))
+        );
+        // Once every handle is dropped the observer finishes
+        assert_eq!(receiver.recv().await, None);
+    }
+}
@@ -0,0 +1,131 @@
+/*! The order in which the actions of an [`InstallPlan`](crate::InstallPlan) can run
+
+Each action of a plan depends on the actions before it which it shares an [`ActionDependency`] with, or on every
+action before it if either does not declare its [`dependencies`](crate::action::Action::dependencies). This forms a
+graph (which is acyclic, as actions only depend on actions before them). Actions are executed once all of the actions
+they depend on have completed, and reverted once all of the actions depending on them have been reverted.
+*/
+
+use crate::action::{ActionDependency, ActionTag};
+
+pub(crate) struct Schedule {
+    /// The indexes of the actions each action depends on
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl Schedule {
+    /// Build the graph of the actions of a plan, given their tags and dependencies
+    pub(crate) fn new(
+        actions: impl IntoIterator<Item = (ActionTag, Option<Vec<ActionDependency>>)>,
+    ) -> Self {
+        let actions = actions.into_iter().collect::<Vec<_>>();
+        let dependencies = actions
+            .iter()
+            .enumerate()
+            .map(|(index, (_, action_dependencies))| {
+                (0..index)
+                    .filter(|&before| {
+                        let (before_tag, before_dependencies) = &actions[before];
+                        let (Some(action_dependencies), Some(before_dependencies)) =
+                            (action_dependencies, before_dependencies)
+                        else {
+                            return true;
+                        };
+                        action_dependencies.iter().any(|dependency| {
+                            *dependency == ActionDependency::Action(*before_tag)
+                                || before_dependencies
+                                    .iter()
+                                    .any(|before_dependency| dependency.overlaps(before_dependency))
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { dependencies }
+    }
+
+    /// The first action which has not `started` where every action it depends on has `finished`
+    pub(crate) fn next(&self, started: &[bool], finished: &[bool]) -> Option<usize> {
+        (0..self.dependencies.len()).find(|&index| {
+            !started[index]
+                && self.dependencies[index]
+                    .iter()
+                    .all(|&dependency| finished[dependency])
+        })
+    }
+
+    /// The last action which has not `started` where every action depending on it has `finished`, for reverting
+    pub(crate) fn next_reverse(&self, started: &[bool], finished: &[bool]) -> Option<usize> {
+        (0..self.dependencies.len()).rev().find(|&index| {
+            !started[index]
+                && self
+                    .dependencies
+                    .iter()
+                    .enumerate()
+                    .all(|(dependent, dependencies)| {
+                        !dependencies.contains(&index) || finished[dependent]
+                    })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Option<Vec<ActionDependency>> {
+        Some(paths.iter().map(ActionDependency::path).collect())
+    }
+
+    #[test]
+    fn runs_independent_actions_together() {
+        let schedule = Schedule::new([
+            (ActionTag::from("create_directory"), paths(&["/nix"])),
+            (ActionTag::from("create_file"), paths(&["/etc/profile"])),
+            (ActionTag::from("create_directory"), paths(&["/nix/store"])),
+            (ActionTag::from("create_group"), None),
+        ]);
+        let mut started = vec![false; 4];
+        let finished = vec![false; 4];
+
+        assert_eq!(schedule.next(&started, &finished), Some(0));
+        started[0] = true;
+        assert_eq!(schedule.next(&started, &finished), Some(1));
+        started[1] = true;
+        // `/nix/store` is inside of `/nix`, and undeclared dependencies wait for everything
+        assert_eq!(schedule.next(&started, &finished), None);
+        assert_eq!(
+            schedule.next(&started, &[true, false, false, false]),
+            Some(2)
+        );
+        started[2] = true;
+        assert_eq!(schedule.next(&started, &[true, false, true, false]), None);
+        assert_eq!(schedule.next(&started, &[true, true, true, false]), Some(3));
+    }
+
+    #[test]
+    fn depends_on_tags_and_reverts_in_reverse() {
+        let schedule = Schedule::new([
+            (ActionTag::from("provision_nix"), paths(&["/nix"])),
+            (
+                ActionTag::from("custom"),
+                Some(vec![ActionDependency::Action(ActionTag::from(
+                    "provision_nix",
+                ))]),
+            ),
+            (ActionTag::from("create_file"), paths(&["/etc/profile"])),
+        ]);
+        let mut started = vec![false; 3];
+
+        assert_eq!(schedule.next_reverse(&started, &[false; 3]), Some(2));
+        started[2] = true;
+        assert_eq!(schedule.next_reverse(&started, &[false; 3]), Some(1));
+        started[1] = true;
+        // `custom` must be reverted before `provision_nix`
+        assert_eq!(schedule.next_reverse(&started, &[false, false, true]), None);
+        assert_eq!(
+            schedule.next_reverse(&started, &[false, true, true]),
+            Some(0)
+        );
+    }
+}
@@ -1,13 +1,47 @@
 /*! Configurable knobs and their related errors
 */
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
 
 #[cfg(feature = "cli")]
 use clap::ArgAction;
 use url::Url;
 
+use crate::{
+    action::{
+        base::NixConfigConflictPolicy,
+        common::{BinaryCaches, NixConfigPreset},
+        RetryPolicy, RetryableError,
+    },
+    bundle::{BundleError, EmbeddedNixPackage},
+    credentials::{ensure_no_credentials_in_url, CredentialsError, DownloadCredentials},
+    parse_ssl_cert, CertificateError,
+};
+
 pub const SCRATCH_DIR: &str = "/nix/temp-install-dir";
 
+/// The longest to wait before retrying a failed step, see [`CommonSettings::retry_policy`]
+const RETRY_MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Resolve an absolute `path` inside of `root`, if one is set (see [`CommonSettings::root`])
+///
+/// ```rust
+/// use std::path::{Path, PathBuf};
+/// use nix_installer::settings::rooted;
+///
+/// assert_eq!(rooted(None, "/nix"), PathBuf::from("/nix"));
+/// assert_eq!(rooted(Some(Path::new("/mnt/target")), "/nix"), PathBuf::from("/mnt/target/nix"));
+/// ```
+pub fn rooted(root: Option<&Path>, path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    match root {
+        Some(root) => root.join(path.strip_prefix("/").unwrap_or(path)),
+        None => path.to_path_buf(),
+    }
+}
+
 /// Default [`nix_package_url`](CommonSettings::nix_package_url) for Linux x86_64
 pub const NIX_X64_64_LINUX_URL: &str =
     "https://releases.nixos.org/nix/nix-2.15.0/nix-2.15.0-x86_64-linux.tar.xz";
@@ -24,6 +58,96 @@ pub const NIX_X64_64_DARWIN_URL: &str =
 pub const NIX_AARCH64_DARWIN_URL: &str =
     "https://releases.nixos.org/nix/nix-2.15.0/nix-2.15.0-aarch64-darwin.tar.xz";
 
+/// The known SHA-256 hashes of Nix package URLs, used when [`nix_package_sha256`](CommonSettings::nix_package_sha256)
+/// is not set
+///
+/// Entries come from the `.sha256` files published next to each tarball on `releases.nixos.org`. Every one of the
+/// [`DEFAULT_NIX_PACKAGE_URLS`] must be listed, installing one without a known (or configured) hash is refused. A
+/// package from any other URL which is not listed here (and which has no configured hash) is unpacked without being
+/// verified.
+pub const KNOWN_NIX_PACKAGE_SHA256: &[(&str, &str)] = &[];
+
+/// The default [`nix_package_url`](CommonSettings::nix_package_url) of every supported system
+pub const DEFAULT_NIX_PACKAGE_URLS: &[&str] = &[
+    NIX_X64_64_LINUX_URL,
+    NIX_I686_LINUX_URL,
+    NIX_AARCH64_LINUX_URL,
+    NIX_X64_64_DARWIN_URL,
+    NIX_AARCH64_DARWIN_URL,
+];
+
+/// The known SHA-256 hash of the Nix package at `url`, see [`KNOWN_NIX_PACKAGE_SHA256`]
+pub fn known_nix_package_sha256(url: &Url) -> Option<&'static str> {
+    KNOWN_NIX_PACKAGE_SHA256
+        .iter()
+        .find(|(known, _)| *known == url.as_str())
+        .map(|(_, sha256)| *sha256)
+}
+
+/// Default [`nix_package_url_template`](CommonSettings::nix_package_url_template), where Nix releases are published
+pub const NIX_PACKAGE_URL_TEMPLATE: &str =
+    "https://releases.nixos.org/nix/nix-{version}/nix-{version}-{system}.tar.xz";
+
+/// The Nix system (eg. `x86_64-linux`) of the host, which Nix packages are published for
+pub fn host_nix_system() -> Result<&'static str, InstallSettingsError> {
+    use target_lexicon::{Architecture, OperatingSystem};
+    match (Architecture::host(), OperatingSystem::host()) {
+        #[cfg(target_os = "linux")]
+        (Architecture::X86_64, OperatingSystem::Linux) => Ok("x86_64-linux"),
+        #[cfg(target_os = "linux")]
+        (Architecture::X86_32(_), OperatingSystem::Linux) => Ok("i686-linux"),
+        #[cfg(target_os = "linux")]
+        (Architecture::Aarch64(_), OperatingSystem::Linux) => Ok("aarch64-linux"),
+        #[cfg(target_os = "macos")]
+        (Architecture::X86_64, OperatingSystem::MacOSX { .. })
+        | (Architecture::X86_64, OperatingSystem::Darwin) => Ok("x86_64-darwin"),
+        #[cfg(target_os = "macos")]
+        (Architecture::Aarch64(_), OperatingSystem::MacOSX { .. })
+        | (Architecture::Aarch64(_), OperatingSystem::Darwin) => Ok("aarch64-darwin"),
+        _ => Err(InstallSettingsError::UnsupportedArchitecture(
+            target_lexicon::HOST,
+        )),
+    }
+}
+
+/// The Nix packages of each version of Nix, for each Nix system, see
+/// [`nix_version_manifest`](CommonSettings::nix_version_manifest)
+///
+/// ```rust
+/// use nix_installer::settings::NixVersionManifest;
+///
+/// let manifest: NixVersionManifest = serde_json::from_str(r#"{
+///     "2.15.0": {
+///         "x86_64-linux": {
+///             "url": "https://releases.nixos.org/nix/nix-2.15.0/nix-2.15.0-x86_64-linux.tar.xz"
+///         }
+///     }
+/// }"#)?;
+/// assert_eq!(manifest["2.15.0"]["x86_64-linux"].url.path(), "/nix/nix-2.15.0/nix-2.15.0-x86_64-linux.tar.xz");
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+pub type NixVersionManifest = BTreeMap<String, BTreeMap<String, NixVersionManifestPackage>>;
+
+/// A Nix package listed in a [`NixVersionManifest`]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
+pub struct NixVersionManifestPackage {
+    pub url: Url,
+    /// The SHA-256 hash (in hex) of the package, if known
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// The Nix package to install, see [`CommonSettings::resolve_nix_package`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedNixPackage {
+    /// The version of Nix, if it was selected by version
+    pub version: Option<String>,
+    /// Where the package is fetched from, in the order they are tried
+    pub urls: Vec<Url>,
+    /// The SHA-256 hash the package is verified against, if one is known
+    pub sha256: Option<String>,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum InitSystem {
@@ -131,6 +255,185 @@ pub struct CommonSettings {
     )]
     pub nix_package_url: Url,
 
+    /// The version of Nix to install (eg. `2.15.0`), which selects the package from the `nix-version-manifest` if
+    /// one is set, or else the `nix-package-url-template`
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            env = "NIX_INSTALLER_NIX_VERSION",
+            conflicts_with = "nix_package_url",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_version: Option<String>,
+
+    /// The URL of the Nix package of a `nix-version`, where `{version}` is replaced by the version and `{system}` by
+    /// the Nix system (eg. `x86_64-linux`)
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            default_value = NIX_PACKAGE_URL_TEMPLATE,
+            env = "NIX_INSTALLER_NIX_PACKAGE_URL_TEMPLATE",
+            global = true
+        )
+    )]
+    #[serde(default = "default_nix_package_url_template")]
+    pub nix_package_url_template: String,
+
+    /// A JSON manifest listing the Nix package (`url` and `sha256`) of each Nix system for each version, as in
+    /// `{"2.15.0": {"x86_64-linux": {"url": "...", "sha256": "..."}}}`, which may be a local `file://` URL
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_VERSION_MANIFEST", global = true)
+    )]
+    #[serde(default)]
+    pub nix_version_manifest: Option<Url>,
+
+    /// A mirror of the Nix package to try before the `nix-package-url`, mirrors are tried in the order given and then
+    /// falling back to the next on connection errors, 404s, or checksum failures
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long = "nix-package-mirror",
+            action = ArgAction::Append,
+            value_delimiter = ',',
+            env = "NIX_INSTALLER_NIX_PACKAGE_MIRRORS",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_mirrors: Vec<Url>,
+
+    /// A file listing mirrors of the Nix package (one URL per line, `#` starts a comment), tried after any
+    /// `nix-package-mirror`s
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_MIRROR_FILE", global = true)
+    )]
+    #[serde(default)]
+    pub nix_package_mirror_file: Option<PathBuf>,
+
+    /// The SHA-256 hash (in hex) the Nix package must have, defaults to the known hash of the default package URLs
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_SHA256", global = true)
+    )]
+    pub nix_package_sha256: Option<String>,
+
+    /// A minisign public key (eg. `RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3`) trusted to sign the Nix
+    /// package, if any are set the Nix package must have a detached signature by one of them
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long = "nix-package-trusted-key",
+            action = ArgAction::Append,
+            value_delimiter = ',',
+            env = "NIX_INSTALLER_NIX_PACKAGE_TRUSTED_KEYS",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_trusted_keys: Vec<String>,
+
+    /// The URL of the detached minisign signature of the Nix package, defaults to the package URL with `.minisig`
+    /// appended
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_SIGNATURE_URL", global = true)
+    )]
+    pub nix_package_signature_url: Option<Url>,
+
+    /// A netrc file with the login and password to fetch the Nix package (and its version manifest) with
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_NETRC_FILE", global = true)
+    )]
+    #[serde(default)]
+    pub nix_package_netrc_file: Option<PathBuf>,
+
+    /// The name of an environment variable holding the `Authorization` header (such as `Bearer $TOKEN`) to fetch the
+    /// Nix package with, instead of the `nix-package-netrc-file`
+    ///
+    /// Only the name is kept in the receipt. When `nix-installer` escalates with `sudo` only `NIX_INSTALLER_*`
+    /// environment variables are kept.
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            env = "NIX_INSTALLER_NIX_PACKAGE_AUTHORIZATION_ENV",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_authorization_env: Option<String>,
+
+    /// A PEM file with a client certificate to fetch the Nix package with, for servers requiring mutual TLS
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            env = "NIX_INSTALLER_NIX_PACKAGE_CLIENT_CERT_FILE",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_client_cert_file: Option<PathBuf>,
+
+    /// A PEM file with the private key of the `nix-package-client-cert-file`, if it is not in that file
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            env = "NIX_INSTALLER_NIX_PACKAGE_CLIENT_KEY_FILE",
+            requires = "nix_package_client_cert_file",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_client_key_file: Option<PathBuf>,
+
+    /// The only host the credentials to fetch the Nix package with are sent to, defaults to the host of the first URL
+    /// the package is fetched from
+    ///
+    /// Credentials are never sent over plain `http://`. The Nix version manifest is only fetched with credentials when
+    /// this is set.
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            env = "NIX_INSTALLER_NIX_PACKAGE_CREDENTIALS_HOST",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_package_credentials_host: Option<String>,
+
+    /// A directory to keep verified Nix packages in (by their SHA-256 hash, eg. `/var/cache/nix-installer`), so
+    /// reinstalling does not fetch the package again, what was kept is left alone by `uninstall` unless `--purge-cache` is passed
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_CACHE_DIR", global = true)
+    )]
+    #[serde(default)]
+    pub nix_package_cache_dir: Option<PathBuf>,
+
+    /// Never fetch the Nix package, failing unless it is in the `nix-package-cache-dir`
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            action(ArgAction::SetTrue),
+            default_value = "false",
+            env = "NIX_INSTALLER_OFFLINE",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub offline: bool,
+
     /// The proxy to use (if any), valid proxy bases are `https://$URL`, `http://$URL` and `socks5://$URL`
     #[cfg_attr(feature = "cli", clap(long, env = "NIX_INSTALLER_PROXY"))]
     pub proxy: Option<Url>,
@@ -143,6 +446,97 @@ pub struct CommonSettings {
     #[cfg_attr(feature = "cli", clap(long, action = ArgAction::Append, num_args = 0.., env = "NIX_INSTALLER_EXTRA_CONF", global = true))]
     pub extra_conf: Vec<String>,
 
+    /// A binary cache to add to `/etc/nix/nix.conf` (as `extra-substituters`), with a `https://`, `http://`,
+    /// `file://`, `s3://`, `ssh://` or `ssh-ng://` URL
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long = "substituter",
+            action = ArgAction::Append,
+            num_args = 0..,
+            env = "NIX_INSTALLER_SUBSTITUTERS",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub substituters: Vec<Url>,
+
+    /// A public key to trust the signatures of in `/etc/nix/nix.conf` (as `extra-trusted-public-keys`), in the form
+    /// `name:base64`
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long = "trusted-public-key",
+            action = ArgAction::Append,
+            num_args = 0..,
+            env = "NIX_INSTALLER_TRUSTED_PUBLIC_KEYS",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub trusted_public_keys: Vec<String>,
+
+    /// Fetch the `nix-cache-info` of each `https://`, `http://` or `file://` substituter while planning, failing if
+    /// one is unreachable
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            action(ArgAction::SetTrue),
+            default_value = "false",
+            env = "NIX_INSTALLER_PROBE_SUBSTITUTERS",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub probe_substituters: bool,
+
+    /// Which defaults are injected into `/etc/nix/nix.conf` (see `--explain` for each setting and why it is there), a
+    /// setting given with `--extra-conf` is kept instead, though lists are appended to
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            value_enum,
+            default_value_t = NixConfigPreset::default(),
+            env = "NIX_INSTALLER_NIX_CONF_PRESET",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_conf_preset: NixConfigPreset,
+
+    /// What to do when a setting for `/etc/nix/nix.conf` which is not a list (unlike `substituters` or any `extra-*`
+    /// setting, which are merged) already has a different value
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            value_enum,
+            default_value_t = NixConfigConflictPolicy::default(),
+            env = "NIX_INSTALLER_NIX_CONF_CONFLICT_POLICY",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_conf_conflict_policy: NixConfigConflictPolicy,
+
+    /// Place the Nix configuration in its own file, `/etc/nix/nix.installer.conf`, which `/etc/nix/nix.conf` only
+    /// gains an `!include` of (at its start, so the settings of `nix.conf` take precedence, though lists are only
+    /// appended to)
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            action(ArgAction::SetTrue),
+            default_value = "false",
+            env = "NIX_INSTALLER_NIX_CONF_DROP_IN",
+            global = true
+        )
+    )]
+    #[serde(default)]
+    pub nix_conf_drop_in: bool,
+
     /// If `nix-installer` should forcibly recreate files it finds existing
     #[cfg_attr(
         feature = "cli",
@@ -156,6 +550,59 @@ pub struct CommonSettings {
     )]
     pub force: bool,
 
+    /// Install into an alternate root directory (eg. a mounted filesystem image) instead of `/`
+    ///
+    /// Users and groups are created by editing the `etc/passwd` and `etc/group` files of the root, and
+    /// init services are enabled offline, they are started when the root is booted.
+    #[cfg_attr(feature = "cli", clap(long, env = "NIX_INSTALLER_ROOT", global = true))]
+    pub root: Option<PathBuf>,
+
+    /// The most times a step which failed for a transient reason is attempted, `1` disables retrying
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            default_value_t = 3,
+            value_parser = clap::value_parser!(u32).range(1..),
+            env = "NIX_INSTALLER_RETRY_ATTEMPTS",
+            global = true
+        )
+    )]
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+
+    /// How long to wait before retrying a failed step the first time, in milliseconds, doubling with each retry
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            default_value_t = 1_000,
+            env = "NIX_INSTALLER_RETRY_BACKOFF_MS",
+            global = true
+        )
+    )]
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+
+    /// The kinds of failures which are retried, only fetching Nix (`download`) by default
+    ///
+    /// Retrying a failed `command` (or `command-output`) executes its whole step again, which is only safe for steps
+    /// that can be executed more than once.
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            num_args = 0..,
+            default_values_t = default_retry_on(),
+            env = "NIX_INSTALLER_RETRY_ON",
+            global = true
+        )
+    )]
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<RetryableError>,
+
     #[cfg(feature = "diagnostics")]
     /// The URL or file path for an installation diagnostic to be sent
     ///
@@ -185,51 +632,67 @@ pub struct CommonSettings {
     pub diagnostic_endpoint: Option<String>,
 }
 
+/// The default [`nix_package_url`](CommonSettings::nix_package_url) for the host's Architecture & Operating System
+fn default_nix_package_url() -> Result<&'static str, InstallSettingsError> {
+    use target_lexicon::{Architecture, OperatingSystem};
+    match (Architecture::host(), OperatingSystem::host()) {
+        #[cfg(target_os = "linux")]
+        (Architecture::X86_64, OperatingSystem::Linux) => Ok(NIX_X64_64_LINUX_URL),
+        #[cfg(target_os = "linux")]
+        (Architecture::X86_32(_), OperatingSystem::Linux) => Ok(NIX_I686_LINUX_URL),
+        #[cfg(target_os = "linux")]
+        (Architecture::Aarch64(_), OperatingSystem::Linux) => Ok(NIX_AARCH64_LINUX_URL),
+        #[cfg(target_os = "macos")]
+        (Architecture::X86_64, OperatingSystem::MacOSX { .. })
+        | (Architecture::X86_64, OperatingSystem::Darwin) => Ok(NIX_X64_64_DARWIN_URL),
+        #[cfg(target_os = "macos")]
+        (Architecture::Aarch64(_), OperatingSystem::MacOSX { .. })
+        | (Architecture::Aarch64(_), OperatingSystem::Darwin) => Ok(NIX_AARCH64_DARWIN_URL),
+        _ => Err(InstallSettingsError::UnsupportedArchitecture(
+            target_lexicon::HOST,
+        )),
+    }
+}
+
 impl CommonSettings {
     /// The default settings for the given Architecture & Operating System
     pub async fn default() -> Result<Self, InstallSettingsError> {
-        let url;
-
-        use target_lexicon::{Architecture, OperatingSystem};
-        match (Architecture::host(), OperatingSystem::host()) {
-            #[cfg(target_os = "linux")]
-            (Architecture::X86_64, OperatingSystem::Linux) => {
-                url = NIX_X64_64_LINUX_URL;
-            },
-            #[cfg(target_os = "linux")]
-            (Architecture::X86_32(_), OperatingSystem::Linux) => {
-                url = NIX_I686_LINUX_URL;
-            },
-            #[cfg(target_os = "linux")]
-            (Architecture::Aarch64(_), OperatingSystem::Linux) => {
-                url = NIX_AARCH64_LINUX_URL;
-            },
-            #[cfg(target_os = "macos")]
-            (Architecture::X86_64, OperatingSystem::MacOSX { .. })
-            | (Architecture::X86_64, OperatingSystem::Darwin) => {
-                url = NIX_X64_64_DARWIN_URL;
-            },
-            #[cfg(target_os = "macos")]
-            (Architecture::Aarch64(_), OperatingSystem::MacOSX { .. })
-            | (Architecture::Aarch64(_), OperatingSystem::Darwin) => {
-                url = NIX_AARCH64_DARWIN_URL;
-            },
-            _ => {
-                return Err(InstallSettingsError::UnsupportedArchitecture(
-                    target_lexicon::HOST,
-                ))
-            },
-        };
+        let url = default_nix_package_url()?;
 
         Ok(Self {
             modify_profile: true,
             nix_build_group_name: String::from("nixbld"),
             nix_build_group_id: 30_000,
             nix_package_url: url.parse()?,
+            nix_version: Default::default(),
+            nix_package_url_template: default_nix_package_url_template(),
+            nix_version_manifest: Default::default(),
+            nix_package_mirrors: Default::default(),
+            nix_package_mirror_file: Default::default(),
+            nix_package_sha256: Default::default(),
+            nix_package_trusted_keys: Default::default(),
+            nix_package_signature_url: Default::default(),
+            nix_package_netrc_file: Default::default(),
+            nix_package_authorization_env: Default::default(),
+            nix_package_client_cert_file: Default::default(),
+            nix_package_client_key_file: Default::default(),
+            nix_package_credentials_host: Default::default(),
+            nix_package_cache_dir: Default::default(),
+            offline: false,
             proxy: Default::default(),
             extra_conf: Default::default(),
+            substituters: Default::default(),
+            trusted_public_keys: Default::default(),
+            probe_substituters: false,
+            nix_conf_preset: Default::default(),
+            nix_conf_conflict_policy: Default::default(),
+            nix_conf_drop_in: false,
             force: false,
+            root: Default::default(),
             ssl_cert_file: Default::default(),
+            retry_attempts: default_retry_attempts(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            retry_on: default_retry_on(),
             #[cfg(feature = "diagnostics")]
             diagnostic_endpoint: Some("https://install.determinate.systems/nix/diagnostic".into()),
         })
@@ -242,10 +705,35 @@ impl CommonSettings {
             nix_build_group_name,
             nix_build_group_id,
             nix_package_url,
+            nix_version,
+            nix_package_url_template,
+            nix_version_manifest,
+            nix_package_mirrors,
+            nix_package_mirror_file,
+            nix_package_sha256,
+            nix_package_trusted_keys,
+            nix_package_signature_url,
+            nix_package_netrc_file,
+            nix_package_authorization_env,
+            nix_package_client_cert_file,
+            nix_package_client_key_file,
+            nix_package_credentials_host,
+            nix_package_cache_dir,
+            offline,
             proxy,
             extra_conf,
+            substituters,
+            trusted_public_keys,
+            probe_substituters,
+            nix_conf_preset,
+            nix_conf_conflict_policy,
+            nix_conf_drop_in,
             force,
+            root,
             ssl_cert_file,
+            retry_attempts,
+            retry_backoff_ms,
+            retry_on,
             #[cfg(feature = "diagnostics")]
             diagnostic_endpoint,
         } = self;
@@ -267,10 +755,95 @@ impl CommonSettings {
             "nix_package_url".into(),
             serde_json::to_value(nix_package_url)?,
         );
+        map.insert("nix_version".into(), serde_json::to_value(nix_version)?);
+        map.insert(
+            "nix_package_url_template".into(),
+            serde_json::to_value(nix_package_url_template)?,
+        );
+        map.insert(
+            "nix_version_manifest".into(),
+            serde_json::to_value(nix_version_manifest)?,
+        );
+        map.insert(
+            "nix_package_mirrors".into(),
+            serde_json::to_value(nix_package_mirrors)?,
+        );
+        map.insert(
+            "nix_package_mirror_file".into(),
+            serde_json::to_value(nix_package_mirror_file)?,
+        );
+        map.insert(
+            "nix_package_sha256".into(),
+            serde_json::to_value(nix_package_sha256)?,
+        );
+        map.insert(
+            "nix_package_trusted_keys".into(),
+            serde_json::to_value(nix_package_trusted_keys)?,
+        );
+        map.insert(
+            "nix_package_signature_url".into(),
+            serde_json::to_value(nix_package_signature_url)?,
+        );
+        map.insert(
+            "nix_package_netrc_file".into(),
+            serde_json::to_value(nix_package_netrc_file)?,
+        );
+        map.insert(
+            "nix_package_authorization_env".into(),
+            serde_json::to_value(nix_package_authorization_env)?,
+        );
+        map.insert(
+            "nix_package_client_cert_file".into(),
+            serde_json::to_value(nix_package_client_cert_file)?,
+        );
+        map.insert(
+            "nix_package_client_key_file".into(),
+            serde_json::to_value(nix_package_client_key_file)?,
+        );
+        map.insert(
+            "nix_package_credentials_host".into(),
+            serde_json::to_value(nix_package_credentials_host)?,
+        );
+        map.insert(
+            "nix_package_cache_dir".into(),
+            serde_json::to_value(nix_package_cache_dir)?,
+        );
+        map.insert("offline".into(), serde_json::to_value(offline)?);
         map.insert("proxy".into(), serde_json::to_value(proxy)?);
         map.insert("ssl_cert_file".into(), serde_json::to_value(ssl_cert_file)?);
         map.insert("extra_conf".into(), serde_json::to_value(extra_conf)?);
+        map.insert("substituters".into(), serde_json::to_value(substituters)?);
+        map.insert(
+            "trusted_public_keys".into(),
+            serde_json::to_value(trusted_public_keys)?,
+        );
+        map.insert(
+            "probe_substituters".into(),
+            serde_json::to_value(probe_substituters)?,
+        );
+        map.insert(
+            "nix_conf_preset".into(),
+            serde_json::to_value(nix_conf_preset)?,
+        );
+        map.insert(
+            "nix_conf_conflict_policy".into(),
+            serde_json::to_value(nix_conf_conflict_policy)?,
+        );
+        map.insert(
+            "nix_conf_drop_in".into(),
+            serde_json::to_value(nix_conf_drop_in)?,
+        );
         map.insert("force".into(), serde_json::to_value(force)?);
+        map.insert("root".into(), serde_json::to_value(root)?);
+        map.insert(
+            "retry_attempts".into(),
+            serde_json::to_value(retry_attempts)?,
+        );
+        map.insert(
+            "retry_backoff_ms".into(),
+            serde_json::to_value(retry_backoff_ms)?,
+        );
+        map.insert("retry_on".into(), serde_json::to_value(retry_on)?);
 
         #[cfg(feature = "diagnostics")]
         map.insert(
@@ -280,6 +853,218 @@ impl CommonSettings {
 
         Ok(map)
     }
+
+    /// Resolve an absolute `path` inside of the configured [`root`](CommonSettings::root)
+    pub fn rooted(&self, path: impl AsRef<Path>) -> PathBuf {
+        rooted(self.root.as_deref(), path)
+    }
+
+    /// Resolve which Nix package to install, from the [`nix_version`](CommonSettings::nix_version) if it is set,
+    /// otherwise the [`nix_package_url`](CommonSettings::nix_package_url)
+    ///
+    /// A `nix-installer` with a Nix package embedded into it (see [`bundle`](crate::bundle)) only ever installs that
+    /// package, and fails if another source for the package was given.
+    ///
+    /// The URLs of the [`ResolvedNixPackage`] are in the order they are tried: the
+    /// [`nix_package_mirrors`](CommonSettings::nix_package_mirrors), those in the
+    /// [`nix_package_mirror_file`](CommonSettings::nix_package_mirror_file), then the resolved URL. Its hash is the
+    /// configured [`nix_package_sha256`](CommonSettings::nix_package_sha256), or else the one listed in the
+    /// [`nix_version_manifest`](CommonSettings::nix_version_manifest), or else the known hash of the resolved URL.
+    pub async fn resolve_nix_package(&self) -> Result<ResolvedNixPackage, InstallSettingsError> {
+        if let Some(embedded) = EmbeddedNixPackage::current().await? {
+            return self.resolve_embedded_nix_package(embedded);
+        }
+        let (url, listed_sha256) = match &self.nix_version {
+            None => (self.nix_package_url.clone(), None),
+            Some(version) => {
+                let system = host_nix_system()?;
+                match &self.nix_version_manifest {
+                    Some(manifest) => {
+                        let manifest = self.load_nix_version_manifest(manifest).await?;
+                        let package = manifest
+                            .get(version)
+                            .and_then(|systems| systems.get(system))
+                            .ok_or_else(|| InstallSettingsError::NixVersionNotInManifest {
+                                version: version.clone(),
+                                system: system.to_string(),
+                            })?;
+                        (package.url.clone(), package.sha256.clone())
+                    },
+                    None => (
+                        self.nix_package_url_template
+                            .replace("{version}", version)
+                            .replace("{system}", system)
+                            .parse()?,
+                        None,
+                    ),
+                }
+            },
+        };
+        let sha256 = self
+            .nix_package_sha256
+            .clone()
+            .or(listed_sha256)
+            .or_else(|| known_nix_package_sha256(&url).map(Into::into));
+        if sha256.is_none() && DEFAULT_NIX_PACKAGE_URLS.contains(&url.as_str()) {
+            return Err(InstallSettingsError::UnknownDefaultNixPackageSha256(url));
+        }
+
+        let mut urls = self.nix_package_mirrors.clone();
+        if let Some(mirror_file) = &self.nix_package_mirror_file {
+            let mirrors = tokio::fs::read_to_string(mirror_file)
+                .await
+                .map_err(|e| InstallSettingsError::MirrorFile(mirror_file.clone(), e))?;
+            for line in mirrors.lines() {
+                let mirror = line.split('#').next().unwrap_or_default().trim();
+                if mirror.is_empty() {
+                    continue;
+                }
+                urls.push(mirror.parse()?);
+            }
+        }
+        urls.push(url);
+        let mut deduplicated = Vec::with_capacity(urls.len());
+        for url in urls {
+            if !deduplicated.contains(&url) {
+                deduplicated.push(url);
+            }
+        }
+
+        Ok(ResolvedNixPackage {
+            version: self.nix_version.clone(),
+            urls: deduplicated,
+            sha256,
+        })
+    }
+
+    /// Resolve the Nix package embedded into `nix-installer`, failing if another source for the package was given
+    fn resolve_embedded_nix_package(
+        &self,
+        embedded: EmbeddedNixPackage,
+    ) -> Result<ResolvedNixPackage, InstallSettingsError> {
+        let mut conflicts = Vec::new();
+        if self.nix_package_url.as_str() != default_nix_package_url()? {
+            conflicts.push("--nix-package-url");
+        }
+        if self.nix_version.is_some() && self.nix_version != embedded.version {
+            conflicts.push("--nix-version");
+        }
+        if !self.nix_package_mirrors.is_empty() {
+            conflicts.push("--nix-package-mirror");
+        }
+        if self.nix_package_mirror_file.is_some() {
+            conflicts.push("--nix-package-mirror-file");
+        }
+        if !conflicts.is_empty() {
+            return Err(InstallSettingsError::EmbeddedNixPackageConflict(conflicts));
+        }
+
+        Ok(ResolvedNixPackage {
+            version: embedded.version,
+            urls: vec![EmbeddedNixPackage::url()],
+            sha256: Some(self.nix_package_sha256.clone().unwrap_or(embedded.sha256)),
+        })
+    }
+
+    /// Where the credentials to fetch the Nix package with come from, sent only to the
+    /// [`nix_package_credentials_host`](CommonSettings::nix_package_credentials_host) or else the host of `primary`
+    pub fn nix_package_credentials(&self, primary: Option<&Url>) -> DownloadCredentials {
+        DownloadCredentials {
+            host: self
+                .nix_package_credentials_host
+                .clone()
+                .or_else(|| primary.and_then(Url::host_str).map(Into::into)),
+            netrc_file: self.nix_package_netrc_file.clone(),
+            authorization_env: self.nix_package_authorization_env.clone(),
+            client_cert_file: self.nix_package_client_cert_file.clone(),
+            client_key_file: self.nix_package_client_key_file.clone(),
+        }
+    }
+
+    /// The binary caches to add to the Nix configuration, probed through the same proxy and SSL cert as Nix is fetched
+    /// with
+    pub fn binary_caches(&self) -> BinaryCaches {
+        BinaryCaches {
+            substituters: self.substituters.clone(),
+            trusted_public_keys: self.trusted_public_keys.clone(),
+            probe: self.probe_substituters,
+            proxy: self.proxy.clone(),
+            ssl_cert_file: self.ssl_cert_file.clone(),
+        }
+    }
+
+    /// Load the [`NixVersionManifest`] at `url`, which may be a local file
+    async fn load_nix_version_manifest(
+        &self,
+        url: &Url,
+    ) -> Result<NixVersionManifest, InstallSettingsError> {
+        let buf = match url.scheme() {
+            "https" | "http" => {
+                let mut buildable_client = reqwest::Client::builder();
+                if let Some(proxy) = &self.proxy {
+                    buildable_client =
+                        buildable_client.proxy(reqwest::Proxy::all(proxy.clone()).map_err(
+                            |e| InstallSettingsError::NixVersionManifest(url.clone(), e),
+                        )?);
+                }
+                if let Some(ssl_cert_file) = &self.ssl_cert_file {
+                    buildable_client =
+                        buildable_client.add_root_certificate(parse_ssl_cert(ssl_cert_file).await?);
+                }
+                let credentials = self.nix_package_credentials(None);
+                ensure_no_credentials_in_url(url)?;
+                let buildable_client = credentials.client(url, buildable_client).await?;
+                let client = buildable_client
+                    .build()
+                    .map_err(|e| InstallSettingsError::NixVersionManifest(url.clone(), e))?;
+                let request = credentials.authorize(url, client.get(url.clone())).await?;
+                let fetched =
+                    async { request.send().await?.error_for_status()?.bytes().await }.await;
+                fetched
+                    .map_err(|e| InstallSettingsError::NixVersionManifest(url.clone(), e))?
+                    .to_vec()
+            },
+            "file" => {
+                let path = PathBuf::from(url.path());
+                tokio::fs::read(&path)
+                    .await
+                    .map_err(|e| InstallSettingsError::ReadNixVersionManifest(path, e))?
+            },
+            _ => {
+                return Err(InstallSettingsError::UnknownNixVersionManifestScheme(
+                    url.clone(),
+                ))
+            },
+        };
+        Ok(serde_json::from_slice(&buf)?)
+    }
+
+    /// The [`RetryPolicy`] for the steps of the install, suitable for
+    /// [`Planner::retry_policy`](crate::planner::Planner::retry_policy)
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_attempts.max(1),
+            initial_backoff_ms: self.retry_backoff_ms,
+            max_backoff_ms: RETRY_MAX_BACKOFF_MS.max(self.retry_backoff_ms),
+            retry_on: self.retry_on.clone(),
+        }
+    }
+}
+
+fn default_nix_package_url_template() -> String {
+    NIX_PACKAGE_URL_TEMPLATE.into()
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    1_000
+}
+
+fn default_retry_on() -> Vec<RetryableError> {
+    vec![RetryableError::Download]
 }
 #[cfg(target_os = "linux")]
 async fn linux_detect_systemd_started() -> bool {
@@ -418,6 +1203,35 @@ pub enum InstallSettingsError {
     ),
     #[error("No supported init system found")]
     InitNotSupported,
+    /// Fetching the [`nix_version_manifest`](CommonSettings::nix_version_manifest)
+    #[error("Fetching the Nix version manifest `{0}`")]
+    NixVersionManifest(Url, #[source] reqwest::Error),
+    /// Reading the [`nix_version_manifest`](CommonSettings::nix_version_manifest) from a local file
+    #[error("Reading the Nix version manifest `{0}`")]
+    ReadNixVersionManifest(PathBuf, #[source] std::io::Error),
+    #[error("Unknown Nix version manifest scheme for `{0}`, `file://`, `https://` and `http://` supported")]
+    UnknownNixVersionManifestScheme(Url),
+    /// The [`nix_version`](CommonSettings::nix_version) is not listed for the host in the
+    /// [`nix_version_manifest`](CommonSettings::nix_version_manifest)
+    #[error("The Nix version manifest does not list Nix `{version}` for `{system}`")]
+    NixVersionNotInManifest { version: String, system: String },
+    /// Reading the credentials to fetch the Nix version manifest with
+    #[error(transparent)]
+    Credentials(#[from] CredentialsError),
+    /// Reading the Nix package embedded into `nix-installer`
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+    /// Reading the [`ssl_cert_file`](CommonSettings::ssl_cert_file)
+    #[error(transparent)]
+    Certificate(#[from] CertificateError),
+    /// Reading the [`nix_package_mirror_file`](CommonSettings::nix_package_mirror_file)
+    #[error("Reading the Nix package mirror file `{0}`")]
+    MirrorFile(PathBuf, #[source] std::io::Error),
+    /// A source for the Nix package was given to a `nix-installer` with a Nix package embedded into it
+    #[error("This `nix-installer` has a Nix package embedded into it, and only installs that package, so `{}` cannot be used", .0.join("`, `"))]
+    EmbeddedNixPackageConflict(Vec<&'static str>),
+    #[error("No SHA-256 hash is known for the default Nix package `{0}`, so it cannot be verified; set `--nix-package-sha256` to the hash published next to it")]
+    UnknownDefaultNixPackageSha256(Url),
 }
 
 #[cfg(feature = "diagnostics")]
@@ -427,3 +1241,115 @@ impl crate::diagnostics::ErrorDiagnostic for InstallSettingsError {
         return static_str.to_string();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolves_nix_version() -> eyre::Result<()> {
+        let system = host_nix_system()?;
+        let mut settings = CommonSettings::default().await?;
+        // The default package is never resolved without a hash to verify it with
+        match settings.resolve_nix_package().await {
+            Ok(package) => assert!(package.sha256.is_some()),
+            Err(err) => assert!(matches!(
+                err,
+                InstallSettingsError::UnknownDefaultNixPackageSha256(_)
+            )),
+        }
+        settings.nix_package_sha256 = Some("b".repeat(64));
+        let package = settings.resolve_nix_package().await?;
+        assert_eq!(package.urls, vec![settings.nix_package_url.clone()]);
+        assert_eq!(package.version, None);
+        settings.nix_package_sha256 = None;
+
+        settings.nix_version = Some("2.17.0".into());
+        let package = settings.resolve_nix_package().await?;
+        assert_eq!(
+            package.urls,
+            vec![Url::parse(&format!(
+                "https://releases.nixos.org/nix/nix-2.17.0/nix-2.17.0-{system}.tar.xz"
+            ))?]
+        );
+        assert_eq!(package.version.as_deref(), Some("2.17.0"));
+
+        // A manifest can be read from a local file, and lists the hash of each package
+        let temp_dir = tempfile::tempdir()?;
+        let manifest = temp_dir.path().join("manifest.json");
+        let sha256 = "a".repeat(64);
+        tokio::fs::write(
+            &manifest,
+            serde_json::to_vec(&serde_json::json!({
+                "2.17.0": { system: { "url": "https://mirror.example/nix-2.17.0.tar.zst", "sha256": sha256 } },
+            }))?,
+        )
+        .await?;
+        settings.nix_version_manifest = Some(Url::from_file_path(&manifest).unwrap());
+        settings.nix_package_mirrors = vec![Url::parse("https://mirror.example/nix.tar.xz")?];
+        let package = settings.resolve_nix_package().await?;
+        assert_eq!(
+            package.urls,
+            vec![
+                Url::parse("https://mirror.example/nix.tar.xz")?,
+                Url::parse("https://mirror.example/nix-2.17.0.tar.zst")?,
+            ]
+        );
+        assert_eq!(package.sha256, Some(sha256));
+
+        settings.nix_version = Some("2.0.0".into());
+        assert!(matches!(
+            settings.resolve_nix_package().await,
+            Err(InstallSettingsError::NixVersionNotInManifest { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "KNOWN_NIX_PACKAGE_SHA256 still needs the published `.sha256` of each default package"]
+    fn knows_sha256_of_default_nix_packages() -> eyre::Result<()> {
+        for url in DEFAULT_NIX_PACKAGE_URLS {
+            let sha256 = known_nix_package_sha256(&Url::parse(url)?)
+                .ok_or_else(|| eyre::eyre!("No known SHA-256 hash of `{url}`"))?;
+            assert_eq!(sha256.len(), 64, "`{url}`");
+            assert!(sha256.chars().all(|c| c.is_ascii_hexdigit()), "`{url}`");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn embedded_nix_package_conflicts_with_other_sources() -> eyre::Result<()> {
+        let embedded = EmbeddedNixPackage {
+            version: Some("2.15.0".into()),
+            sha256: "a".repeat(64),
+            offset: 0,
+            len: 0,
+        };
+        let mut settings = CommonSettings::default().await?;
+        let package = settings.resolve_embedded_nix_package(embedded.clone())?;
+        assert_eq!(package.urls, vec![EmbeddedNixPackage::url()]);
+        assert_eq!(package.sha256, Some(embedded.sha256.clone()));
+
+        // Naming the version the package already is does not conflict
+        settings.nix_version = Some("2.15.0".into());
+        settings.resolve_embedded_nix_package(embedded.clone())?;
+
+        settings.nix_version = Some("2.17.0".into());
+        settings.nix_package_url = Url::parse("https://mirror.example/nix.tar.xz")?;
+        settings.nix_package_mirrors = vec![Url::parse("https://mirror.example/nix.tar.xz")?];
+        settings.nix_package_mirror_file = Some("mirrors.txt".into());
+        match settings.resolve_embedded_nix_package(embedded) {
+            Err(InstallSettingsError::EmbeddedNixPackageConflict(conflicts)) => assert_eq!(
+                conflicts,
+                vec![
+                    "--nix-package-url",
+                    "--nix-version",
+                    "--nix-package-mirror",
+                    "--nix-package-mirror-file"
+                ]
+            ),
+            other => panic!("Expected a conflict, got {other:?}"),
+        }
+        Ok(())
+    }
+}
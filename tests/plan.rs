@@ -3,6 +3,10 @@ use nix_installer::InstallPlan;
 #[cfg(target_os = "linux")]
 const LINUX: &str = include_str!("./fixtures/linux/linux.json");
 #[cfg(target_os = "linux")]
+const LINUX_0_7_0: &str = include_str!("./fixtures/linux/linux-0.7.0.json");
+#[cfg(target_os = "linux")]
+const LINUX_0_8_0: &str = include_str!("./fixtures/linux/linux-0.8.0.json");
+#[cfg(target_os = "linux")]
 const STEAM_DECK: &str = include_str!("./fixtures/linux/steam-deck.json");
 #[cfg(target_os = "macos")]
 const MACOS: &str = include_str!("./fixtures/macos/macos.json");
@@ -16,6 +20,34 @@ fn plan_compat_linux() -> eyre::Result<()> {
     Ok(())
 }
 
+// Ensure plans from previous versions are migrated
+// These fixtures should never be updated, instead add a new fixture for each released version.
+#[cfg(target_os = "linux")]
+#[test]
+fn plan_compat_linux_0_7_0() -> eyre::Result<()> {
+    let plan: InstallPlan = serde_json::from_str(LINUX_0_7_0)?;
+    let migrated = serde_json::to_value(&plan)?;
+    assert_eq!(migrated["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        migrated["actions"][1]["action"]["delete_users_in_group"]["action"]["delete_users"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+    Ok(())
+}
+
+// Ensure plans from previous versions are migrated
+// These fixtures should never be updated, instead add a new fixture for each released version.
+#[cfg(target_os = "linux")]
+#[test]
+fn plan_compat_linux_0_8_0() -> eyre::Result<()> {
+    let plan: InstallPlan = serde_json::from_str(LINUX_0_8_0)?;
+    let migrated = serde_json::to_value(&plan)?;
+    assert_eq!(migrated["version"], env!("CARGO_PKG_VERSION"));
+    Ok(())
+}
+
 // Ensure existing plans still parse
 // If this breaks and you need to update the fixture, disable these tests, bump `nix_installer` to a new version, and update the plans.
 #[cfg(target_os = "linux")]